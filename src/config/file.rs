@@ -1,33 +1,67 @@
 use std::path::Path;
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use config::Config;
 use log::info;
 
 use crate::config::AppConfig;
+use crate::config::template::get_template_config;
+use crate::config::validate::{get_missing_keys, get_unknown_keys, validate_config};
 
+/// Load config, unknown keys and invalid values are reported all together.
 pub fn load_config_from_file(config_path: &Path) -> anyhow::Result<AppConfig> {
+    let (config, problems) = check_config_file(config_path)?;
+
+    if !problems.is_empty() {
+        return Err(anyhow!("invalid config file '{}'\n - {}", config_path.display(), problems.join("\n - ")))
+    }
+
+    info!("config:");
+    info!("{}", config);
+
+    Ok(config)
+}
+
+/// Parse config file and find its problems: unknown keys (typos) and invalid values, e.g. urls.
+/// Error is returned if file can't be parsed at all.
+pub fn check_config_file(config_path: &Path) -> anyhow::Result<(AppConfig, Vec<String>)> {
     info!("load config from file: '{}'", config_path.display());
 
     let config_path = format!("{}", config_path.display());
 
     let settings = Config::builder()
         .add_source(config::File::with_name(&config_path))
-        .build().context("unable to load app config from file")?;
+        .build().context(format!("unable to load app config from file '{config_path}'"))?;
 
-    let config = settings.try_deserialize::<AppConfig>()?;
+    let config_keys: serde_json::Value = settings.clone().try_deserialize()
+        .context(format!("unable to read config file '{config_path}'"))?;
 
-    info!("config:");
-    info!("{}", config);
+    let known_keys = serde_json::to_value(get_template_config()).context("unable to serialize config structure")?;
 
-    Ok(config)
+    let mut problems = get_unknown_keys(&config_keys, &known_keys);
+
+    let config = match settings.try_deserialize::<AppConfig>() {
+        Ok(config) => config,
+        Err(e) => {
+            problems.append(&mut get_missing_keys(&config_keys));
+
+            let message = problems.iter().fold(format!("invalid config file '{config_path}'"),
+                                               |message, problem| format!("{message}\n - {problem}"));
+
+            return Err(anyhow::Error::new(e).context(message))
+        }
+    };
+
+    problems.append(&mut validate_config(&config));
+
+    Ok((config, problems))
 }
 
 #[cfg(test)]
 mod tests {
     use std::path::Path;
 
-    use crate::config::{AppConfig, ErrorHandlersConfig, InstanceConfig, MigrationConfig};
+    use crate::config::{AppConfig, ArchivedProjectsPolicy, CollisionsConfig, ErrorHandlersConfig, InstanceConfig, LogFormat, MetricsConfig, MigrationConfig, MirrorConfig, PersonalProjectsConfig, PhasesConfig, UsersConfig};
     use crate::config::file::load_config_from_file;
     use crate::tests::init_logging;
 
@@ -41,8 +75,12 @@ mod tests {
         let expected_config = AppConfig {
             log_level: "debug".to_string(),
 
+            log_format: LogFormat::Text,
+
             git_bin_path: "/usr/bin/git".to_string(),
 
+            tmp_dir: "gmt-tmp".to_string(),
+
             source: InstanceConfig {
                 public_url: "https://old-gitlab.company.com".to_string(),
                 git_url: "ssh://old-gitlab.company.com:2222".to_string(),
@@ -57,11 +95,22 @@ mod tests {
 
             migration: MigrationConfig {
                 ignore_empty_repos: true,
+                collisions: CollisionsConfig::default(),
+                personal_projects: PersonalProjectsConfig::default(),
+                archived: ArchivedProjectsPolicy::Migrate,
             },
 
             error_handlers: ErrorHandlersConfig {
                 remove_target_repo_after_clone_error: true,
             },
+
+            mirror: MirrorConfig::default(),
+
+            users: UsersConfig::default(),
+
+            phases: PhasesConfig::default(),
+
+            metrics: MetricsConfig::default(),
         };
 
         assert_eq!(expected_config, config)
@@ -1,30 +1,85 @@
+use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 pub mod file;
+pub mod template;
+pub mod validate;
 
-#[derive(Deserialize,Debug,Clone,PartialEq)]
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub struct AppConfig {
+    #[serde(default = "default_log_level")]
     pub log_level: String,
+
+    /// Format of `gmt.log` lines
+    #[serde(default)]
+    pub log_format: LogFormat,
+
+    /// Git binary, found in `PATH` by default
+    #[serde(default = "default_git_bin_path")]
     pub git_bin_path: String,
+
+    /// Directory for temporary repository clones
+    #[serde(default = "default_tmp_dir")]
+    pub tmp_dir: String,
+
     pub source: InstanceConfig,
     pub target: InstanceConfig,
 
+    #[serde(default)]
     pub migration: MigrationConfig,
 
-    pub error_handlers: ErrorHandlersConfig
+    #[serde(default)]
+    pub error_handlers: ErrorHandlersConfig,
+
+    #[serde(default)]
+    pub mirror: MirrorConfig,
+
+    #[serde(default)]
+    pub users: UsersConfig,
+
+    #[serde(default)]
+    pub phases: PhasesConfig,
+
+    #[serde(default)]
+    pub metrics: MetricsConfig,
 }
 
 impl Display for AppConfig {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "log-level: {}, git-bin-path: '{}', source: {}, target: {}, {}, error-handlers: {}",
-               self.log_level, self.git_bin_path, self.source, self.target, self.migration, self.error_handlers)
+        write!(f, "log-level: {}, log-format: {:?}, git-bin-path: '{}', tmp-dir: '{}', source: {}, target: {}, {}, \
+                   error-handlers: {}, mirror: {}, users: {}, phases: {}, metrics: {}",
+               self.log_level, self.log_format, self.git_bin_path, self.tmp_dir, self.source, self.target, self.migration,
+               self.error_handlers, self.mirror, self.users, self.phases, self.metrics)
     }
 }
 
-#[derive(Deserialize,Debug,Clone,PartialEq)]
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_git_bin_path() -> String {
+    "git".to_string()
+}
+
+fn default_tmp_dir() -> String {
+    "gmt-tmp".to_string()
+}
+
+#[derive(Deserialize,Serialize,Debug,Clone,Copy,PartialEq,Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// Human readable lines
+    #[default]
+    Text,
+
+    /// JSON line per record with fields: project, phase, instance, duration_ms, error_class
+    Json,
+}
+
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub struct InstanceConfig {
     pub public_url: String,
@@ -38,20 +93,134 @@ impl Display for InstanceConfig {
     }
 }
 
-#[derive(Deserialize,Debug,Clone,PartialEq)]
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq,Default)]
 #[serde(rename_all = "kebab-case")]
 pub struct MigrationConfig {
-    pub ignore_empty_repos: bool
+    #[serde(default)]
+    pub ignore_empty_repos: bool,
+
+    #[serde(default)]
+    pub collisions: CollisionsConfig,
+
+    #[serde(default)]
+    pub personal_projects: PersonalProjectsConfig,
+
+    /// Handling of archived source projects
+    #[serde(default)]
+    pub archived: ArchivedProjectsPolicy,
 }
 
 impl Display for MigrationConfig {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "migration.ignore-empty-repos: {}", self.ignore_empty_repos)
+        write!(f, "migration.ignore-empty-repos: {}, migration.collisions: {}, migration.personal-projects: {}, \
+                   migration.archived: {:?}",
+               self.ignore_empty_repos, self.collisions, self.personal_projects, self.archived)
+    }
+}
+
+#[derive(Deserialize,Serialize,Debug,Clone,Copy,PartialEq,Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArchivedProjectsPolicy {
+    /// Leave archived projects out of migration
+    Skip,
+
+    /// Migrate archived projects as active ones
+    #[default]
+    Migrate,
+
+    /// Archive target project after its repository and phases are migrated
+    MigrateAndArchive,
+}
+
+/// Projects in user namespaces
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct PersonalProjectsConfig {
+    pub policy: PersonalProjectsPolicy,
+
+    /// Top-level group path for `group` policy, projects are migrated to `{group}/{username}`
+    pub group: String,
+}
+
+impl Default for PersonalProjectsConfig {
+    fn default() -> Self {
+        PersonalProjectsConfig {
+            policy: PersonalProjectsPolicy::Exclude,
+            group: "personal".to_string(),
+        }
+    }
+}
+
+impl Display for PersonalProjectsConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "policy: {:?}, group: '{}'", self.policy, self.group)
+    }
+}
+
+#[derive(Deserialize,Serialize,Debug,Clone,Copy,PartialEq,Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PersonalProjectsPolicy {
+    /// Leave personal projects out of migration
+    #[default]
+    Exclude,
+
+    /// Create projects in namespace of mapped target user, requires admin token on target instance
+    UserNamespace,
+
+    /// Create projects in `{group}/{username}` subgroup
+    Group,
+}
+
+/// Handling of target projects which take name or path of source project but aren't its copy.
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct CollisionsConfig {
+    pub policy: CollisionPolicy,
+
+    /// Appended to name and path of project with `rename` policy
+    pub rename_suffix: String,
+
+    /// Top-level group path for projects with `quarantine` policy
+    pub quarantine_group: String,
+}
+
+impl Default for CollisionsConfig {
+    fn default() -> Self {
+        CollisionsConfig {
+            policy: CollisionPolicy::Skip,
+            rename_suffix: "-migrated".to_string(),
+            quarantine_group: "gmt-quarantine".to_string(),
+        }
+    }
+}
+
+impl Display for CollisionsConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "policy: {:?}, rename-suffix: '{}', quarantine-group: '{}'",
+               self.policy, self.rename_suffix, self.quarantine_group)
     }
 }
 
-#[derive(Deserialize,Debug,Clone,PartialEq)]
+#[derive(Deserialize,Serialize,Debug,Clone,Copy,PartialEq,Default)]
 #[serde(rename_all = "kebab-case")]
+pub enum CollisionPolicy {
+    /// Leave source project out of migration
+    #[default]
+    Skip,
+
+    /// Stop migration
+    Fail,
+
+    /// Create project with `rename-suffix` in the same group
+    Rename,
+
+    /// Create project in `quarantine-group`, path contains source group path
+    Quarantine,
+}
+
+/// Disabled handlers by default, nothing is removed without explicit permission
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq,Default)]
+#[serde(rename_all = "kebab-case", default)]
 pub struct ErrorHandlersConfig {
     /// Remove repository on target GitLab instance
     /// if `clone & push` step has error(s) (permissions, connection timeouts, etc.).
@@ -62,4 +231,198 @@ impl Display for ErrorHandlersConfig {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "remove-target-repo-after-clone-error: {}", self.remove_target_repo_after_clone_error)
     }
-}
\ No newline at end of file
+}
+
+/// Credentials used by source instance to push into target repositories (push mirroring).
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct MirrorConfig {
+    pub username: String,
+
+    /// Access token with `write_repository` scope only on target instance, required by `mirror create`.
+    /// Source project maintainers can read it, so `target.token` must not be used here
+    pub token: String,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        MirrorConfig {
+            username: "gmt-mirror".to_string(),
+            token: "".to_string(),
+        }
+    }
+}
+
+impl Display for MirrorConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "username: {}, token: ***********", self.username)
+    }
+}
+
+/// Migration statistics in Prometheus format
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq,Default)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct MetricsConfig {
+    /// File for node_exporter textfile collector, e.g. `/var/lib/node_exporter/gmt.prom`,
+    /// rewritten after every migrated project. Metrics aren't exported if empty.
+    pub textfile: String,
+}
+
+impl Display for MetricsConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "textfile: '{}'", self.textfile)
+    }
+}
+
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq,Default)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct UsersConfig {
+    /// Source username -> target username, users with the same username are matched without mapping
+    pub mapping: BTreeMap<String, String>,
+}
+
+impl Display for UsersConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "mapping: {:?}", self.mapping)
+    }
+}
+
+/// Optional migration phases, executed for every project after its repository has been pushed.
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq,Default)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct PhasesConfig {
+    pub merge_requests: MergeRequestsPhaseConfig,
+    pub ci_variables: CiVariablesPhaseConfig,
+    pub protections: PhaseConfig,
+    pub integrations: IntegrationsPhaseConfig,
+    pub releases: PhaseConfig,
+    pub snippets: SnippetsPhaseConfig,
+    pub registry: RegistryPhaseConfig,
+    pub packages: PhaseConfig,
+}
+
+impl Display for PhasesConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "merge-requests: {}, ci-variables: {}, protections: {}, integrations: {}, releases: {}, snippets: {}, \
+                   registry: {}, packages: {}",
+               self.merge_requests, self.ci_variables, self.protections, self.integrations, self.releases,
+               self.snippets, self.registry, self.packages)
+    }
+}
+
+/// Settings of phase without options
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq,Default)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct PhaseConfig {
+    pub enabled: bool,
+}
+
+impl Display for PhaseConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "enabled: {}", self.enabled)
+    }
+}
+
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct MergeRequestsPhaseConfig {
+    pub enabled: bool,
+
+    /// Merge request states to migrate: `opened`, `merged`, `closed`
+    pub states: Vec<String>,
+}
+
+impl Default for MergeRequestsPhaseConfig {
+    fn default() -> Self {
+        MergeRequestsPhaseConfig {
+            enabled: false,
+            states: vec!["opened".to_string()],
+        }
+    }
+}
+
+impl Display for MergeRequestsPhaseConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "enabled: {}, states: {:?}", self.enabled, self.states)
+    }
+}
+
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq,Default)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct CiVariablesPhaseConfig {
+    pub enabled: bool,
+    pub masked: MaskedVariablesPolicy,
+}
+
+impl Display for CiVariablesPhaseConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "enabled: {}, masked: {:?}", self.enabled, self.masked)
+    }
+}
+
+#[derive(Deserialize,Serialize,Debug,Clone,Copy,PartialEq,Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MaskedVariablesPolicy {
+    /// Copy masked variables with their values
+    #[default]
+    Copy,
+
+    /// Don't copy masked variables, only report them for manual setup
+    Report,
+}
+
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq,Default)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct IntegrationsPhaseConfig {
+    pub enabled: bool,
+
+    /// Webhook url -> secret token, tokens can't be read from source instance
+    pub webhook_tokens: BTreeMap<String, String>,
+}
+
+impl Display for IntegrationsPhaseConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "enabled: {}, webhook-tokens: {} (hidden)", self.enabled, self.webhook_tokens.len())
+    }
+}
+
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq,Default)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SnippetsPhaseConfig {
+    pub enabled: bool,
+
+    /// Copy personal snippets to mapped target users, requires admin tokens on both instances
+    pub personal: bool,
+}
+
+impl Display for SnippetsPhaseConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "enabled: {}, personal: {}", self.enabled, self.personal)
+    }
+}
+
+#[derive(Deserialize,Serialize,Debug,Clone,PartialEq,Default)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RegistryPhaseConfig {
+    pub enabled: bool,
+
+    /// Container registry urls, e.g. `https://registry.company.com`, taken from GitLab API if empty
+    pub source_url: String,
+    pub target_url: String,
+
+    /// Tag patterns with `*` wildcard, all tags are copied if empty
+    pub tags: Vec<String>,
+
+    /// Tag patterns with `*` wildcard which aren't copied
+    pub exclude_tags: Vec<String>,
+
+    /// Maximum size of images copied per project in megabytes, 0 - unlimited
+    pub max_size_mb: u64,
+}
+
+impl Display for RegistryPhaseConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "enabled: {}, source-url: '{}', target-url: '{}', tags: {:?}, exclude-tags: {:?}, max-size-mb: {}",
+               self.enabled, self.source_url, self.target_url, self.tags, self.exclude_tags, self.max_size_mb)
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt::Write;
+
+use anyhow::Context;
+use serde_yaml::{Mapping, Value};
+
+use crate::config::{AppConfig, default_git_bin_path, default_log_level, default_tmp_dir, ErrorHandlersConfig,
+                    InstanceConfig, LogFormat, MetricsConfig, MigrationConfig, MirrorConfig, PhasesConfig,
+                    UsersConfig};
+use crate::config::validate::TOKEN_PLACEHOLDER;
+
+const TEMPLATE_HEADER: &str = "# Migration Tool for GitLab config, generated by `gmt config init`";
+
+/// Comments of template keys, keys without comment are written as is.
+/// Every key except `enabled` flags has to be described, see tests.
+const KEY_COMMENTS: &[(&str, &str)] = &[
+    ("log-level", "trace, debug, info, warn, error, off"),
+    ("log-format", "Format of gmt.log lines: text, json (JSON line per record, for log aggregation)"),
+    ("git-bin-path", "Git binary, found in PATH if path isn't absolute"),
+    ("tmp-dir", "Directory for temporary repository clones"),
+    ("source", "Instance to migrate from"),
+    ("source.public-url", "Web url without trailing slash"),
+    ("source.git-url", "Trailing port ':22' is important even for default tcp/22"),
+    ("source.token", "Obtain token here - User Settings > Access Tokens"),
+    ("target", "Instance to migrate to"),
+    ("target.public-url", "Web url without trailing slash"),
+    ("target.git-url", "Trailing port ':22' is important even for default tcp/22"),
+    ("target.token", "Obtain token here - User Settings > Access Tokens"),
+    ("migration", "Which projects are migrated and where"),
+    ("migration.ignore-empty-repos", "Do not migrate empty projects (without branches)"),
+    ("migration.collisions", "Target projects which take name or path of source project but have unrelated repository"),
+    ("migration.collisions.policy", "skip, fail, rename, quarantine"),
+    ("migration.collisions.rename-suffix", "`rename` policy: appended to project name and path"),
+    ("migration.collisions.quarantine-group",
+     "`quarantine` policy: top-level group for collided projects, path contains source group path"),
+    ("migration.personal-projects", "Projects in user namespaces"),
+    ("migration.personal-projects.policy", "exclude, user-namespace (admin token on target instance), group"),
+    ("migration.personal-projects.group", "`group` policy: projects are migrated to `{group}/{username}`"),
+    ("migration.archived", "Archived source projects: migrate, skip, migrate-and-archive"),
+    ("error-handlers", "Reactions to migration errors"),
+    ("error-handlers.remove-target-repo-after-clone-error",
+     "Remove repository on target GitLab instance if `git clone & push` step(s) has error(s) \
+      (permissions, connection timeouts, etc.).\n\nDisabled by default for data safety,\n\
+      but required if you want to resume process after clone error without any manual work."),
+    ("mirror", "Push mirroring from source to target instance (`gmt mirror` command)"),
+    ("mirror.username", "Credentials used by source instance to push into target repositories"),
+    ("mirror.token",
+     "Access token with `write_repository` scope only on target instance, required by `gmt mirror create`.\n\
+      Source project maintainers can read it, so don't use `target.token` here"),
+    ("users", "Users of source instance matched to target users for assignees, reviewers, etc."),
+    ("users.mapping", "Source username -> target username, e.g. `jdoe: 'john.doe'`\n\
+      Users with the same username on both instances are matched without mapping"),
+    ("phases", "Optional phases executed by `migrate` for every new project, or by `gmt phase <name>` for migrated projects"),
+    ("phases.merge-requests", "merge requests by state"),
+    ("phases.merge-requests.states", "opened, merged, closed"),
+    ("phases.ci-variables", "project and group CI/CD variables"),
+    ("phases.ci-variables.masked", "copy - copy masked variables with values, report - only list them for manual setup"),
+    ("phases.protections", "protected branches, protected tags and push rules"),
+    ("phases.integrations", "webhooks, deploy keys, deploy tokens report"),
+    ("phases.integrations.webhook-tokens", "Webhook url -> secret token, e.g. `'https://jenkins.company.com/project/api': 'secret'`"),
+    ("phases.releases", "releases with milestones, asset links and uploaded files"),
+    ("phases.snippets", "project snippets with all files"),
+    ("phases.snippets.personal", "Copy personal snippets to mapped users, requires admin tokens on both instances"),
+    ("phases.registry", "container registry images, copied with registry HTTP API"),
+    ("phases.registry.source-url", "Registry urls, taken from GitLab API if empty, e.g. 'https://registry.gitlab.company.com'"),
+    ("phases.registry.target-url", "e.g. 'https://registry.git.company.com'"),
+    ("phases.registry.tags", "Tag patterns with `*` wildcard, all tags are copied if empty, e.g. ['v*', 'latest']"),
+    ("phases.registry.exclude-tags", "Tag patterns with `*` wildcard which aren't copied, e.g. ['*-dev']"),
+    ("phases.registry.max-size-mb", "Maximum size of images copied per project in megabytes, 0 - unlimited"),
+    ("phases.packages", "package registry: generic, Maven, npm and PyPI packages"),
+    ("metrics", "Migration statistics in Prometheus format"),
+    ("metrics.textfile", "File for node_exporter textfile collector, metrics aren't exported if empty,\n\
+      file is rewritten after every project, e.g. '/var/lib/node_exporter/textfile_collector/gmt.prom'"),
+];
+
+/// Config with default values and sample instances
+pub fn get_template_config() -> AppConfig {
+    AppConfig {
+        log_level: default_log_level(),
+        log_format: LogFormat::default(),
+        git_bin_path: default_git_bin_path(),
+        tmp_dir: default_tmp_dir(),
+        source: InstanceConfig {
+            public_url: "https://old-gitlab.company.com".to_string(),
+            git_url: "ssh://git@old-gitlab.company.com:22".to_string(),
+            token: TOKEN_PLACEHOLDER.to_string(),
+        },
+        target: InstanceConfig {
+            public_url: "https://gitlab.company.com".to_string(),
+            git_url: "ssh://git@gitlab.company.com:2222".to_string(),
+            token: TOKEN_PLACEHOLDER.to_string(),
+        },
+        migration: MigrationConfig::default(),
+        error_handlers: ErrorHandlersConfig::default(),
+        mirror: MirrorConfig::default(),
+        users: UsersConfig::default(),
+        phases: PhasesConfig::default(),
+        metrics: MetricsConfig::default(),
+    }
+}
+
+/// Commented YAML config with every key of `AppConfig`, keys go in order of config structure.
+pub fn get_config_template() -> anyhow::Result<String> {
+    let config = serde_yaml::to_value(get_template_config()).context("unable to serialize config template")?;
+
+    let mapping = config.as_mapping().context("config template should be a mapping")?;
+
+    let mut out = format!("{TEMPLATE_HEADER}\n");
+    write_mapping(&mut out, mapping, "", 0);
+
+    Ok(out)
+}
+
+fn write_mapping(out: &mut String, mapping: &Mapping, path: &str, indent: usize) {
+    let padding = " ".repeat(indent);
+
+    for (index, (key, value)) in mapping.iter().enumerate() {
+        let key = key.as_str().unwrap_or_default();
+        let key_path = format!("{path}{key}");
+
+        let comment = get_comment(&key_path);
+
+        // top-level sections and commented keys are separated by empty line
+        if indent == 0 || (index > 0 && comment.is_some()) {
+            out.push('\n');
+        }
+
+        for line in comment.unwrap_or_default().lines() {
+            let _ = writeln!(out, "{padding}#{}{line}", if line.is_empty() { "" } else { " " });
+        }
+
+        match value {
+            Value::Mapping(nested) if !nested.is_empty() => {
+                let _ = writeln!(out, "{padding}{key}:");
+                write_mapping(out, nested, &format!("{key_path}."), indent + 2);
+            }
+            _ => {
+                let _ = writeln!(out, "{padding}{key}: {}", format_value(value));
+            }
+        }
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(text) => format!("'{}'", text.replace('\'', "''")),
+        Value::Sequence(items) => format!("[{}]", items.iter().map(format_value).collect::<Vec<String>>().join(", ")),
+        Value::Mapping(_) => "{}".to_string(),
+        Value::Bool(flag) => flag.to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::Null => "~".to_string(),
+    }
+}
+
+fn get_comment(key_path: &str) -> Option<&'static str> {
+    KEY_COMMENTS.iter().find(|(path, _)| *path == key_path).map(|(_, comment)| *comment)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::Value;
+    use tempfile::TempDir;
+
+    use crate::config::file::check_config_file;
+    use crate::config::template::{get_comment, get_config_template, get_template_config, KEY_COMMENTS};
+
+    #[test]
+    fn template_should_be_loaded_as_default_config() {
+        let tmp_dir = TempDir::new().unwrap();
+        let template_path = tmp_dir.path().join("gmt.yml");
+        let template = get_config_template().unwrap();
+        fs::write(&template_path, &template).unwrap();
+
+        assert!(template.contains("\nerror-handlers:\n  # Remove repository on target GitLab instance"), "{template}");
+        assert!(template.contains("\n  git-url: 'ssh://git@gitlab.company.com:2222'\n"), "{template}");
+
+        let (config, problems) = check_config_file(&template_path).unwrap();
+
+        assert_eq!(get_template_config(), config);
+        assert_eq!(vec!["source.token: access token isn't set", "target.token: access token isn't set"], problems);
+    }
+
+    #[test]
+    fn every_config_key_should_be_described() {
+        let mut key_paths: Vec<String> = vec![];
+        collect_key_paths(&mut key_paths, "", &serde_json::to_value(get_template_config()).unwrap());
+
+        let undescribed: Vec<&String> = key_paths.iter()
+            .filter(|path| !path.ends_with(".enabled") && get_comment(path).is_none())
+            .collect();
+        assert!(undescribed.is_empty(), "{undescribed:?}");
+
+        let unknown: Vec<&str> = KEY_COMMENTS.iter().map(|(path, _)| *path)
+            .filter(|path| !key_paths.iter().any(|p| p == path))
+            .collect();
+        assert!(unknown.is_empty(), "{unknown:?}");
+    }
+
+    fn collect_key_paths(key_paths: &mut Vec<String>, path: &str, value: &Value) {
+        if let Value::Object(map) = value {
+            for (key, nested) in map {
+                key_paths.push(format!("{path}{key}"));
+                collect_key_paths(key_paths, &format!("{path}{key}."), nested);
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use reqwest::Url;
+use serde_json::Value;
+
+use crate::config::{AppConfig, InstanceConfig};
+
+/// Token value of config template
+pub const TOKEN_PLACEHOLDER: &str = "CHANGE-ME";
+
+const GIT_URL_SCHEMES: [&str; 4] = ["ssh", "https", "http", "file"];
+
+/// Keys without defaults
+const REQUIRED_KEYS: [&str; 6] = [
+    "source.public-url", "source.git-url", "source.token", "target.public-url", "target.git-url", "target.token",
+];
+
+/// Check values which can't be checked by deserialization, e.g. urls.
+pub fn validate_config(config: &AppConfig) -> Vec<String> {
+    let mut problems: Vec<String> = vec![];
+
+    validate_instance(&mut problems, "source", &config.source);
+    validate_instance(&mut problems, "target", &config.target);
+
+    if config.source.public_url.trim_end_matches('/') == config.target.public_url.trim_end_matches('/') {
+        problems.push("source.public-url and target.public-url point to the same instance".to_string());
+    }
+
+    if config.tmp_dir.is_empty() {
+        problems.push("tmp-dir: shouldn't be empty".to_string());
+    }
+
+    let registry = &config.phases.registry;
+
+    for (key, url) in [("source-url", &registry.source_url), ("target-url", &registry.target_url)] {
+        if !url.is_empty() {
+            if let Err(e) = check_public_url(url) {
+                problems.push(format!("phases.registry.{key}: {e}"));
+            }
+        }
+    }
+
+    problems
+}
+
+fn validate_instance(problems: &mut Vec<String>, name: &str, instance: &InstanceConfig) {
+    if let Err(e) = check_public_url(&instance.public_url) {
+        problems.push(format!("{name}.public-url: {e}"));
+    }
+
+    if let Err(e) = check_git_url(&instance.git_url) {
+        problems.push(format!("{name}.git-url: {e}"));
+    }
+
+    if instance.token.is_empty() || instance.token == TOKEN_PLACEHOLDER {
+        problems.push(format!("{name}.token: access token isn't set"));
+    }
+}
+
+/// API urls are built as `{public-url}/api/v4/..`
+fn check_public_url(url: &str) -> Result<(), String> {
+    let parsed_url = Url::parse(url)
+        .map_err(|e| format!("'{url}' isn't valid url ({e}), e.g. 'https://gitlab.company.com'"))?;
+
+    if !["https", "http"].contains(&parsed_url.scheme()) {
+        return Err(format!("'{url}' should start with 'https://' or 'http://'"))
+    }
+
+    if url.ends_with('/') {
+        return Err(format!("'{url}' shouldn't end with '/'"))
+    }
+
+    Ok(())
+}
+
+/// Repository urls are built as `{git-url}/{group}/{project}`, so scp-like syntax `git@host:group` can't be used.
+/// Port has to be set for ssh urls, even default `:22`.
+fn check_git_url(url: &str) -> Result<(), String> {
+    let parsed_url = Url::parse(url)
+        .map_err(|_| format!("'{url}' isn't valid url, e.g. 'ssh://git@gitlab.company.com:22'"))?;
+
+    if !GIT_URL_SCHEMES.contains(&parsed_url.scheme()) {
+        return Err(format!("'{url}' has unsupported scheme, supported: {}", GIT_URL_SCHEMES.join(", ")))
+    }
+
+    if url.ends_with('/') {
+        return Err(format!("'{url}' shouldn't end with '/'"))
+    }
+
+    if parsed_url.scheme() == "ssh" && parsed_url.port().is_none() {
+        let host = parsed_url.host_str().unwrap_or_default();
+        return Err(format!("'{url}' should contain port, even default one, e.g. 'ssh://git@{host}:22'"))
+    }
+
+    Ok(())
+}
+
+/// Required keys which aren't set in `config`, serde reports only the first one without its section.
+pub fn get_missing_keys(config: &Value) -> Vec<String> {
+    REQUIRED_KEYS.iter()
+        .filter(|key| key.split('.').try_fold(config, |value, segment| value.get(segment)).is_none())
+        .map(|key| format!("{key}: required key is missing"))
+        .collect()
+}
+
+/// Keys of `config` which aren't present in `known` config structure, e.g. typos.
+/// Empty mappings of `known` accept any keys, e.g. `users.mapping`.
+pub fn get_unknown_keys(config: &Value, known: &Value) -> Vec<String> {
+    let mut unknown_keys: Vec<String> = vec![];
+    collect_unknown_keys(&mut unknown_keys, "", config, known);
+    unknown_keys
+}
+
+fn collect_unknown_keys(unknown_keys: &mut Vec<String>, path: &str, config: &Value, known: &Value) {
+    let (config_map, known_map) = match (config, known) {
+        (Value::Object(config_map), Value::Object(known_map)) if !known_map.is_empty() => (config_map, known_map),
+        _ => return
+    };
+
+    for (key, value) in config_map {
+        let key_path = format!("{path}{key}");
+
+        match known_map.get(key) {
+            Some(known_value) => collect_unknown_keys(unknown_keys, &format!("{key_path}."), value, known_value),
+            None => {
+                let similar_key = known_map.keys()
+                    .map(|k| (get_edit_distance(k, key), k))
+                    .filter(|(distance, _)| *distance <= 2)
+                    .min();
+
+                match similar_key {
+                    Some((_, similar_key)) =>
+                        unknown_keys.push(format!("{key_path}: unknown key, did you mean '{path}{similar_key}'?")),
+                    None => unknown_keys.push(format!("{key_path}: unknown key"))
+                }
+            }
+        }
+    }
+}
+
+/// Levenshtein distance
+fn get_edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::config::InstanceConfig;
+    use crate::config::template::get_template_config;
+    use crate::config::validate::{get_missing_keys, get_unknown_keys, validate_config};
+
+    #[test]
+    fn urls_and_unknown_keys_should_be_reported() {
+        let mut config = get_template_config();
+        config.source = InstanceConfig {
+            public_url: "gitlab.company.com".to_string(),
+            git_url: "ssh://git@old-gitlab.company.com".to_string(),
+            token: "secret".to_string(),
+        };
+        config.target.public_url = "https://gitlab.company.com/".to_string();
+        config.target.git_url = "git@gitlab.company.com:backend".to_string();
+
+        assert_eq!(vec![
+            "source.public-url: 'gitlab.company.com' isn't valid url (relative URL without a base), \
+             e.g. 'https://gitlab.company.com'",
+            "source.git-url: 'ssh://git@old-gitlab.company.com' should contain port, even default one, \
+             e.g. 'ssh://git@old-gitlab.company.com:22'",
+            "target.public-url: 'https://gitlab.company.com/' shouldn't end with '/'",
+            "target.git-url: 'git@gitlab.company.com:backend' isn't valid url, e.g. 'ssh://git@gitlab.company.com:22'",
+            "target.token: access token isn't set",
+        ], validate_config(&config));
+
+        let known = serde_json::to_value(get_template_config()).unwrap();
+
+        let config = json!({
+            "migration": {"ignore-empty-repo": true},
+            "users": {"mapping": {"jdoe": "john.doe"}},
+            "mirrors": {},
+        });
+
+        assert_eq!(vec![
+            "migration.ignore-empty-repo: unknown key, did you mean 'migration.ignore-empty-repos'?",
+            "mirrors: unknown key, did you mean 'mirror'?",
+        ], get_unknown_keys(&config, &known));
+
+        let config = json!({"source": {"public-url": "https://old-gitlab.company.com", "git-url": "", "token": ""}});
+
+        assert_eq!(vec![
+            "target.public-url: required key is missing",
+            "target.git-url: required key is missing",
+            "target.token: required key is missing",
+        ], get_missing_keys(&config));
+    }
+}
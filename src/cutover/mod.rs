@@ -0,0 +1,617 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use log::{error, info};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config::{AppConfig, InstanceConfig};
+use crate::migration::api::{delete, encode_path_segment, get_all_pages, get_json, post_json, put_json};
+use crate::phases::protections::{get_role_access_level, GitLabAccessLevel, GitLabProtectedBranch};
+use crate::verify::{verify_migration, VerifyOptions};
+
+/// Changes made on source instance are recorded here, `cutover --undo` reverts them.
+pub const CUTOVER_STATE_FILE_PATH: &str = "gmt-cutover.json";
+
+/// Wildcard rule protects every branch of project
+const ALL_BRANCHES_PATTERN: &str = "*";
+
+/// Access level `No one`
+const NO_ACCESS_LEVEL: u32 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CutoverMode {
+    /// Archive source project, it becomes read-only
+    Archive,
+
+    /// Protect all branches with `No one` push and merge access levels.
+    /// GitLab applies the most permissive rule if branch matches several, so existing rules
+    /// which allow pushes or merges are lowered to `No one` too, their levels are recorded for undo.
+    Protect,
+}
+
+#[derive(Debug, Clone)]
+pub struct CutoverOptions {
+    pub mode: CutoverMode,
+
+    /// Add notice with target project url to source project description
+    pub banner: bool,
+
+    /// Show planned changes only, nothing is changed on source instance
+    pub dry_run: bool,
+}
+
+/// Changes made on source project.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CutoverRecord {
+    pub project_id: u32,
+    pub project_path: String,
+    pub archived: bool,
+    pub protected_branch: Option<String>,
+
+    /// Existing protection rules lowered to `No one`, restored by undo
+    #[serde(default)]
+    pub lowered_branches: Vec<LoweredProtectedBranch>,
+
+    /// Description before banner was added
+    pub previous_description: Option<String>,
+}
+
+/// Protected branch rule as it was before cutover.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoweredProtectedBranch {
+    pub name: String,
+    pub push_access_level: u32,
+    pub merge_access_level: u32,
+    pub allow_force_push: bool,
+    pub code_owner_approval_required: bool,
+
+    /// Access granted to specific users and groups, e.g. `{"user_id": 5}`
+    pub allowed_to_push: Vec<Value>,
+    pub allowed_to_merge: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CutoverStatus {
+    /// Verification has failed, project is left untouched
+    NotVerified(Vec<String>),
+
+    /// Dry-run, changes which would be applied
+    Planned(Vec<String>),
+
+    Done(Vec<String>),
+
+    /// Project is already recorded in state file
+    AlreadyDone,
+
+    Reverted(Vec<String>),
+
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectCutover {
+    pub project_path: String,
+    pub status: CutoverStatus,
+}
+
+/// Freeze verified source projects, every change is recorded to `state_file_path`.
+pub fn cutover_source_projects(client: &Client, config: &AppConfig, options: &CutoverOptions,
+                               state_file_path: &Path) -> anyhow::Result<Vec<ProjectCutover>> {
+    info!("cutover source projects at '{}' (dry-run: {})..", config.source.public_url, options.dry_run);
+
+    let verify_options = VerifyOptions { compare_visibility: false, compare_counts: false };
+
+    let verifications = verify_migration(client, config, &verify_options)?;
+
+    let mut records = load_cutover_records(state_file_path)?;
+
+    let mut results: Vec<ProjectCutover> = vec![];
+
+    for verification in verifications {
+        let project_path = verification.project_path.to_string();
+
+        let status = if !verification.is_verified() {
+            CutoverStatus::NotVerified(verification.mismatches)
+
+        } else if records.iter().any(|r| r.project_id == verification.source_project_id) {
+            CutoverStatus::AlreadyDone
+
+        } else {
+            let protected_branches = match options.mode {
+                CutoverMode::Protect => get_all_pages(
+                    client, &config.source,
+                    &format!("projects/{}/protected_branches", verification.source_project_id)),
+                CutoverMode::Archive => Ok(vec![])
+            };
+
+            let protected_branches: Vec<GitLabProtectedBranch> = match protected_branches {
+                Ok(protected_branches) => protected_branches,
+                Err(e) => {
+                    error!("unable to get protected branches of project '{project_path}': {}", e.root_cause());
+                    let status = CutoverStatus::Failed(format!("{}", e.root_cause()));
+                    results.push(ProjectCutover { project_path, status });
+                    continue
+                }
+            };
+
+            let actions = get_planned_actions(options, &protected_branches);
+
+            if options.dry_run {
+                CutoverStatus::Planned(actions)
+
+            } else {
+                let target_url = format!("{}/{}", config.target.public_url.trim_end_matches('/'),
+                                         verification.target_project_path.as_deref().unwrap_or_default());
+
+                let mut record = CutoverRecord {
+                    project_id: verification.source_project_id,
+                    project_path: project_path.to_string(),
+                    archived: false,
+                    protected_branch: None,
+                    lowered_branches: vec![],
+                    previous_description: None,
+                };
+
+                let freeze_result = freeze_project(client, &config.source, &target_url, options,
+                                                   &protected_branches, &mut record);
+
+                // partially applied changes are recorded as well to be reverted by undo
+                if !get_undo_actions(&record).is_empty() {
+                    records.push(record);
+                    save_cutover_records(state_file_path, &records)?;
+                }
+
+                match freeze_result {
+                    Ok(_) => CutoverStatus::Done(actions),
+                    Err(e) => {
+                        error!("cutover error for project '{project_path}': {}", e);
+                        error!("{}", e.root_cause());
+                        CutoverStatus::Failed(format!("{}", e.root_cause()))
+                    }
+                }
+            }
+        };
+
+        info!("project '{project_path}' cutover status: {:?}", status);
+
+        results.push(ProjectCutover { project_path, status });
+    }
+
+    Ok(results)
+}
+
+/// Revert changes recorded in `state_file_path`, reverted projects are removed from state file.
+pub fn undo_cutover(client: &Client, config: &AppConfig, state_file_path: &Path,
+                    dry_run: bool) -> anyhow::Result<Vec<ProjectCutover>> {
+    info!("undo cutover at '{}' (dry-run: {dry_run})..", config.source.public_url);
+
+    let records = load_cutover_records(state_file_path)?;
+
+    let mut remaining_records: Vec<CutoverRecord> = vec![];
+
+    let mut results: Vec<ProjectCutover> = vec![];
+
+    for record in records {
+        let actions = get_undo_actions(&record);
+
+        let status = if dry_run {
+            remaining_records.push(record.clone());
+            CutoverStatus::Planned(actions)
+
+        } else {
+            match unfreeze_project(client, &config.source, &record) {
+                Ok(_) => CutoverStatus::Reverted(actions),
+                Err(e) => {
+                    error!("undo cutover error for project '{}': {}", record.project_path, e);
+                    error!("{}", e.root_cause());
+                    remaining_records.push(record.clone());
+                    CutoverStatus::Failed(format!("{}", e.root_cause()))
+                }
+            }
+        };
+
+        info!("project '{}' undo cutover status: {:?}", record.project_path, status);
+
+        results.push(ProjectCutover { project_path: record.project_path, status });
+    }
+
+    if !dry_run {
+        save_cutover_records(state_file_path, &remaining_records)?;
+    }
+
+    Ok(results)
+}
+
+/// Apply cutover changes to source project, `record` is updated after every applied change.
+fn freeze_project(client: &Client, instance: &InstanceConfig, target_url: &str, options: &CutoverOptions,
+                  protected_branches: &[GitLabProtectedBranch], record: &mut CutoverRecord) -> anyhow::Result<()> {
+    let project_id = record.project_id;
+
+    // archived project can't be updated, so banner goes first
+    if options.banner {
+        let project: Value = get_json(client, instance, &format!("projects/{project_id}"))
+            .context("unable to get source project")?;
+
+        let previous_description = project["description"].as_str().unwrap_or_default().to_string();
+
+        let description = format!("{} {}", get_banner(target_url), previous_description);
+
+        let _: Value = put_json(client, instance, &format!("projects/{project_id}"),
+                                &json!({"description": description.trim_end()}))
+            .context("unable to update source project description")?;
+
+        record.previous_description = Some(previous_description);
+    }
+
+    match options.mode {
+        CutoverMode::Archive => {
+            let _: Value = post_json(client, instance, &format!("projects/{project_id}/archive"), &json!({}))
+                .context("unable to archive source project")?;
+
+            record.archived = true;
+        }
+        CutoverMode::Protect => {
+            let protected_branches_path = format!("projects/{project_id}/protected_branches");
+
+            for branch in protected_branches.iter().filter(|b| is_permissive(b)) {
+                delete(client, instance, &format!("{protected_branches_path}/{}", encode_path_segment(&branch.name)))
+                    .context(format!("unable to unprotect source project branch '{}'", branch.name))?;
+
+                record.lowered_branches.push(get_lowered_branch(branch));
+
+                protect_branch(client, instance, &protected_branches_path, &branch.name)?;
+            }
+
+            if !protected_branches.iter().any(|b| b.name == ALL_BRANCHES_PATTERN) {
+                protect_branch(client, instance, &protected_branches_path, ALL_BRANCHES_PATTERN)?;
+
+                record.protected_branch = Some(ALL_BRANCHES_PATTERN.to_string());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn unfreeze_project(client: &Client, instance: &InstanceConfig, record: &CutoverRecord) -> anyhow::Result<()> {
+    let project_id = record.project_id;
+
+    if record.archived {
+        let _: Value = post_json(client, instance, &format!("projects/{project_id}/unarchive"), &json!({}))
+            .context("unable to unarchive source project")?;
+    }
+
+    let protected_branches_path = format!("projects/{project_id}/protected_branches");
+
+    if let Some(protected_branch) = &record.protected_branch {
+        delete(client, instance, &format!("{protected_branches_path}/{}", encode_path_segment(protected_branch)))
+            .context("unable to unprotect source project branches")?;
+    }
+
+    if !record.lowered_branches.is_empty() {
+        let protected_branches: Vec<GitLabProtectedBranch> = get_all_pages(client, instance, &protected_branches_path)
+            .context("unable to get protected branches of source project")?;
+
+        for branch in &record.lowered_branches {
+            // rule is missing if cutover has failed between unprotect and protect
+            if protected_branches.iter().any(|b| b.name == branch.name) {
+                delete(client, instance, &format!("{protected_branches_path}/{}", encode_path_segment(&branch.name)))
+                    .context(format!("unable to unprotect source project branch '{}'", branch.name))?;
+            }
+
+            let _: Value = post_json(client, instance, &protected_branches_path, &json!({
+                "name": branch.name,
+                "push_access_level": branch.push_access_level,
+                "merge_access_level": branch.merge_access_level,
+                "allow_force_push": branch.allow_force_push,
+                "code_owner_approval_required": branch.code_owner_approval_required,
+                "allowed_to_push": branch.allowed_to_push,
+                "allowed_to_merge": branch.allowed_to_merge,
+            })).context(format!("unable to restore protected branch '{}' of source project", branch.name))?;
+        }
+    }
+
+    if let Some(previous_description) = &record.previous_description {
+        let _: Value = put_json(client, instance, &format!("projects/{project_id}"),
+                                &json!({"description": previous_description}))
+            .context("unable to restore source project description")?;
+    }
+
+    Ok(())
+}
+
+/// Protect branches matching `name` with `No one` push and merge access levels.
+fn protect_branch(client: &Client, instance: &InstanceConfig, protected_branches_path: &str,
+                  name: &str) -> anyhow::Result<()> {
+    let _: Value = post_json(client, instance, protected_branches_path, &json!({
+        "name": name,
+        "push_access_level": NO_ACCESS_LEVEL,
+        "merge_access_level": NO_ACCESS_LEVEL,
+    })).context(format!("unable to protect source project branches '{name}'"))?;
+
+    Ok(())
+}
+
+/// Rule allows someone to push, merge or force push
+fn is_permissive(branch: &GitLabProtectedBranch) -> bool {
+    branch.allow_force_push ||
+        branch.push_access_levels.iter().chain(branch.merge_access_levels.iter())
+            .any(|al| !al.is_role() || al.access_level.unwrap_or(NO_ACCESS_LEVEL) != NO_ACCESS_LEVEL)
+}
+
+fn get_lowered_branch(branch: &GitLabProtectedBranch) -> LoweredProtectedBranch {
+    LoweredProtectedBranch {
+        name: branch.name.to_string(),
+        push_access_level: get_role_access_level(&branch.push_access_levels),
+        merge_access_level: get_role_access_level(&branch.merge_access_levels),
+        allow_force_push: branch.allow_force_push,
+        code_owner_approval_required: branch.code_owner_approval_required,
+        allowed_to_push: get_specific_access(&branch.push_access_levels),
+        allowed_to_merge: get_specific_access(&branch.merge_access_levels),
+    }
+}
+
+/// Entries for `allowed_to_*` parameters, ids are valid since rules are restored on the same instance
+fn get_specific_access(access_levels: &[GitLabAccessLevel]) -> Vec<Value> {
+    access_levels.iter().filter_map(|al| match (al.user_id, al.group_id) {
+        (Some(user_id), _) => Some(json!({"user_id": user_id})),
+        (None, Some(group_id)) => Some(json!({"group_id": group_id})),
+        _ => None
+    }).collect()
+}
+
+fn get_banner(target_url: &str) -> String {
+    format!("⚠️ This project has been moved to {target_url}")
+}
+
+fn get_planned_actions(options: &CutoverOptions, protected_branches: &[GitLabProtectedBranch]) -> Vec<String> {
+    let mut actions: Vec<String> = vec![];
+
+    if options.banner {
+        actions.push("add moved notice to description".to_string());
+    }
+
+    match options.mode {
+        CutoverMode::Archive => actions.push("archive project".to_string()),
+        CutoverMode::Protect => {
+            for branch in protected_branches.iter().filter(|b| is_permissive(b)) {
+                actions.push(format!("lower protected branch '{}' to No one", branch.name));
+            }
+
+            if !protected_branches.iter().any(|b| b.name == ALL_BRANCHES_PATTERN) {
+                actions.push(format!("protect branches '{ALL_BRANCHES_PATTERN}'"));
+            }
+        }
+    }
+
+    actions
+}
+
+fn get_undo_actions(record: &CutoverRecord) -> Vec<String> {
+    let mut actions: Vec<String> = vec![];
+
+    if record.archived {
+        actions.push("unarchive project".to_string());
+    }
+
+    if let Some(protected_branch) = &record.protected_branch {
+        actions.push(format!("unprotect branches '{protected_branch}'"));
+    }
+
+    for branch in &record.lowered_branches {
+        actions.push(format!("restore protected branch '{}'", branch.name));
+    }
+
+    if record.previous_description.is_some() {
+        actions.push("restore description".to_string());
+    }
+
+    actions
+}
+
+fn load_cutover_records(state_file_path: &Path) -> anyhow::Result<Vec<CutoverRecord>> {
+    if !state_file_path.exists() {
+        return Ok(vec![])
+    }
+
+    let content = fs::read_to_string(state_file_path)
+        .context(format!("unable to read cutover state file '{}'", state_file_path.display()))?;
+
+    serde_json::from_str(&content)
+        .context(format!("unable to decode cutover state file '{}'", state_file_path.display()))
+}
+
+fn save_cutover_records(state_file_path: &Path, records: &[CutoverRecord]) -> anyhow::Result<()> {
+    if records.is_empty() {
+        if state_file_path.exists() {
+            fs::remove_file(state_file_path).context("unable to remove cutover state file")?;
+        }
+
+        return Ok(())
+    }
+
+    let content = serde_json::to_string_pretty(records)?;
+
+    fs::write(state_file_path, content)
+        .context(format!("unable to write cutover state file '{}'", state_file_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::config::CollisionPolicy;
+    use crate::cutover::{CutoverMode, CutoverOptions, CutoverStatus, cutover_source_projects, undo_cutover};
+    use crate::migration::migrate_gitlab_instance;
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    #[test]
+    fn verified_projects_should_be_archived_and_restored() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+        source.set_project_attribute(api_id, "description", json!("Public API"));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        migrate_gitlab_instance(&config).unwrap();
+
+        let web_id = source.add_project(group_id, "web", &["main"]);
+
+        let client = ClientBuilder::new().build().unwrap();
+        let state_file_path = tmp_dir.path().join("cutover.json");
+
+        let options = CutoverOptions { mode: CutoverMode::Archive, banner: true, dry_run: false };
+
+        let results = cutover_source_projects(&client, &config, &options, &state_file_path).unwrap();
+
+        let api = results.iter().find(|r| r.project_path == "backend/api").unwrap();
+        assert!(matches!(api.status, CutoverStatus::Done(_)));
+
+        let web = results.iter().find(|r| r.project_path == "backend/web").unwrap();
+        assert!(matches!(web.status, CutoverStatus::NotVerified(_)));
+
+        assert_eq!(json!(true), source.project_attribute(api_id, "archived"));
+        assert_eq!(json!(false), source.project_attribute(web_id, "archived"));
+
+        let description = source.project_attribute(api_id, "description");
+        let description = description.as_str().unwrap();
+        assert!(description.contains("moved to"), "{description}");
+        assert!(description.ends_with("Public API"), "{description}");
+
+        let results = cutover_source_projects(&client, &config, &options, &state_file_path).unwrap();
+        let api = results.iter().find(|r| r.project_path == "backend/api").unwrap();
+        assert_eq!(CutoverStatus::AlreadyDone, api.status);
+
+        let results = undo_cutover(&client, &config, &state_file_path, false).unwrap();
+        assert!(matches!(results.first().unwrap().status, CutoverStatus::Reverted(_)));
+
+        assert_eq!(json!(false), source.project_attribute(api_id, "archived"));
+        assert_eq!(json!("Public API"), source.project_attribute(api_id, "description"));
+        assert!(!state_file_path.exists());
+    }
+
+    #[test]
+    fn banner_should_point_to_renamed_target_project() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        // unrelated project takes the path, source project is migrated as `api-migrated`
+        let target = FakeGitLab::start();
+        let target_group_id = target.add_group("Backend", None);
+        target.add_project(target_group_id, "api", &["master"]);
+
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.migration.collisions.policy = CollisionPolicy::Rename;
+        migrate_gitlab_instance(&config).unwrap();
+
+        let client = ClientBuilder::new().build().unwrap();
+        let state_file_path = tmp_dir.path().join("cutover.json");
+
+        let options = CutoverOptions { mode: CutoverMode::Archive, banner: true, dry_run: false };
+
+        cutover_source_projects(&client, &config, &options, &state_file_path).unwrap();
+
+        let description = source.project_attribute(api_id, "description");
+        let description = description.as_str().unwrap();
+        assert!(description.ends_with(&format!("moved to {}/backend/api-migrated", target.instance().public_url)),
+                "{description}");
+    }
+
+    #[test]
+    fn dry_run_should_not_change_source_projects() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        migrate_gitlab_instance(&config).unwrap();
+
+        let client = ClientBuilder::new().build().unwrap();
+        let state_file_path = tmp_dir.path().join("cutover.json");
+
+        let options = CutoverOptions { mode: CutoverMode::Protect, banner: false, dry_run: true };
+
+        let results = cutover_source_projects(&client, &config, &options, &state_file_path).unwrap();
+
+        assert_eq!(CutoverStatus::Planned(vec!["protect branches '*'".to_string()]),
+                   results.first().unwrap().status);
+
+        assert!(source.project_resources(api_id, "protected_branches").is_empty());
+        assert!(!source.requests().iter().any(|r| r.method != "GET" && r.method != "HEAD"));
+        assert!(!state_file_path.exists());
+    }
+
+    #[test]
+    fn protected_branches_should_be_lowered_and_restored_on_undo() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        migrate_gitlab_instance(&config).unwrap();
+
+        // existing rule allows pushes, it would override wildcard rule
+        source.add_project_resource(api_id, "protected_branches", json!({
+            "id": 1, "name": "main",
+            "push_access_levels": [{"access_level": 40}, {"access_level": 40, "user_id": 7}],
+            "merge_access_levels": [{"access_level": 30}]
+        }));
+
+        let client = ClientBuilder::new().build().unwrap();
+        let state_file_path = tmp_dir.path().join("cutover.json");
+
+        let options = CutoverOptions { mode: CutoverMode::Protect, banner: false, dry_run: false };
+
+        let results = cutover_source_projects(&client, &config, &options, &state_file_path).unwrap();
+
+        assert_eq!(CutoverStatus::Done(vec!["lower protected branch 'main' to No one".to_string(),
+                                            "protect branches '*'".to_string()]),
+                   results.first().unwrap().status);
+
+        let protected_branches = source.project_resources(api_id, "protected_branches");
+        assert_eq!(2, protected_branches.len());
+
+        for protected_branch in &protected_branches {
+            assert_eq!(0, protected_branch["push_access_level"]);
+            assert_eq!(0, protected_branch["merge_access_level"]);
+            assert!(protected_branch["allowed_to_push"].is_null());
+        }
+
+        undo_cutover(&client, &config, &state_file_path, false).unwrap();
+
+        let protected_branches = source.project_resources(api_id, "protected_branches");
+        assert_eq!(1, protected_branches.len());
+
+        let main = protected_branches.first().unwrap();
+        assert_eq!("main", main["name"]);
+        assert_eq!(40, main["push_access_level"]);
+        assert_eq!(30, main["merge_access_level"]);
+        assert_eq!(json!([{"user_id": 7}]), main["allowed_to_push"]);
+    }
+}
@@ -1,28 +1,168 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
 use anyhow::Context;
-use log::info;
+use log::{error, info};
 use reqwest::blocking::Client;
+use serde::Serialize;
 
 use crate::config::InstanceConfig;
+use crate::diag::{get_csv_line, get_flag, get_table, OutputFormat};
+use crate::metrics::{attach_api_metrics, get_api_metrics};
 use crate::migration::domain::GitLabProject;
 use crate::migration::project::{get_all_projects, get_project_branches};
 
-pub fn get_empty_projects(client: &Client, instance: &InstanceConfig) -> anyhow::Result<Vec<GitLabProject>> {
+/// Parallel branch list requests for instances which don't report `empty_repo`
+const BRANCH_CHECK_THREADS: usize = 8;
+
+const TABLE_COLUMNS: [&str; 5] = ["PROJECT", "ARCHIVED", "FORK", "LAST ACTIVITY", "ERROR"];
+
+const CSV_COLUMNS: [&str; 5] = ["project", "archived", "fork", "last_activity_at", "error"];
+
+/// Project without branches or project which couldn't be checked
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EmptyProject {
+    pub project: String,
+    pub archived: bool,
+    pub fork: bool,
+    pub last_activity_at: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Projects are checked by `empty_repo` attribute of project list, branches are requested
+/// only when instance doesn't provide it. Errors of single projects are included to results.
+pub fn get_empty_projects(client: &Client, instance: &InstanceConfig) -> anyhow::Result<Vec<EmptyProject>> {
     info!("get repositories without branches from instance '{}'", instance.git_url);
 
-    let projects = get_all_projects(&client, &instance)
+    let projects = get_all_projects(client, instance)
         .context("cannot get project list from gitlab instance")?;
 
-    let mut empty_projects: Vec<GitLabProject> = vec![];
+    let next_index = AtomicUsize::new(0);
+    let results: Mutex<Vec<(usize, EmptyProject)>> = Mutex::new(vec![]);
+    let api_metrics = get_api_metrics();
+
+    thread::scope(|scope| {
+        for _ in 0..BRANCH_CHECK_THREADS.min(projects.len()) {
+            scope.spawn(|| {
+                let _api_metrics = attach_api_metrics(api_metrics.clone());
+
+                loop {
+                    let index = next_index.fetch_add(1, Ordering::SeqCst);
+
+                    let project = match projects.get(index) {
+                        Some(project) => project,
+                        None => break
+                    };
 
-    for project in projects {
-        let branches = get_project_branches(&client, &instance, project.id)
-            .context("cannot get branch list from project")?;
+                    if let Some(empty_project) = check_project(client, instance, project) {
+                        results.lock().unwrap().push((index, empty_project));
+                    }
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+
+    Ok(results.into_iter().map(|(_, empty_project)| empty_project).collect())
+}
+
+fn check_project(client: &Client, instance: &InstanceConfig, project: &GitLabProject) -> Option<EmptyProject> {
+    let project_path = format!("{}/{}", project.namespace.full_path, project.path);
 
-        if branches.is_empty() {
-            info!("empty project '{}'", project.path);
-            empty_projects.push(project.clone());
+    let is_empty = match project.empty_repo {
+        Some(empty_repo) => Ok(empty_repo),
+        None => get_project_branches(client, instance, project.id)
+            .context("cannot get branch list from project")
+            .map(|branches| branches.is_empty())
+    };
+
+    let error = match is_empty {
+        Ok(false) => return None,
+        Ok(true) => {
+            info!("empty project '{project_path}'");
+            None
+        }
+        Err(e) => {
+            error!("unable to check project '{project_path}': {}", e);
+            Some(format!("{}: {}", e, e.root_cause()))
+        }
+    };
+
+    Some(EmptyProject {
+        project: project_path,
+        archived: project.archived,
+        fork: project.forked_from_project.is_some(),
+        last_activity_at: project.last_activity_at.clone(),
+        error,
+    })
+}
+
+pub fn format_empty_projects(empty_projects: &[EmptyProject], format: OutputFormat) -> anyhow::Result<String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(empty_projects)
+            .context("unable to serialize empty projects"),
+        OutputFormat::Csv => {
+            let mut lines = vec![CSV_COLUMNS.join(",")];
+
+            for item in empty_projects {
+                lines.push(get_csv_line(&[
+                    item.project.to_string(), item.archived.to_string(), item.fork.to_string(),
+                    item.last_activity_at.clone().unwrap_or_default(), item.error.clone().unwrap_or_default(),
+                ]));
+            }
+
+            Ok(lines.join("\n"))
+        }
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = empty_projects.iter().map(|item| vec![
+                item.project.to_string(), get_flag(item.archived), get_flag(item.fork),
+                item.last_activity_at.as_deref().and_then(|d| d.get(..10)).unwrap_or("-").to_string(),
+                item.error.clone().unwrap_or_default(),
+            ]).collect();
+
+            Ok(get_table(&TABLE_COLUMNS, &rows))
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::{json, Value};
+
+    use crate::diag::empty::{format_empty_projects, get_empty_projects};
+    use crate::diag::OutputFormat;
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::init_logging;
+
+    #[test]
+    fn only_projects_without_branches_should_be_returned() {
+        init_logging();
 
-    Ok(empty_projects)
-}
\ No newline at end of file
+        let gitlab = FakeGitLab::start();
+        let group_id = gitlab.add_group("Backend", None);
+        gitlab.add_project(group_id, "api", &["main"]);
+        let drafts_id = gitlab.add_project(group_id, "drafts", &[]);
+        gitlab.set_project_attribute(drafts_id, "archived", json!(true));
+
+        // instance without `empty_repo` attribute, branches are checked
+        let legacy_id = gitlab.add_project(group_id, "legacy", &[]);
+        gitlab.set_project_attribute(legacy_id, "empty_repo", Value::Null);
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let empty_projects = get_empty_projects(&client, &gitlab.instance()).unwrap();
+
+        let paths: Vec<&str> = empty_projects.iter().map(|p| p.project.as_str()).collect();
+        assert_eq!(vec!["backend/drafts", "backend/legacy"], paths);
+        assert!(empty_projects[0].archived);
+
+        assert_eq!(1, gitlab.requests().iter().filter(|r| r.url.contains("/repository/branches")).count());
+
+        let csv = format_empty_projects(&empty_projects, OutputFormat::Csv).unwrap();
+        assert_eq!("backend/drafts,true,false,,", csv.lines().nth(1).unwrap());
+    }
+}
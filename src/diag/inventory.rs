@@ -0,0 +1,264 @@
+use std::path::Path;
+
+use anyhow::Context;
+use log::{error, info};
+use reqwest::blocking::Client;
+use serde::Serialize;
+
+use crate::config::{AppConfig, InstanceConfig};
+use crate::diag::{get_csv_line, get_flag, get_table, OutputFormat};
+use crate::git::clone::execute_git_command;
+use crate::migration::api::{get_feature_all_pages, get_optional_total_count, get_total_count};
+use crate::migration::domain::GitLabProject;
+use crate::migration::project::{get_all_projects, get_project_statistics};
+
+const MEGABYTE: u64 = 1024 * 1024;
+
+const TABLE_COLUMNS: [&str; 14] = [
+    "PROJECT", "REPO MB", "LFS MB", "BRANCHES", "TAGS", "LAST ACTIVITY", "ARCHIVED", "FORK", "WIKI", "REGISTRY",
+    "PACKAGES", "OPEN MRS", "OPEN ISSUES", "RISKS",
+];
+
+const CSV_COLUMNS: [&str; 15] = [
+    "project", "repository_size", "lfs_size", "branches", "tags", "last_activity_at", "archived", "fork",
+    "wiki_size", "registry_repositories", "packages", "open_merge_requests", "open_issues", "risks", "error",
+];
+
+pub struct InventoryOptions {
+    /// Repositories bigger than this size are flagged
+    pub huge_repository_size_mb: u64,
+}
+
+/// Project facts for migration planning, sizes are in bytes.
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct ProjectInventory {
+    pub project: String,
+    pub repository_size: u64,
+    pub lfs_size: u64,
+    pub branches: usize,
+    pub tags: usize,
+    pub last_activity_at: Option<String>,
+    pub archived: bool,
+    pub fork: bool,
+    pub wiki_size: u64,
+    pub registry_repositories: usize,
+    pub packages: usize,
+    pub open_merge_requests: usize,
+    pub open_issues: usize,
+
+    /// Factors which make migration of project harder
+    pub risks: Vec<String>,
+
+    /// Project facts couldn't be gathered
+    pub error: Option<String>,
+}
+
+/// Gather facts about every source project. Errors of single projects are recorded to their inventory.
+pub fn get_inventory(client: &Client, config: &AppConfig,
+                     options: &InventoryOptions) -> anyhow::Result<Vec<ProjectInventory>> {
+    info!("gather inventory of instance '{}'..", config.source.public_url);
+
+    let source_projects = get_all_projects(client, &config.source)
+        .context("cannot get project list from source instance")?;
+
+    let target_projects = get_all_projects(client, &config.target)
+        .context("cannot get project list from target instance")?;
+
+    let git_lfs_installed = execute_git_command(&config.git_bin_path, Path::new("."), &["lfs", "version"]).is_ok();
+
+    let mut results: Vec<ProjectInventory> = vec![];
+
+    for project in &source_projects {
+        let project_path = format!("{}/{}", project.namespace.full_path, project.path);
+
+        let inventory = match get_project_inventory(client, &config.source, project, &project_path) {
+            Ok(mut inventory) => {
+                inventory.risks = get_risks(&inventory, project, &target_projects, git_lfs_installed, options);
+                inventory
+            }
+            Err(e) => {
+                error!("unable to gather inventory of project '{project_path}': {}", e);
+                ProjectInventory {
+                    project: project_path,
+                    error: Some(format!("{}: {}", e, e.root_cause())),
+                    ..ProjectInventory::default()
+                }
+            }
+        };
+
+        results.push(inventory);
+    }
+
+    Ok(results)
+}
+
+fn get_project_inventory(client: &Client, instance: &InstanceConfig, project: &GitLabProject,
+                         project_path: &str) -> anyhow::Result<ProjectInventory> {
+    info!("project '{project_path}'..");
+
+    let project_api_path = format!("projects/{}", project.id);
+
+    let statistics = get_project_statistics(client, instance, project.id)
+        .context("unable to get project statistics")?;
+
+    let registry_repositories: Vec<serde_json::Value> = get_feature_all_pages(
+        client, instance, &format!("{project_api_path}/registry/repositories"))
+        .context("unable to get registry repositories")?.unwrap_or_default();
+
+    Ok(ProjectInventory {
+        project: project_path.to_string(),
+        repository_size: statistics.as_ref().map(|s| s.repository_size).unwrap_or_default(),
+        lfs_size: statistics.as_ref().map(|s| s.lfs_objects_size).unwrap_or_default(),
+        branches: get_total_count(client, instance, &format!("{project_api_path}/repository/branches"))
+            .context("unable to get branch count")?,
+        tags: get_total_count(client, instance, &format!("{project_api_path}/repository/tags"))
+            .context("unable to get tag count")?,
+        last_activity_at: project.last_activity_at.clone(),
+        archived: project.archived,
+        fork: project.forked_from_project.is_some(),
+        wiki_size: statistics.as_ref().map(|s| s.wiki_size).unwrap_or_default(),
+        registry_repositories: registry_repositories.len(),
+        // package registry and merge requests can be disabled for the project
+        packages: get_optional_total_count(client, instance, &format!("{project_api_path}/packages"))
+            .context("unable to get package count")?.unwrap_or_default(),
+        open_merge_requests: get_optional_total_count(client, instance,
+                                                      &format!("{project_api_path}/merge_requests?state=opened"))
+            .context("unable to get merge request count")?.unwrap_or_default(),
+        open_issues: project.open_issues_count.unwrap_or_default(),
+        risks: vec![],
+        error: None,
+    })
+}
+
+fn get_risks(inventory: &ProjectInventory, project: &GitLabProject, target_projects: &[GitLabProject],
+             git_lfs_installed: bool, options: &InventoryOptions) -> Vec<String> {
+    let mut risks: Vec<String> = vec![];
+
+    if inventory.repository_size > options.huge_repository_size_mb * MEGABYTE {
+        risks.push(format!("huge repository ({} MB)", inventory.repository_size / MEGABYTE));
+    }
+
+    if inventory.lfs_size > 0 && !git_lfs_installed {
+        risks.push("lfs objects, git-lfs isn't installed".to_string());
+    }
+
+    let namesake_found = target_projects.iter()
+        .find(|tp| tp.namespace.full_path == project.namespace.full_path &&
+            (tp.path == project.path || tp.name == project.name));
+
+    if let Some(namesake) = namesake_found {
+        risks.push(format!("name collision with target project '{}/{}'", namesake.namespace.full_path, namesake.path));
+    }
+
+    risks
+}
+
+pub fn format_inventory(inventory: &[ProjectInventory], format: OutputFormat) -> anyhow::Result<String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(inventory).context("unable to serialize inventory"),
+        OutputFormat::Csv => {
+            let mut lines = vec![CSV_COLUMNS.join(",")];
+
+            for item in inventory {
+                lines.push(get_csv_line(&[
+                    item.project.to_string(), item.repository_size.to_string(), item.lfs_size.to_string(),
+                    item.branches.to_string(), item.tags.to_string(),
+                    item.last_activity_at.clone().unwrap_or_default(), item.archived.to_string(),
+                    item.fork.to_string(), item.wiki_size.to_string(), item.registry_repositories.to_string(),
+                    item.packages.to_string(), item.open_merge_requests.to_string(), item.open_issues.to_string(),
+                    item.risks.join("; "), item.error.clone().unwrap_or_default(),
+                ]));
+            }
+
+            Ok(lines.join("\n"))
+        }
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = inventory.iter().map(|item| vec![
+                item.project.to_string(), (item.repository_size / MEGABYTE).to_string(),
+                (item.lfs_size / MEGABYTE).to_string(), item.branches.to_string(), item.tags.to_string(),
+                item.last_activity_at.as_deref().and_then(|d| d.get(..10)).unwrap_or("-").to_string(),
+                get_flag(item.archived), get_flag(item.fork), get_flag(item.wiki_size > 0),
+                get_flag(item.registry_repositories > 0), get_flag(item.packages > 0),
+                item.open_merge_requests.to_string(), item.open_issues.to_string(),
+                match &item.error {
+                    Some(e) => format!("error: {e}"),
+                    None => item.risks.join("; ")
+                },
+            ]).collect();
+
+            Ok(get_table(&TABLE_COLUMNS, &rows))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::diag::inventory::{format_inventory, get_inventory, InventoryOptions};
+    use crate::diag::OutputFormat;
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    #[test]
+    fn inventory_should_contain_project_facts_and_risks() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main", "develop"]);
+        source.add_tag(api_id, "v1.0", "main");
+        source.set_lfs_objects_size(api_id, 5 * 1024 * 1024);
+        source.set_project_attribute(api_id, "statistics", json!({"repository_size": 3 * 1024 * 1024, "wiki_size": 10}));
+        source.set_project_attribute(api_id, "last_activity_at", json!("2022-10-01T10:00:00.000Z"));
+        source.set_project_attribute(api_id, "open_issues_count", json!(4));
+        source.add_project_resource(api_id, "merge_requests", json!({"id": 1, "iid": 1, "state": "opened"}));
+        source.add_project_resource(api_id, "merge_requests", json!({"id": 2, "iid": 2, "state": "merged"}));
+        source.add_project_resource(api_id, "registry/repositories", json!({"id": 1, "path": "backend/api"}));
+
+        let drafts_id = source.add_project(group_id, "drafts", &[]);
+        source.set_project_attribute(drafts_id, "archived", json!(true));
+        source.set_project_attribute(drafts_id, "merge_requests_enabled", json!(false));
+        source.set_project_attribute(drafts_id, "packages_enabled", json!(false));
+        source.set_project_attribute(drafts_id, "container_registry_enabled", json!(false));
+        source.set_project_attribute(drafts_id, "forked_from_project",
+                                     json!({"id": 1, "path_with_namespace": "libs/drafts"}));
+
+        let target = FakeGitLab::start();
+        let target_group_id = target.add_group("Backend", None);
+        target.add_project(target_group_id, "drafts", &[]);
+
+        let tmp_dir = TempDir::new().unwrap();
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.git_bin_path = "git-without-lfs-support".to_string();
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let inventory = get_inventory(&client, &config, &InventoryOptions { huge_repository_size_mb: 2 }).unwrap();
+
+        let api = &inventory[0];
+        assert_eq!("backend/api", api.project);
+        assert_eq!(3 * 1024 * 1024, api.repository_size);
+        assert_eq!((2, 1), (api.branches, api.tags));
+        assert_eq!((1, 4, 1), (api.open_merge_requests, api.open_issues, api.registry_repositories));
+        assert_eq!(vec!["huge repository (3 MB)", "lfs objects, git-lfs isn't installed"], api.risks);
+
+        let drafts = &inventory[1];
+        assert!(drafts.archived && drafts.fork);
+        assert!(drafts.error.is_none(), "{:?}", drafts.error);
+        assert_eq!((0, 0, 0), (drafts.packages, drafts.open_merge_requests, drafts.registry_repositories));
+        assert_eq!(vec!["name collision with target project 'backend/drafts'"], drafts.risks);
+
+        let table = format_inventory(&inventory, OutputFormat::Table).unwrap();
+        assert!(table.lines().nth(1).unwrap().starts_with("backend/api     3        5       2         1     2022-10-01"),
+                "{table}");
+
+        let csv = format_inventory(&inventory, OutputFormat::Csv).unwrap();
+        assert!(csv.lines().nth(2).unwrap().starts_with("backend/drafts,0,0,0,0,,true,true,"), "{csv}");
+
+        let json: serde_json::Value = serde_json::from_str(&format_inventory(&inventory, OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(5 * 1024 * 1024, json[0]["lfs_size"]);
+    }
+}
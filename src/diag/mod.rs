@@ -1 +1,67 @@
-pub mod empty;
\ No newline at end of file
+pub mod empty;
+pub mod inventory;
+
+pub const OUTPUT_FORMATS: [&str; 3] = ["table", "csv", "json"];
+
+/// Output format of diagnostic reports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name {
+            "table" => Some(OutputFormat::Table),
+            "csv" => Some(OutputFormat::Csv),
+            "json" => Some(OutputFormat::Json),
+            _ => None
+        }
+    }
+}
+
+/// CSV line, values with separators, quotes or line breaks are quoted.
+pub fn get_csv_line(values: &[String]) -> String {
+    values.iter()
+        .map(|value| {
+            if value.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", value.replace('"', "\"\""))
+            } else {
+                value.to_string()
+            }
+        })
+        .collect::<Vec<String>>().join(",")
+}
+
+pub fn get_flag(value: bool) -> String {
+    if value { "yes".to_string() } else { "-".to_string() }
+}
+
+/// Columns are aligned by the longest value, the last column isn't padded.
+pub fn get_table(columns: &[&str], rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = columns.iter().enumerate()
+        .map(|(index, column)| rows.iter().map(|r| r[index].chars().count())
+            .chain([column.len()]).max().unwrap_or_default())
+        .collect();
+
+    let header: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+
+    [header].iter().chain(rows.iter())
+        .map(|row| row.iter().zip(&widths)
+            .map(|(value, width)| format!("{value:width$}"))
+            .collect::<Vec<String>>().join("  ").trim_end().to_string())
+        .collect::<Vec<String>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diag::get_csv_line;
+
+    #[test]
+    fn csv_values_should_be_quoted_when_needed() {
+        assert_eq!("api,\"a, b\",\"say \"\"hi\"\"\",",
+                   get_csv_line(&["api".to_string(), "a, b".to_string(), "say \"hi\"".to_string(), "".to_string()]));
+    }
+}
@@ -1,52 +1,95 @@
-use std::{env, fs};
+use std::fs;
+use std::io::Read;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Stdio};
 
 use anyhow::{anyhow, Context};
 use log::{debug, error};
 
-const REPO_TMP_DIR: &str = "gmt-tmp";
+/// Progress lines reporting transferred size, e.g. `Receiving objects:  45% (450/1000), 1.20 MiB | 2.00 MiB/s`
+const TRANSFER_PROGRESS_PREFIXES: [&str; 2] = ["Receiving objects:", "Writing objects:"];
 
-pub fn copy_git_repo_from_one_remote_to_another(repo_name: &str,
-                                                source_git_url: &str, source_group_path: &str,
-                                                target_group_path: &str,
-                                                target_git_url: &str) -> anyhow::Result<()> {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GitTransfer {
+    /// Received from source instance
+    Received,
 
-    if Path::new(REPO_TMP_DIR).exists() {
-        fs::remove_dir_all(REPO_TMP_DIR)?;
-    }
+    /// Pushed to target instance
+    Pushed,
+}
+
+/// Copy all branches and tags, LFS objects too if `lfs` is set (requires `git-lfs`).
+/// Project paths contain group path, e.g. `backend/api`.
+/// Bytes received from source and pushed to target are reported to `on_transfer` as they go.
+pub fn copy_git_repo_from_one_remote_to_another(git_bin_path: &str, tmp_dir: &Path, source_repo_url: &str,
+                                                target_git_url: &str, target_project_path: &str, lfs: bool,
+                                                on_transfer: &mut dyn FnMut(GitTransfer, u64)) -> anyhow::Result<()> {
 
-    fs::create_dir(REPO_TMP_DIR).context("cannot create temporary directory")?;
+    let cloned_repo_dir = tmp_dir.join(target_project_path);
+
+    if cloned_repo_dir.exists() {
+        fs::remove_dir_all(&cloned_repo_dir)?;
+    }
 
-    let args = format!("clone --mirror {source_git_url}/{source_group_path}/{repo_name} {REPO_TMP_DIR}/.git");
+    fs::create_dir_all(&cloned_repo_dir).context("cannot create temporary directory")?;
 
-    execute_git_command(&args).context("unable to clone source repository")?;
+    let git_dir = cloned_repo_dir.join(".git");
+    let git_dir = format!("{}", git_dir.display());
 
-    let current_dir = env::current_dir()?;
-    let cloned_repo_dir = current_dir.join(REPO_TMP_DIR);
+    let result = push_mirror_clone(git_bin_path, &cloned_repo_dir, source_repo_url, &git_dir,
+                                   &format!("{target_git_url}/{target_project_path}.git"), on_transfer)
+        .and_then(|_| push_tags(git_bin_path, &cloned_repo_dir, on_transfer))
+        .and_then(|_| if lfs { push_lfs_objects(git_bin_path, &cloned_repo_dir) } else { Ok(()) });
 
-    env::set_current_dir(&cloned_repo_dir)?;
+    fs::remove_dir_all(&cloned_repo_dir)?;
 
-    let args = format!("remote add --mirror=fetch secondary {target_git_url}/{target_group_path}/{repo_name}.git");
-    execute_git_command(&args).context("unable to set remote repository for target git instance")?;
+    result
+}
 
-    execute_git_command("fetch origin").context("unable to fetch origin repo")?;
+fn push_mirror_clone(git_bin_path: &str, repo_dir: &Path, source_repo_url: &str, git_dir: &str,
+                     target_repo_url: &str, on_transfer: &mut dyn FnMut(GitTransfer, u64)) -> anyhow::Result<()> {
+    execute_git_command_with_progress(git_bin_path, repo_dir,
+                                      &["clone", "--mirror", "--progress", source_repo_url, git_dir],
+                                      &mut |bytes| on_transfer(GitTransfer::Received, bytes))
+        .context("unable to clone source repository")?;
 
-    execute_git_command("push secondary --all").context("unable to fetch origin repo")?;
+    execute_git_command(git_bin_path, repo_dir,
+                        &["remote", "add", "--mirror=fetch", "secondary", target_repo_url])
+        .context("unable to set remote repository for target git instance")?;
 
-    env::set_current_dir(current_dir)?;
+    execute_git_command(git_bin_path, repo_dir, &["fetch", "origin"])
+        .context("unable to fetch origin repo")?;
 
-    fs::remove_dir_all(&cloned_repo_dir)?;
+    execute_git_command_with_progress(git_bin_path, repo_dir, &["push", "--progress", "secondary", "--all"],
+                                      &mut |bytes| on_transfer(GitTransfer::Pushed, bytes))
+        .context("unable to push repo to target instance")?;
 
     Ok(())
 }
 
-fn execute_git_command(args_row: &str) -> anyhow::Result<String> {
-    debug!("args '{}'", args_row);
+/// `push --all` sends branches only
+fn push_tags(git_bin_path: &str, repo_dir: &Path, on_transfer: &mut dyn FnMut(GitTransfer, u64)) -> anyhow::Result<()> {
+    execute_git_command_with_progress(git_bin_path, repo_dir, &["push", "--progress", "secondary", "--tags"],
+                                      &mut |bytes| on_transfer(GitTransfer::Pushed, bytes))
+        .context("unable to push tags to target instance")
+}
+
+/// LFS objects aren't part of git objects, they are fetched from source and pushed to target by `git-lfs`.
+fn push_lfs_objects(git_bin_path: &str, repo_dir: &Path) -> anyhow::Result<()> {
+    execute_git_command(git_bin_path, repo_dir, &["lfs", "fetch", "--all", "origin"])
+        .context("unable to fetch lfs objects from source repository")?;
 
-    let args: Vec<&str> = args_row.split(" ").collect();
+    execute_git_command(git_bin_path, repo_dir, &["lfs", "push", "--all", "secondary"])
+        .context("unable to push lfs objects to target instance")?;
 
-    let output = Command::new("/usr/bin/git").args(args).output()?;
+    Ok(())
+}
+
+pub fn execute_git_command(git_bin_path: &str, work_dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    debug!("args '{}'", args.join(" "));
+
+    let output = Command::new(git_bin_path).args(args).current_dir(work_dir).output()
+        .context("unable to execute git")?;
 
     if output.status.success() {
         let stdout = format!("{}", String::from_utf8_lossy(&output.stdout));
@@ -66,4 +109,114 @@ fn execute_git_command(args_row: &str) -> anyhow::Result<String> {
 
         Err(anyhow!("git command error"))
     }
-}
\ No newline at end of file
+}
+
+/// Execute git command and read its progress output from stderr while it runs,
+/// growth of transferred size is reported to `on_bytes`. Standard output of command is dropped.
+pub fn execute_git_command_with_progress(git_bin_path: &str, work_dir: &Path, args: &[&str],
+                                         on_bytes: &mut dyn FnMut(u64)) -> anyhow::Result<()> {
+    debug!("args '{}'", args.join(" "));
+
+    let mut child = Command::new(git_bin_path).args(args).current_dir(work_dir)
+        .stdout(Stdio::null()).stderr(Stdio::piped())
+        .spawn().context("unable to execute git")?;
+
+    let mut stderr = child.stderr.take().context("unable to read git output")?;
+
+    let mut output = String::new();
+    let mut line: Vec<u8> = vec![];
+    let mut reported_bytes = 0;
+    let mut buffer = [0; 1024];
+
+    loop {
+        let read = stderr.read(&mut buffer).context("unable to read git output")?;
+
+        if read == 0 {
+            break;
+        }
+
+        // progress lines are redrawn with carriage return
+        for byte in &buffer[..read] {
+            if *byte != b'\r' && *byte != b'\n' {
+                line.push(*byte);
+                continue
+            }
+
+            let text = String::from_utf8_lossy(&line).to_string();
+            line.clear();
+
+            if let Some(bytes) = get_transferred_bytes(&text) {
+                if bytes > reported_bytes {
+                    on_bytes(bytes - reported_bytes);
+                }
+                reported_bytes = bytes;
+
+            } else if !TRANSFER_PROGRESS_PREFIXES.iter().any(|prefix| text.starts_with(prefix)) {
+                // sizes of the next transfer stage start from zero
+                reported_bytes = 0;
+
+                if !text.trim().is_empty() {
+                    output.push_str(&text);
+                    output.push('\n');
+                }
+            }
+        }
+    }
+
+    output.push_str(&String::from_utf8_lossy(&line));
+
+    let status = child.wait().context("unable to execute git")?;
+
+    if status.success() {
+        debug!("<stderr>");
+        debug!("{}", output);
+        debug!("</stderr>");
+
+        Ok(())
+
+    } else {
+        error!("<stderr>");
+        error!("{}", output);
+        error!("</stderr>");
+
+        Err(anyhow!("git command error"))
+    }
+}
+
+/// Transferred size of git progress line, e.g. `Writing objects: 100% (3/3), 232 bytes | 232.00 KiB/s, done.`
+pub fn get_transferred_bytes(progress_line: &str) -> Option<u64> {
+    let progress_line = progress_line.trim_start_matches("remote:").trim();
+
+    TRANSFER_PROGRESS_PREFIXES.iter().find(|prefix| progress_line.starts_with(*prefix))?;
+
+    let size = progress_line.split("), ").nth(1)?
+        .split([',', '|']).next()?.trim();
+
+    let (value, unit) = size.split_once(' ')?;
+    let value: f64 = value.parse().ok()?;
+
+    let multiplier = match unit {
+        "bytes" | "byte" => 1,
+        "KiB" => 1024,
+        "MiB" => 1024 * 1024,
+        "GiB" => 1024 * 1024 * 1024,
+        _ => return None
+    };
+
+    Some((value * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::clone::get_transferred_bytes;
+
+    #[test]
+    fn transferred_bytes_should_be_parsed_from_progress_lines() {
+        assert_eq!(Some(1258291), get_transferred_bytes("Receiving objects:  45% (450/1000), 1.20 MiB | 2.00 MiB/s"));
+        assert_eq!(Some(232), get_transferred_bytes("Writing objects: 100% (3/3), 232 bytes | 232.00 KiB/s, done."));
+        assert_eq!(Some(10240), get_transferred_bytes("Writing objects:  50% (1/2), 10.00 KiB"));
+        assert_eq!(None, get_transferred_bytes("Receiving objects:   0% (1/1000)"));
+        assert_eq!(None, get_transferred_bytes("remote: Counting objects: 100% (3/3), done."));
+        assert_eq!(None, get_transferred_bytes("Resolving deltas: 100% (2/2), done."));
+    }
+}
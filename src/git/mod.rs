@@ -1 +1,2 @@
-pub mod clone;
\ No newline at end of file
+pub mod clone;
+pub mod sync;
\ No newline at end of file
@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{anyhow, Context};
+use log::{debug, error, info};
+
+use crate::git::clone::execute_git_command;
+
+const TARGET_REFS_PREFIX: &str = "refs/gmt-target/";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepoSyncResult {
+    /// Refs pushed to target repository
+    pub updated_refs: Vec<String>,
+
+    /// Refs which target has changed independently, they are not pushed without `force`
+    pub diverged_refs: Vec<String>,
+}
+
+/// Push new commits and tags from source repository to target.
+///
+/// Branches are updated only if target head is an ancestor of source head, unless `force` is set.
+/// Refs existing only on target are left untouched.
+pub fn sync_git_repo(git_bin_path: &str, tmp_dir: &Path, repo_dir_name: &str,
+                     source_repo_url: &str, target_repo_url: &str,
+                     force: bool) -> anyhow::Result<RepoSyncResult> {
+    let repo_dir = tmp_dir.join(repo_dir_name);
+
+    if repo_dir.exists() {
+        fs::remove_dir_all(&repo_dir)?;
+    }
+
+    fs::create_dir_all(&repo_dir).context("cannot create temporary directory")?;
+
+    let result = sync_refs(git_bin_path, &repo_dir, source_repo_url, target_repo_url, force);
+
+    fs::remove_dir_all(&repo_dir)?;
+
+    result
+}
+
+fn sync_refs(git_bin_path: &str, repo_dir: &Path, source_repo_url: &str,
+             target_repo_url: &str, force: bool) -> anyhow::Result<RepoSyncResult> {
+    execute_git_command(git_bin_path, repo_dir, &["clone", "--mirror", source_repo_url, "."])
+        .context("unable to clone source repository")?;
+
+    let target_refspecs = [
+        format!("+refs/heads/*:{TARGET_REFS_PREFIX}heads/*"),
+        format!("+refs/tags/*:{TARGET_REFS_PREFIX}tags/*"),
+    ];
+
+    let mut fetch_args = vec!["fetch", "--no-tags", target_repo_url];
+    fetch_args.extend(target_refspecs.iter().map(|r| r.as_str()));
+
+    execute_git_command(git_bin_path, repo_dir, &fetch_args)
+        .context("unable to fetch target repository")?;
+
+    let source_refs = get_refs(git_bin_path, repo_dir, &["refs/heads", "refs/tags"])?;
+    let target_refs: BTreeMap<String, String> = get_refs(git_bin_path, repo_dir, &[TARGET_REFS_PREFIX])?
+        .into_iter()
+        .map(|(name, sha)| (name.replacen(TARGET_REFS_PREFIX, "refs/", 1), sha))
+        .collect();
+
+    let mut result = RepoSyncResult::default();
+
+    let mut push_refspecs: Vec<String> = vec![];
+
+    for (name, source_sha) in &source_refs {
+        match target_refs.get(name) {
+            Some(target_sha) if target_sha == source_sha => {}
+            Some(target_sha) => {
+                let fast_forward = name.starts_with("refs/heads/") &&
+                    is_ancestor(git_bin_path, repo_dir, target_sha, source_sha)?;
+
+                if fast_forward {
+                    push_refspecs.push(format!("{name}:{name}"));
+                    result.updated_refs.push(name.to_string());
+
+                } else if force {
+                    info!("ref '{name}' has diverged on target, force update");
+                    push_refspecs.push(format!("+{name}:{name}"));
+                    result.updated_refs.push(name.to_string());
+
+                } else {
+                    info!("ref '{name}' has diverged on target, skip");
+                    result.diverged_refs.push(name.to_string());
+                }
+            }
+            None => {
+                push_refspecs.push(format!("{name}:{name}"));
+                result.updated_refs.push(name.to_string());
+            }
+        }
+    }
+
+    if !push_refspecs.is_empty() {
+        let mut push_args = vec!["push", target_repo_url];
+        push_args.extend(push_refspecs.iter().map(|r| r.as_str()));
+
+        execute_git_command(git_bin_path, repo_dir, &push_args)
+            .context("unable to push refs to target repository")?;
+    }
+
+    Ok(result)
+}
+
+fn get_refs(git_bin_path: &str, repo_dir: &Path, prefixes: &[&str]) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut args = vec!["for-each-ref", "--format=%(refname) %(objectname)"];
+    args.extend(prefixes);
+
+    let output = execute_git_command(git_bin_path, repo_dir, &args)?;
+
+    Ok(output.lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(name, sha)| (name.to_string(), sha.to_string()))
+        .collect())
+}
+
+/// `merge-base --is-ancestor` exits with 1 if commit isn't an ancestor, other codes are errors, e.g. missing object.
+fn is_ancestor(git_bin_path: &str, repo_dir: &Path, ancestor: &str, commit: &str) -> anyhow::Result<bool> {
+    debug!("check if '{ancestor}' is ancestor of '{commit}'");
+
+    let output = Command::new(git_bin_path)
+        .args(["merge-base", "--is-ancestor", ancestor, commit])
+        .current_dir(repo_dir)
+        .output().context("unable to execute git")?;
+
+    match output.status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        _ => {
+            error!("<stderr>");
+            error!("{}", String::from_utf8_lossy(&output.stderr));
+            error!("</stderr>");
+
+            Err(anyhow!("git command error"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::git::sync::is_ancestor;
+    use crate::tests::gitlab::git;
+
+    #[test]
+    fn ancestor_check_should_fail_on_git_error() {
+        let repo_dir = TempDir::new().unwrap();
+        let repo_path = repo_dir.path();
+
+        git(repo_path, &["init", "--quiet"]);
+        let tree = git(repo_path, &["mktree"]);
+        let root_commit = git(repo_path, &["commit-tree", &tree, "-m", "root"]);
+        let commit = git(repo_path, &["commit-tree", &tree, "-p", &root_commit, "-m", "next"]);
+
+        assert!(is_ancestor("git", repo_path, &root_commit, &commit).unwrap());
+        assert!(!is_ancestor("git", repo_path, &commit, &root_commit).unwrap());
+
+        let missing_commit = "0123456789012345678901234567890123456789";
+        assert!(is_ancestor("git", repo_path, missing_commit, &commit).is_err());
+        assert!(is_ancestor("git", repo_path, &commit, missing_commit).is_err());
+    }
+}
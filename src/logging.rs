@@ -3,39 +3,57 @@ use log4rs::append::rolling_file::policy::compound::roll::fixed_window::FixedWin
 use log4rs::append::rolling_file::policy::compound::trigger::size::SizeTrigger;
 use log4rs::append::rolling_file::RollingFileAppender;
 use log4rs::config::{Appender, Config, Logger, Root};
+use log4rs::encode::Encode;
+use log4rs::encode::json::JsonEncoder;
 use log4rs::encode::pattern::PatternEncoder;
 use log4rs::filter::threshold::ThresholdFilter;
 use log::LevelFilter;
+use log_mdc::InsertGuard;
+
+use crate::config::LogFormat;
 
 const FILE_APPENDER_NAME: &str = "file";
 
 const LOG_FILE_PATH: &str = "gmt.log";
 
+/// Path of source project, e.g. `backend/api`
+pub const LOG_FIELD_PROJECT: &str = "project";
+
+pub const LOG_FIELD_PHASE: &str = "phase";
+
+/// Public url of instance which API is called
+pub const LOG_FIELD_INSTANCE: &str = "instance";
+
+pub const LOG_FIELD_DURATION_MS: &str = "duration_ms";
+
+/// See `get_error_class`
+pub const LOG_FIELD_ERROR_CLASS: &str = "error_class";
+
 fn get_logging_level_from_string(level: &str) -> LevelFilter {
-    return match level {
+    match level {
         "debug" => LevelFilter::Debug,
         "error" => LevelFilter::Error,
         "warn" => LevelFilter::Warn,
         "trace" => LevelFilter::Trace,
         "off" => LevelFilter::Off,
         _ => LevelFilter::Info
-    };
+    }
 }
 
-pub fn get_logging_config(logging_level: &str) -> Config {
+pub fn get_logging_config(logging_level: &str, log_format: LogFormat) -> Config {
     let level = get_logging_level_from_string(logging_level);
 
     Config::builder()
-        .appender(get_rolling_appender(level))
+        .appender(get_rolling_appender(level, log_format))
         .logger(get_default_logger(level))
         .build(
             Root::builder()
                 .appender(FILE_APPENDER_NAME)
                 .build(level)
-        ).expect(&format!("unable to create log file '{}'", LOG_FILE_PATH))
+        ).unwrap_or_else(|_| panic!("unable to create log file '{}'", LOG_FILE_PATH))
 }
 
-fn get_rolling_appender(level: LevelFilter) -> Appender {
+fn get_rolling_appender(level: LevelFilter, log_format: LogFormat) -> Appender {
     let log_file_format = format!("{}.{{}}", LOG_FILE_PATH);
 
     let fixed_window_roller = FixedWindowRoller::builder()
@@ -47,7 +65,7 @@ fn get_rolling_appender(level: LevelFilter) -> Appender {
         Box::new(size_trigger), Box::new(fixed_window_roller)
     );
     let rolling_appender = RollingFileAppender::builder()
-        .encoder(get_encoder())
+        .encoder(get_encoder(log_format))
         .build(LOG_FILE_PATH, Box::new(policy))
         .expect("couldn't build rolling appender");
 
@@ -56,10 +74,71 @@ fn get_rolling_appender(level: LevelFilter) -> Appender {
         .build(FILE_APPENDER_NAME, Box::new(rolling_appender))
 }
 
-fn get_encoder() -> Box<PatternEncoder> {
-    Box::new(PatternEncoder::new("{d(%Y-%m-%d %H:%M:%S)} - {l} - [{M}] - {m}{n}"))
+/// JSON lines carry log fields of current thread in `mdc` object
+fn get_encoder(log_format: LogFormat) -> Box<dyn Encode> {
+    match log_format {
+        LogFormat::Text => Box::new(PatternEncoder::new("{d(%Y-%m-%d %H:%M:%S)} - {l} - [{M}] - {m}{n}")),
+        LogFormat::Json => Box::new(JsonEncoder::new())
+    }
 }
 
 fn get_default_logger(level: LevelFilter) -> Logger {
     Logger::builder().build("default", level)
-}
\ No newline at end of file
+}
+
+/// Add field to log lines of current thread until returned guard is dropped, e.g. project path.
+/// Fields are written in `json` log format only.
+pub fn log_field(key: &str, value: &str) -> InsertGuard {
+    log_mdc::insert_scoped(key, value)
+}
+
+/// Coarse error class to group failures on dashboards: `http`, `http-timeout`, `api-response`, `decode`,
+/// `git`, `io` or `other`.
+pub fn get_error_class(error: &anyhow::Error) -> &'static str {
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return if e.is_timeout() {
+                "http-timeout"
+            } else if e.is_decode() {
+                "decode"
+            } else {
+                "http"
+            }
+        }
+
+        if cause.is::<serde_json::Error>() {
+            return "decode"
+        }
+
+        if cause.is::<std::io::Error>() {
+            return "io"
+        }
+    }
+
+    match error.root_cause().to_string().as_str() {
+        "unexpected server response" => "api-response",
+        "git command error" => "git",
+        _ => "other"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use crate::logging::get_error_class;
+
+    #[test]
+    fn errors_should_be_classified_by_cause() {
+        let api_error: anyhow::Result<()> = Err(anyhow!("unexpected server response"));
+        assert_eq!("api-response", get_error_class(&api_error.context("unable to create project").unwrap_err()));
+
+        let git_error: anyhow::Result<()> = Err(anyhow!("git command error"));
+        assert_eq!("git", get_error_class(&git_error.context("unable to clone source repository").unwrap_err()));
+
+        let io_error = std::fs::read("missing-file").context("unable to read file").unwrap_err();
+        assert_eq!("io", get_error_class(&io_error));
+
+        assert_eq!("other", get_error_class(&anyhow!("fork repository copy has failed")));
+    }
+}
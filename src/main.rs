@@ -1,59 +1,271 @@
+use std::fs;
 use std::path::Path;
 use std::process::exit;
 
-use clap::{App, SubCommand};
+use anyhow::Context;
+use clap::{App, AppSettings, Arg, SubCommand};
 use reqwest::blocking::ClientBuilder;
 
-use crate::config::file::load_config_from_file;
-use crate::diag::empty::get_empty_projects;
+use crate::config::ArchivedProjectsPolicy;
+use crate::config::file::{check_config_file, load_config_from_file};
+use crate::config::template::get_config_template;
+use crate::cutover::{CUTOVER_STATE_FILE_PATH, CutoverMode, CutoverOptions, CutoverStatus, cutover_source_projects, undo_cutover};
+use crate::diag::{OUTPUT_FORMATS, OutputFormat};
+use crate::diag::empty::{format_empty_projects, get_empty_projects};
+use crate::diag::inventory::{format_inventory, get_inventory, InventoryOptions};
 use crate::logging::get_logging_config;
-use crate::migration::migrate_gitlab_instance;
+use crate::migration::collision::TargetPlacement;
+use crate::migration::{migrate_gitlab_instance_with_progress, MigrationReport};
+use crate::migration::scope::get_scoped_projects;
+use crate::mirror::{create_push_mirrors, get_push_mirrors_status, MirrorStatus, ProjectMirror, remove_push_mirrors};
+use crate::phases::{Phase, PHASES, PhaseReport, run_phase_on_migrated_projects};
+use crate::progress::TerminalProgress;
+use crate::sync::{ProjectSyncStatus, sync_migrated_projects};
+use crate::verify::{verify_migration, VerifyOptions};
 
 pub mod config;
 pub mod logging;
+pub mod metrics;
 pub mod git;
 pub mod migration;
 pub mod diag;
+pub mod verify;
+pub mod sync;
+pub mod mirror;
+pub mod cutover;
+pub mod phases;
+pub mod progress;
 
 #[cfg(test)]
 pub mod tests;
 
 const MIGRATE_COMMAND: &str = "migrate";
 const SHOW_EMPTY_COMMAND: &str = "show-empty";
+const INVENTORY_COMMAND: &str = "inventory";
+const VERIFY_COMMAND: &str = "verify";
+const SYNC_COMMAND: &str = "sync";
+const MIRROR_COMMAND: &str = "mirror";
+const MIRROR_CREATE_COMMAND: &str = "create";
+const MIRROR_STATUS_COMMAND: &str = "status";
+const MIRROR_REMOVE_COMMAND: &str = "remove";
+const CUTOVER_COMMAND: &str = "cutover";
+const PHASE_COMMAND: &str = "phase";
+const CONFIG_COMMAND: &str = "config";
+const CONFIG_VALIDATE_COMMAND: &str = "validate";
+const CONFIG_INIT_COMMAND: &str = "init";
+
+const COUNTS_ARG: &str = "counts";
+const VISIBILITY_ARG: &str = "visibility";
+const FORCE_ARG: &str = "force";
+const MODIFY_SOURCE_ARG: &str = "modify-source";
+const MODE_ARG: &str = "mode";
+const BANNER_ARG: &str = "banner";
+const UNDO_ARG: &str = "undo";
+const PHASE_NAME_ARG: &str = "name";
+const DRY_RUN_ARG: &str = "dry-run";
+const FORMAT_ARG: &str = "format";
+const HUGE_REPO_SIZE_ARG: &str = "huge-repo-size";
+const TARGET_ARG: &str = "target";
+const FILE_ARG: &str = "file";
+
+const CONFIG_FILE_PATH: &str = "gmt.yml";
 
 const EXIT_CODE_ERROR: i32 = -1;
 
 fn main() {
+    let phase_names: Vec<&str> = PHASES.iter().map(|p| p.get_name()).collect();
+
     let matches = App::new("Migration Tool for GitLab")
         .version("0.3.0")
         .about("Migrate groups and projects from one GitLab instance to another.")
         .subcommand(SubCommand::with_name(MIGRATE_COMMAND)
             .about("Migrate groups and projects from source GitLab instance to target instance")
+            .arg(Arg::with_name(DRY_RUN_ARG)
+                .long(DRY_RUN_ARG)
+                .help("Show where projects would be created, name and path collisions"))
         )
         .subcommand(SubCommand::with_name(SHOW_EMPTY_COMMAND)
             .about("Show projects without branches from source GitLab instance")
+            .arg(Arg::with_name(FORMAT_ARG)
+                .long(FORMAT_ARG)
+                .takes_value(true)
+                .possible_values(&OUTPUT_FORMATS)
+                .default_value("table")
+                .help("Output format"))
+            .arg(Arg::with_name(TARGET_ARG)
+                .long(TARGET_ARG)
+                .help("Check projects of target instance"))
+        )
+        .subcommand(SubCommand::with_name(INVENTORY_COMMAND)
+            .about("Show size, activity and migration risks of source projects")
+            .arg(Arg::with_name(FORMAT_ARG)
+                .long(FORMAT_ARG)
+                .takes_value(true)
+                .possible_values(&OUTPUT_FORMATS)
+                .default_value("table")
+                .help("Output format"))
+            .arg(Arg::with_name(HUGE_REPO_SIZE_ARG)
+                .long(HUGE_REPO_SIZE_ARG)
+                .takes_value(true)
+                .value_name("MB")
+                .default_value("1024")
+                .help("Flag repositories bigger than this size"))
+        )
+        .subcommand(SubCommand::with_name(VERIFY_COMMAND)
+            .about("Compare migrated projects on target instance with source projects")
+            .arg(Arg::with_name(COUNTS_ARG)
+                .long(COUNTS_ARG)
+                .help("Compare issue and merge request counts"))
+            .arg(Arg::with_name(VISIBILITY_ARG)
+                .long(VISIBILITY_ARG)
+                .help("Compare project visibility, target projects are created as private by migrate"))
+        )
+        .subcommand(SubCommand::with_name(SYNC_COMMAND)
+            .about("Push new commits and tags from source projects to already migrated projects")
+            .arg(Arg::with_name(FORCE_ARG)
+                .long(FORCE_ARG)
+                .help("Overwrite branches and tags which have diverged on target instance"))
+        )
+        .subcommand(SubCommand::with_name(MIRROR_COMMAND)
+            .about("Manage push mirrors from source projects to migrated projects")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name(MIRROR_CREATE_COMMAND)
+                .about("Create push mirror on every migrated source project"))
+            .subcommand(SubCommand::with_name(MIRROR_STATUS_COMMAND)
+                .about("Show push mirrors status"))
+            .subcommand(SubCommand::with_name(MIRROR_REMOVE_COMMAND)
+                .about("Remove push mirrors created by 'mirror create'"))
+        )
+        .subcommand(SubCommand::with_name(CUTOVER_COMMAND)
+            .about("Freeze verified source projects, shows planned changes unless --modify-source is set")
+            .arg(Arg::with_name(MODIFY_SOURCE_ARG)
+                .long(MODIFY_SOURCE_ARG)
+                .help("Apply changes to source instance"))
+            .arg(Arg::with_name(MODE_ARG)
+                .long(MODE_ARG)
+                .takes_value(true)
+                .possible_values(&["archive", "protect"])
+                .default_value("archive")
+                .help("Archive source projects or protect all their branches"))
+            .arg(Arg::with_name(BANNER_ARG)
+                .long(BANNER_ARG)
+                .help("Add notice with new project url to source project description"))
+            .arg(Arg::with_name(UNDO_ARG)
+                .long(UNDO_ARG)
+                .help(&format!("Revert changes recorded in '{CUTOVER_STATE_FILE_PATH}'")))
+        )
+        .subcommand(SubCommand::with_name(PHASE_COMMAND)
+            .about("Run optional migration phase for already migrated projects")
+            .arg(Arg::with_name(PHASE_NAME_ARG)
+                .required(true)
+                .possible_values(&phase_names)
+                .help("Phase name"))
+            .arg(Arg::with_name(DRY_RUN_ARG)
+                .long(DRY_RUN_ARG)
+                .help("Show planned changes without changing target instance"))
+        )
+        .subcommand(SubCommand::with_name(CONFIG_COMMAND)
+            .about("Check config file or generate a new one")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name(CONFIG_VALIDATE_COMMAND)
+                .about("Check config file for unknown keys, invalid urls and missing tokens")
+                .arg(Arg::with_name(FILE_ARG)
+                    .default_value(CONFIG_FILE_PATH)
+                    .help("Config file")))
+            .subcommand(SubCommand::with_name(CONFIG_INIT_COMMAND)
+                .about("Write commented config template with default values")
+                .arg(Arg::with_name(FILE_ARG)
+                    .default_value(CONFIG_FILE_PATH)
+                    .help("Config file"))
+                .arg(Arg::with_name(FORCE_ARG)
+                    .long(FORCE_ARG)
+                    .help("Overwrite existing file")))
         )
         .get_matches();
 
-    let config_file_path = Path::new("gmt.yml");
+    // config commands work without valid config file
+    if let Some(config_matches) = matches.subcommand_matches(CONFIG_COMMAND) {
+        if let Some(validate_matches) = config_matches.subcommand_matches(CONFIG_VALIDATE_COMMAND) {
+            let file_path = validate_matches.value_of(FILE_ARG).unwrap_or(CONFIG_FILE_PATH);
+
+            match check_config_file(Path::new(file_path)) {
+                Ok((_, problems)) if problems.is_empty() => println!("config '{file_path}' is valid"),
+                Ok((_, problems)) => {
+                    println!("config '{file_path}' has problems:");
+
+                    for problem in problems {
+                        println!("  - {problem}");
+                    }
+
+                    exit(EXIT_CODE_ERROR);
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    eprintln!("details: {}", e.root_cause());
+                    exit(EXIT_CODE_ERROR);
+                }
+            }
+        }
+
+        if let Some(init_matches) = config_matches.subcommand_matches(CONFIG_INIT_COMMAND) {
+            let file_path = init_matches.value_of(FILE_ARG).unwrap_or(CONFIG_FILE_PATH);
 
-    match load_config_from_file(&config_file_path) {
+            if Path::new(file_path).exists() && !init_matches.is_present(FORCE_ARG) {
+                eprintln!("file '{file_path}' already exists, use --{FORCE_ARG} to overwrite it");
+                exit(EXIT_CODE_ERROR);
+            }
+
+            match get_config_template().and_then(|template| fs::write(file_path, template)
+                .context(format!("unable to write file '{file_path}'"))) {
+                Ok(_) => println!("config template has been written to '{file_path}', set instance urls and tokens"),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    eprintln!("details: {}", e.root_cause());
+                    exit(EXIT_CODE_ERROR);
+                }
+            }
+        }
+
+        return;
+    }
+
+    let config_file_path = Path::new(CONFIG_FILE_PATH);
+
+    match load_config_from_file(config_file_path) {
         Ok(app_config) => {
 
-            let logging_config = get_logging_config(&app_config.log_level);
+            let logging_config = get_logging_config(&app_config.log_level, app_config.log_format);
             match log4rs::init_config(logging_config) {
                 Ok(_) => {
 
-                    match matches.subcommand_matches(MIGRATE_COMMAND) {
-                        Some(_) => {
-                            println!("migrating..");
+                    if let Some(migrate_matches) = matches.subcommand_matches(MIGRATE_COMMAND) {
+                        if migrate_matches.is_present(DRY_RUN_ARG) {
+                            let client = ClientBuilder::new().build().unwrap();
+
+                            match get_scoped_projects(&client, &app_config) {
+                                Ok(scoped_projects) => {
+                                    println!("migration plan (dry-run):");
 
-                            match migrate_gitlab_instance(&app_config.source, &app_config.target,
-                                                          &app_config.migration,
-                                                          &app_config.error_handlers) {
-                                Ok(_) => {
-                                    println!("-----");
-                                    println!("migration completed");
+                                    for scoped_project in scoped_projects {
+                                        let fork_note = match (&scoped_project.placement,
+                                                               &scoped_project.source_project.forked_from_project) {
+                                            (TargetPlacement::New(new_project), Some(upstream))
+                                                if new_project.existing_project.is_none() =>
+                                                format!(", fork of '{}'", upstream.path_with_namespace),
+                                            _ => "".to_string()
+                                        };
+
+                                        let archived_note = match (scoped_project.source_project.archived,
+                                                                   app_config.migration.archived) {
+                                            (true, ArchivedProjectsPolicy::MigrateAndArchive) =>
+                                                ", archived, will be archived on target",
+                                            (true, _) => ", archived",
+                                            _ => ""
+                                        };
+
+                                        println!(" - '{}': {}{fork_note}{archived_note}",
+                                                 scoped_project.get_full_path(), scoped_project.placement);
+                                    }
                                 }
                                 Err(e) => {
                                     eprintln!("migration error: {}", e);
@@ -62,39 +274,294 @@ fn main() {
                                 }
                             }
 
+                            return;
                         }
-                        None => {}
+
+                        println!("migrating..");
+
+                        match migrate_gitlab_instance_with_progress(&app_config, &mut TerminalProgress::new()) {
+                            Ok(report) => {
+                                print_migration_report(&report);
+                                print_phase_reports(&report.phases);
+
+                                println!("-----");
+                                println!("migration completed");
+                            }
+                            Err(e) => {
+                                eprintln!("migration error: {}", e);
+                                eprintln!("details: {}", e.root_cause());
+                                exit(EXIT_CODE_ERROR);
+                            }
+                        }
+
                     }
 
-                    match matches.subcommand_matches(SHOW_EMPTY_COMMAND) {
-                        Some(_) => {
-                            println!("gathering data..");
+                    if let Some(show_empty_matches) = matches.subcommand_matches(SHOW_EMPTY_COMMAND) {
+                        let client = ClientBuilder::new().build().unwrap();
 
-                            let client = ClientBuilder::new().build().unwrap();
+                        let format = show_empty_matches.value_of(FORMAT_ARG)
+                            .and_then(OutputFormat::from_name).unwrap();
 
-                            match get_empty_projects(&client, &app_config.source) {
-                                Ok(empty_projects) => {
-                                    println!("empty projects at '{}':", app_config.source.public_url);
+                        let instance = if show_empty_matches.is_present(TARGET_ARG) {
+                            &app_config.target
+                        } else {
+                            &app_config.source
+                        };
 
-                                    if !empty_projects.is_empty() {
-                                        for empty_project in empty_projects {
-                                            println!(" - '{}'", empty_project.path);
-                                        }
+                        match get_empty_projects(&client, instance)
+                            .and_then(|empty_projects| format_empty_projects(&empty_projects, format)) {
+                            Ok(output) => println!("{output}"),
+                            Err(e) => {
+                                eprintln!("migration error: {}", e);
+                                eprintln!("details: {}", e.root_cause());
+                                exit(EXIT_CODE_ERROR);
+                            }
+                        }
+
+                    }
+
+                    if let Some(inventory_matches) = matches.subcommand_matches(INVENTORY_COMMAND) {
+                        let client = ClientBuilder::new().build().unwrap();
+
+                        let format = inventory_matches.value_of(FORMAT_ARG)
+                            .and_then(OutputFormat::from_name).unwrap();
+
+                        let huge_repository_size_mb = match inventory_matches.value_of(HUGE_REPO_SIZE_ARG)
+                            .unwrap_or_default().parse::<u64>() {
+                            Ok(size) => size,
+                            Err(_) => {
+                                eprintln!("--{HUGE_REPO_SIZE_ARG} should be a number of megabytes");
+                                exit(EXIT_CODE_ERROR);
+                            }
+                        };
+
+                        let options = InventoryOptions { huge_repository_size_mb };
+
+                        match get_inventory(&client, &app_config, &options)
+                            .and_then(|inventory| format_inventory(&inventory, format)) {
+                            Ok(output) => println!("{output}"),
+                            Err(e) => {
+                                eprintln!("inventory error: {}", e);
+                                eprintln!("details: {}", e.root_cause());
+                                exit(EXIT_CODE_ERROR);
+                            }
+                        }
+
+                    }
+
+                    if let Some(verify_matches) = matches.subcommand_matches(VERIFY_COMMAND) {
+                        println!("verifying..");
+
+                        let client = ClientBuilder::new().build().unwrap();
+
+                        let options = VerifyOptions {
+                            compare_visibility: verify_matches.is_present(VISIBILITY_ARG),
+                            compare_counts: verify_matches.is_present(COUNTS_ARG),
+                        };
+
+                        match verify_migration(&client, &app_config, &options) {
+                            Ok(results) => {
+                                for result in &results {
+                                    if result.is_verified() {
+                                        println!("[OK] {}", result.project_path);
 
                                     } else {
-                                        println!("no empty projects found");
+                                        println!("[MISMATCH] {}", result.project_path);
+
+                                        for mismatch in &result.mismatches {
+                                            println!("  - {mismatch}");
+                                        }
                                     }
+                                }
 
+                                let verified = results.iter().filter(|r| r.is_verified()).count();
+
+                                println!("-----");
+                                println!("verified projects: {verified}/{}", results.len());
+
+                                if verified != results.len() {
+                                    exit(EXIT_CODE_ERROR);
                                 }
-                                Err(e) => {
-                                    eprintln!("migration error: {}", e);
-                                    eprintln!("details: {}", e.root_cause());
+                            }
+                            Err(e) => {
+                                eprintln!("verification error: {}", e);
+                                eprintln!("details: {}", e.root_cause());
+                                exit(EXIT_CODE_ERROR);
+                            }
+                        }
+
+                    }
+
+                    if let Some(sync_matches) = matches.subcommand_matches(SYNC_COMMAND) {
+                        println!("syncing..");
+
+                        let client = ClientBuilder::new().build().unwrap();
+
+                        match sync_migrated_projects(&client, &app_config, sync_matches.is_present(FORCE_ARG)) {
+                            Ok(results) => {
+                                let mut has_problems = false;
+
+                                for result in &results {
+                                    match &result.status {
+                                        ProjectSyncStatus::NotMigrated => {
+                                            println!("[NOT MIGRATED] {}", result.project_path);
+                                        }
+                                        ProjectSyncStatus::Synced(sync_result) => {
+                                            if !sync_result.diverged_refs.is_empty() {
+                                                has_problems = true;
+                                                println!("[DIVERGED] {}", result.project_path);
+
+                                                for diverged_ref in &sync_result.diverged_refs {
+                                                    println!("  - {diverged_ref}");
+                                                }
+
+                                            } else if !sync_result.updated_refs.is_empty() {
+                                                println!("[UPDATED] {}", result.project_path);
+
+                                            } else {
+                                                println!("[UP-TO-DATE] {}", result.project_path);
+                                            }
+
+                                            for updated_ref in &sync_result.updated_refs {
+                                                println!("  + {updated_ref}");
+                                            }
+                                        }
+                                        ProjectSyncStatus::Failed(e) => {
+                                            has_problems = true;
+                                            println!("[ERROR] {}: {e}", result.project_path);
+                                        }
+                                    }
+                                }
+
+                                println!("-----");
+                                println!("sync completed");
+
+                                if has_problems {
+                                    exit(EXIT_CODE_ERROR);
+                                }
+                            }
+                            Err(e) => {
+                                eprintln!("sync error: {}", e);
+                                eprintln!("details: {}", e.root_cause());
+                                exit(EXIT_CODE_ERROR);
+                            }
+                        }
+
+                    }
+
+                    if let Some(mirror_matches) = matches.subcommand_matches(MIRROR_COMMAND) {
+                        let client = ClientBuilder::new().build().unwrap();
+
+                        let results = match mirror_matches.subcommand_name() {
+                            Some(MIRROR_CREATE_COMMAND) => create_push_mirrors(&client, &app_config),
+                            Some(MIRROR_REMOVE_COMMAND) => remove_push_mirrors(&client, &app_config),
+                            _ => get_push_mirrors_status(&client, &app_config)
+                        };
+
+                        match results {
+                            Ok(results) => {
+                                print_project_mirrors(&results);
+
+                                if results.iter().any(|r| matches!(r.status, MirrorStatus::Failed(_))) {
+                                    exit(EXIT_CODE_ERROR);
+                                }
+                            }
+                            Err(e) => {
+                                eprintln!("push mirror error: {}", e);
+                                eprintln!("details: {}", e.root_cause());
+                                exit(EXIT_CODE_ERROR);
+                            }
+                        }
+
+                    }
+
+                    if let Some(cutover_matches) = matches.subcommand_matches(CUTOVER_COMMAND) {
+                        let client = ClientBuilder::new().build().unwrap();
+
+                        let dry_run = !cutover_matches.is_present(MODIFY_SOURCE_ARG);
+
+                        if dry_run {
+                            println!("dry-run mode, use --{MODIFY_SOURCE_ARG} to apply changes to source instance");
+                        }
+
+                        let state_file_path = Path::new(CUTOVER_STATE_FILE_PATH);
+
+                        let results = if cutover_matches.is_present(UNDO_ARG) {
+                            undo_cutover(&client, &app_config, state_file_path, dry_run)
+
+                        } else {
+                            let mode = match cutover_matches.value_of(MODE_ARG) {
+                                Some("protect") => CutoverMode::Protect,
+                                _ => CutoverMode::Archive
+                            };
+
+                            let options = CutoverOptions {
+                                mode, banner: cutover_matches.is_present(BANNER_ARG), dry_run,
+                            };
+
+                            cutover_source_projects(&client, &app_config, &options, state_file_path)
+                        };
+
+                        match results {
+                            Ok(results) => {
+                                for result in &results {
+                                    match &result.status {
+                                        CutoverStatus::NotVerified(mismatches) => {
+                                            println!("[NOT VERIFIED] {}", result.project_path);
+
+                                            for mismatch in mismatches {
+                                                println!("  - {mismatch}");
+                                            }
+                                        }
+                                        CutoverStatus::Planned(actions) => {
+                                            println!("[PLANNED] {}: {}", result.project_path, actions.join(", "));
+                                        }
+                                        CutoverStatus::Done(actions) => {
+                                            println!("[DONE] {}: {}", result.project_path, actions.join(", "));
+                                        }
+                                        CutoverStatus::AlreadyDone => println!("[ALREADY DONE] {}", result.project_path),
+                                        CutoverStatus::Reverted(actions) => {
+                                            println!("[REVERTED] {}: {}", result.project_path, actions.join(", "));
+                                        }
+                                        CutoverStatus::Failed(e) => println!("[ERROR] {}: {e}", result.project_path),
+                                    }
+                                }
+
+                                if results.iter().any(|r| matches!(r.status, CutoverStatus::Failed(_))) {
                                     exit(EXIT_CODE_ERROR);
                                 }
                             }
+                            Err(e) => {
+                                eprintln!("cutover error: {}", e);
+                                eprintln!("details: {}", e.root_cause());
+                                exit(EXIT_CODE_ERROR);
+                            }
+                        }
+
+                    }
+
+                    if let Some(phase_matches) = matches.subcommand_matches(PHASE_COMMAND) {
+                        let client = ClientBuilder::new().build().unwrap();
+
+                        let phase = phase_matches.value_of(PHASE_NAME_ARG)
+                            .and_then(Phase::from_name).unwrap();
+
+                        match run_phase_on_migrated_projects(&client, &app_config, phase,
+                                                             phase_matches.is_present(DRY_RUN_ARG)) {
+                            Ok(reports) => {
+                                print_phase_reports(&reports);
 
+                                if reports.iter().any(|r| r.error.is_some()) {
+                                    exit(EXIT_CODE_ERROR);
+                                }
+                            }
+                            Err(e) => {
+                                eprintln!("phase error: {}", e);
+                                eprintln!("details: {}", e.root_cause());
+                                exit(EXIT_CODE_ERROR);
+                            }
                         }
-                        None => {}
+
                     }
 
                 }
@@ -107,7 +574,94 @@ fn main() {
         }
         Err(e) => {
             eprintln!("unable to load app config: {}", e);
+            eprintln!("check it with 'gmt config validate' or create a new one with 'gmt config init'");
             exit(EXIT_CODE_ERROR)
         }
     }
 }
+
+fn print_project_mirrors(results: &[ProjectMirror]) {
+    for result in results {
+        match &result.status {
+            MirrorStatus::NotMigrated => println!("[NOT MIGRATED] {}", result.project_path),
+            MirrorStatus::Created => println!("[CREATED] {}", result.project_path),
+            MirrorStatus::AlreadyExists => println!("[EXISTS] {}", result.project_path),
+            MirrorStatus::Active(mirror) => {
+                println!("[MIRROR] {} -> {}", result.project_path, mirror.url);
+                println!("  enabled: {}, status: {}, last update: {}, last successful update: {}",
+                         mirror.enabled,
+                         mirror.update_status.as_deref().unwrap_or("-"),
+                         mirror.last_update_at.as_deref().unwrap_or("-"),
+                         mirror.last_successful_update_at.as_deref().unwrap_or("-"));
+
+                if let Some(last_error) = &mirror.last_error {
+                    println!("  last error: {last_error}");
+                }
+            }
+            MirrorStatus::Removed => println!("[REMOVED] {}", result.project_path),
+            MirrorStatus::NotFound => println!("[NO MIRROR] {}", result.project_path),
+            MirrorStatus::Failed(e) => println!("[ERROR] {}: {e}", result.project_path),
+        }
+    }
+}
+
+fn print_migration_report(report: &MigrationReport) {
+    println!("migrated projects: {}", report.projects.len());
+
+    for project in &report.projects {
+        let archived_note = match (project.archived, project.target_archived) {
+            (true, true) => " (archived)",
+            (true, false) => " (archived on source)",
+            _ => ""
+        };
+
+        println!("  + '{}' -> '{}'{archived_note}", project.source_path, project.target_path);
+    }
+
+    for archived in &report.archived {
+        println!("  ~ archived {archived}");
+    }
+
+    for skipped in &report.skipped {
+        println!("  - skipped {skipped}");
+    }
+
+    for error in &report.errors {
+        println!("  ! {error}");
+    }
+}
+
+fn print_phase_reports(reports: &[PhaseReport]) {
+    for report in reports {
+        match &report.error {
+            Some(e) => println!("[ERROR] {} ({}): {e}", report.path, report.phase.get_name()),
+            None if report.dry_run => println!("[PLANNED] {} ({})", report.path, report.phase.get_name()),
+            None => println!("[DONE] {} ({})", report.path, report.phase.get_name())
+        }
+
+        for created in &report.created {
+            println!("  + {created}");
+        }
+
+        for updated in &report.updated {
+            println!("  ~ {updated}");
+        }
+
+        for skipped in &report.skipped {
+            println!("  - skipped {skipped}");
+        }
+    }
+
+    let manual_items: Vec<(&String, &String)> = reports.iter()
+        .flat_map(|r| r.manual.iter().map(move |item| (&r.path, item)))
+        .collect();
+
+    if !manual_items.is_empty() {
+        println!("-----");
+        println!("items to be set up manually on target instance: {}", manual_items.len());
+
+        for (path, item) in manual_items {
+            println!("  ! {path}: {item}");
+        }
+    }
+}
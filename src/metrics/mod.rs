@@ -0,0 +1,404 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use log::warn;
+
+use crate::config::MetricsConfig;
+use crate::git::clone::GitTransfer;
+use crate::progress::{MigrationProgress, ProjectOutcome};
+
+/// Upper bounds of histogram buckets in seconds
+const API_LATENCY_BUCKETS: [f64; 9] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+const PROJECT_DURATION_BUCKETS: [f64; 8] = [1.0, 5.0, 15.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+
+const PROJECT_OUTCOMES: [ProjectOutcome; 3] = [ProjectOutcome::Migrated, ProjectOutcome::Skipped,
+                                               ProjectOutcome::Failed];
+
+thread_local! {
+    /// Metrics of migration running on current thread, API calls are recorded wherever they are made.
+    /// Threads spawned by migration get them with `attach_api_metrics`.
+    static API_METRICS: RefCell<Option<Arc<Mutex<Metrics>>>> = const { RefCell::new(None) };
+}
+
+pub struct Metrics {
+    /// Outcome name -> count
+    projects: BTreeMap<&'static str, u64>,
+
+    project_duration: Histogram,
+
+    received_bytes: u64,
+    pushed_bytes: u64,
+
+    /// (instance, status code or `error`) -> count
+    api_requests: BTreeMap<(String, String), u64>,
+
+    /// Instance -> latency
+    api_latency: BTreeMap<String, Histogram>,
+}
+
+struct Histogram {
+    bounds: &'static [f64],
+
+    /// Observations per bucket, not cumulative
+    counts: Vec<u64>,
+
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    const fn new(bounds: &'static [f64]) -> Histogram {
+        Histogram { bounds, counts: Vec::new(), sum: 0.0, count: 0 }
+    }
+
+    fn observe(&mut self, value: f64) {
+        self.counts.resize(self.bounds.len(), 0);
+
+        if let Some(index) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[index] += 1;
+        }
+
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Lines of `name_bucket`, `name_sum` and `name_count`, e.g. `labels` is `instance="https://gitlab.company.com"`
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+
+        for (index, bound) in self.bounds.iter().enumerate() {
+            cumulative += self.counts.get(index).copied().unwrap_or_default();
+            let _ = writeln!(out, "{name}_bucket{} {cumulative}", get_labels(&[labels, &format!("le=\"{bound}\"")]));
+        }
+
+        let _ = writeln!(out, "{name}_bucket{} {}", get_labels(&[labels, "le=\"+Inf\""]), self.count);
+        let _ = writeln!(out, "{name}_sum{} {}", get_labels(&[labels]), self.sum);
+        let _ = writeln!(out, "{name}_count{} {}", get_labels(&[labels]), self.count);
+    }
+}
+
+impl Metrics {
+    pub const fn new() -> Metrics {
+        Metrics {
+            projects: BTreeMap::new(),
+            project_duration: Histogram::new(&PROJECT_DURATION_BUCKETS),
+            received_bytes: 0,
+            pushed_bytes: 0,
+            api_requests: BTreeMap::new(),
+            api_latency: BTreeMap::new(),
+        }
+    }
+
+    pub fn record_project(&mut self, outcome: ProjectOutcome, duration: Duration) {
+        *self.projects.entry(outcome.get_name()).or_default() += 1;
+        self.project_duration.observe(duration.as_secs_f64());
+    }
+
+    pub fn record_git_transfer(&mut self, transfer: GitTransfer, bytes: u64) {
+        match transfer {
+            GitTransfer::Received => self.received_bytes += bytes,
+            GitTransfer::Pushed => self.pushed_bytes += bytes,
+        }
+    }
+
+    /// `status` is `None` if response wasn't received
+    pub fn record_api_request(&mut self, instance: &str, status: Option<u16>, duration: Duration) {
+        let status = status.map(|s| s.to_string()).unwrap_or("error".to_string());
+
+        *self.api_requests.entry((instance.to_string(), status)).or_default() += 1;
+
+        self.api_latency.entry(instance.to_string())
+            .or_insert_with(|| Histogram::new(&API_LATENCY_BUCKETS))
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Prometheus text exposition format
+    pub fn get_text(&self) -> String {
+        let mut out = String::new();
+
+        write_header(&mut out, "gmt_projects_total", "counter", "Source projects processed by migration.");
+        for outcome in PROJECT_OUTCOMES {
+            let _ = writeln!(out, "gmt_projects_total{{outcome=\"{}\"}} {}", outcome.get_name(),
+                             self.projects.get(outcome.get_name()).copied().unwrap_or_default());
+        }
+
+        write_header(&mut out, "gmt_project_duration_seconds", "histogram",
+                     "Duration of project migration including repository copy and phases.");
+        self.project_duration.write(&mut out, "gmt_project_duration_seconds", "");
+
+        write_header(&mut out, "gmt_git_bytes_total", "counter", "Bytes transferred by git.");
+        let _ = writeln!(out, "gmt_git_bytes_total{{direction=\"received\"}} {}", self.received_bytes);
+        let _ = writeln!(out, "gmt_git_bytes_total{{direction=\"pushed\"}} {}", self.pushed_bytes);
+
+        write_header(&mut out, "gmt_api_requests_total", "counter", "GitLab API requests by response status.");
+        for ((instance, status), count) in &self.api_requests {
+            let _ = writeln!(out, "gmt_api_requests_total{{instance=\"{}\",status=\"{status}\"}} {count}",
+                             escape_label_value(instance));
+        }
+
+        write_header(&mut out, "gmt_api_request_duration_seconds", "histogram", "Latency of GitLab API requests.");
+        for (instance, latency) in &self.api_latency {
+            latency.write(&mut out, "gmt_api_request_duration_seconds",
+                          &format!("instance=\"{}\"", escape_label_value(instance)));
+        }
+
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// E.g. `{instance="https://gitlab.company.com",le="0.5"}`, empty if there are no labels
+fn get_labels(labels: &[&str]) -> String {
+    let labels: Vec<&str> = labels.iter().copied().filter(|l| !l.is_empty()).collect();
+
+    if labels.is_empty() {
+        "".to_string()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Record API request to metrics of migration running on current thread, if there is one.
+pub fn record_api_request(instance: &str, status: Option<u16>, duration: Duration) {
+    API_METRICS.with(|current| {
+        if let Some(metrics) = current.borrow().as_ref() {
+            metrics.lock().unwrap().record_api_request(instance, status, duration);
+        }
+    });
+}
+
+/// Metrics of migration running on current thread, pass them to `attach_api_metrics` in spawned threads.
+pub fn get_api_metrics() -> Option<Arc<Mutex<Metrics>>> {
+    API_METRICS.with(|current| current.borrow().clone())
+}
+
+/// Record API requests made on current thread to `metrics` until the guard is dropped.
+pub fn attach_api_metrics(metrics: Option<Arc<Mutex<Metrics>>>) -> ApiMetricsGuard {
+    let previous = API_METRICS.with(|current| current.replace(metrics));
+    ApiMetricsGuard { previous }
+}
+
+/// Restores metrics which were attached to the thread before.
+pub struct ApiMetricsGuard {
+    previous: Option<Arc<Mutex<Metrics>>>,
+}
+
+impl Drop for ApiMetricsGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        API_METRICS.with(|current| current.replace(previous));
+    }
+}
+
+/// Write metrics to `metrics.textfile` if it's set. File is replaced at once, so collector never reads
+/// partially written file.
+pub fn export_metrics(config: &MetricsConfig, metrics: &Metrics) -> anyhow::Result<()> {
+    if config.textfile.is_empty() {
+        return Ok(())
+    }
+
+    let text = metrics.get_text();
+
+    let tmp_file_path = format!("{}.tmp", config.textfile);
+
+    fs::write(&tmp_file_path, text).context(format!("unable to write metrics to '{tmp_file_path}'"))?;
+    fs::rename(&tmp_file_path, &config.textfile)
+        .context(format!("unable to replace metrics file '{}'", config.textfile))
+}
+
+/// Records migration progress to metrics and exports them after every project, events are passed on to `progress`.
+///
+/// Every migration has its own metrics, API requests made on the same thread and threads with attached
+/// metrics are recorded to them while recorder exists.
+pub struct MetricsRecorder<'a> {
+    progress: &'a mut dyn MigrationProgress,
+    config: &'a MetricsConfig,
+    metrics: Arc<Mutex<Metrics>>,
+    project_started_at: Instant,
+    _api_metrics: ApiMetricsGuard,
+}
+
+impl<'a> MetricsRecorder<'a> {
+    pub fn new(progress: &'a mut dyn MigrationProgress, config: &'a MetricsConfig) -> MetricsRecorder<'a> {
+        let metrics = Arc::new(Mutex::new(Metrics::new()));
+        let api_metrics = attach_api_metrics(Some(metrics.clone()));
+
+        MetricsRecorder { progress, config, metrics, project_started_at: Instant::now(), _api_metrics: api_metrics }
+    }
+
+    fn export(&self) {
+        if let Err(e) = export_metrics(self.config, &self.metrics.lock().unwrap()) {
+            warn!("metrics export error: {}", e);
+        }
+    }
+}
+
+impl MigrationProgress for MetricsRecorder<'_> {
+    fn start(&mut self, total: usize) {
+        self.progress.start(total);
+        self.export();
+    }
+
+    fn project_started(&mut self, project_path: &str) {
+        self.project_started_at = Instant::now();
+        self.progress.project_started(project_path);
+    }
+
+    fn bytes_transferred(&mut self, transfer: GitTransfer, bytes: u64) {
+        self.metrics.lock().unwrap().record_git_transfer(transfer, bytes);
+        self.progress.bytes_transferred(transfer, bytes);
+    }
+
+    fn project_finished(&mut self, project_path: &str, outcome: ProjectOutcome) {
+        self.metrics.lock().unwrap().record_project(outcome, self.project_started_at.elapsed());
+        self.progress.project_finished(project_path, outcome);
+        self.export();
+    }
+
+    fn finish(&mut self) {
+        self.progress.finish();
+        self.export();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::Value;
+    use tempfile::TempDir;
+
+    use crate::diag::empty::get_empty_projects;
+    use crate::git::clone::GitTransfer;
+    use crate::metrics::{Metrics, MetricsRecorder};
+    use crate::migration::migrate_gitlab_instance;
+    use crate::progress::{MigrationProgress, NoProgress, ProjectOutcome};
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    #[test]
+    fn metrics_should_be_formatted_for_prometheus() {
+        let mut metrics = Metrics::new();
+        metrics.record_project(ProjectOutcome::Migrated, Duration::from_secs(3));
+        metrics.record_git_transfer(GitTransfer::Pushed, 2048);
+        metrics.record_api_request("https://gitlab.company.com", Some(200), Duration::from_millis(70));
+        metrics.record_api_request("https://gitlab.company.com", None, Duration::from_secs(60));
+
+        let text = metrics.get_text();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines.contains(&"gmt_projects_total{outcome=\"migrated\"} 1"), "{text}");
+        assert!(lines.contains(&"gmt_projects_total{outcome=\"failed\"} 0"), "{text}");
+        assert!(lines.contains(&"gmt_project_duration_seconds_bucket{le=\"5\"} 1"), "{text}");
+        assert!(lines.contains(&"gmt_git_bytes_total{direction=\"pushed\"} 2048"), "{text}");
+        assert!(lines.contains(&"gmt_api_requests_total{instance=\"https://gitlab.company.com\",status=\"error\"} 1"),
+                "{text}");
+        assert!(lines.contains(
+            &"gmt_api_request_duration_seconds_bucket{instance=\"https://gitlab.company.com\",le=\"0.1\"} 1"), "{text}");
+        assert!(lines.contains(
+            &"gmt_api_request_duration_seconds_bucket{instance=\"https://gitlab.company.com\",le=\"+Inf\"} 2"), "{text}");
+        assert!(lines.contains(&"gmt_api_request_duration_seconds_count{instance=\"https://gitlab.company.com\"} 2"),
+                "{text}");
+    }
+
+    #[test]
+    fn metrics_textfile_should_be_written_by_migration() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        source.add_project(group_id, "api", &["main"]);
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.metrics.textfile = format!("{}", tmp_dir.path().join("gmt.prom").display());
+
+        migrate_gitlab_instance(&config).unwrap();
+
+        let text = fs::read_to_string(&config.metrics.textfile).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines.contains(&"gmt_projects_total{outcome=\"migrated\"} 1"), "{text}");
+        assert!(lines.contains(&"gmt_projects_total{outcome=\"skipped\"} 0"), "{text}");
+        assert!(lines.contains(&"gmt_project_duration_seconds_count 1"), "{text}");
+        assert!(get_value(&lines, "gmt_git_bytes_total{direction=\"pushed\"}") > 0, "{text}");
+
+        let target_requests = get_value(
+            &lines, &format!("gmt_api_requests_total{{instance=\"{}\",status=\"201\"}}", config.target.public_url));
+        assert!(target_requests >= 2, "group and project have to be created: {text}");
+
+        // next migration starts with its own metrics
+        migrate_gitlab_instance(&config).unwrap();
+
+        let text = fs::read_to_string(&config.metrics.textfile).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines.contains(&"gmt_projects_total{outcome=\"migrated\"} 0"), "{text}");
+        assert!(lines.contains(&"gmt_projects_total{outcome=\"skipped\"} 1"), "{text}");
+        assert!(lines.contains(&"gmt_git_bytes_total{direction=\"pushed\"} 0"), "{text}");
+        assert!(!text.contains("status=\"201\""), "{text}");
+    }
+
+    #[test]
+    fn api_requests_of_spawned_threads_should_be_recorded() {
+        init_logging();
+
+        let gitlab = FakeGitLab::start();
+        let group_id = gitlab.add_group("Backend", None);
+
+        // branches are requested from parallel threads without `empty_repo` attribute
+        for name in ["api", "web", "docs"] {
+            let project_id = gitlab.add_project(group_id, name, &["main"]);
+            gitlab.set_project_attribute(project_id, "empty_repo", Value::Null);
+        }
+
+        let tmp_dir = TempDir::new().unwrap();
+        let mut config = get_test_config(gitlab.instance(), gitlab.instance(), tmp_dir.path());
+        config.metrics.textfile = format!("{}", tmp_dir.path().join("gmt.prom").display());
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let mut progress = NoProgress;
+        let mut recorder = MetricsRecorder::new(&mut progress, &config.metrics);
+        get_empty_projects(&client, &config.source).unwrap();
+        recorder.finish();
+
+        let text = fs::read_to_string(&config.metrics.textfile).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        let requests = get_value(
+            &lines, &format!("gmt_api_requests_total{{instance=\"{}\",status=\"200\"}}", config.source.public_url));
+        assert_eq!(gitlab.requests().len() as u64, requests, "{text}");
+    }
+
+    /// Value of metric line, `series` is metric name with labels
+    fn get_value(lines: &[&str], series: &str) -> u64 {
+        lines.iter()
+            .find_map(|line| line.strip_prefix(series).map(|value| value.trim().parse().unwrap()))
+            .unwrap_or_default()
+    }
+}
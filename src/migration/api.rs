@@ -0,0 +1,322 @@
+use std::fmt::Debug;
+use std::time::Instant;
+
+use anyhow::{anyhow, Context};
+use log::{debug, error};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::blocking::multipart::{Form, Part};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::config::InstanceConfig;
+use crate::logging::{get_error_class, log_field, LOG_FIELD_DURATION_MS, LOG_FIELD_ERROR_CLASS, LOG_FIELD_INSTANCE};
+use crate::metrics::record_api_request;
+use crate::migration::PRIVATE_TOKEN_HEADER;
+
+const PER_PAGE: u32 = 100;
+
+const TOTAL_HEADER: &str = "X-Total";
+
+/// GET request to `/api/v4/{path}`, `path` may contain query string.
+pub fn get_json<T: DeserializeOwned + Debug>(client: &Client, instance: &InstanceConfig,
+                                             path: &str) -> anyhow::Result<T> {
+    match get_optional_json(client, instance, path)? {
+        Some(result) => Ok(result),
+        None => {
+            error!("unexpected server response code {}", reqwest::StatusCode::NOT_FOUND);
+            Err(anyhow!("unexpected server response"))
+        }
+    }
+}
+
+/// GET request to `/api/v4/{path}`, `None` if resource wasn't found or isn't supported by instance.
+pub fn get_optional_json<T: DeserializeOwned + Debug>(client: &Client, instance: &InstanceConfig,
+                                                      path: &str) -> anyhow::Result<Option<T>> {
+    get_json_unless(client, instance, path, &[reqwest::StatusCode::NOT_FOUND])
+}
+
+/// GET request to `/api/v4/{path}`, `None` if response has one of `unavailable_statuses`.
+fn get_json_unless<T: DeserializeOwned + Debug>(client: &Client, instance: &InstanceConfig, path: &str,
+                                                unavailable_statuses: &[reqwest::StatusCode])
+                                                -> anyhow::Result<Option<T>> {
+    let url = format!("{}/api/v4/{path}", instance.public_url);
+
+    debug!("url: {url}");
+
+    let response = send_request(client.get(url), instance)?;
+
+    let response_status = response.status();
+
+    if unavailable_statuses.contains(&response_status) {
+        Ok(None)
+
+    } else if response_status == reqwest::StatusCode::OK {
+        let result: T = decode_json_response(response)?;
+
+        Ok(Some(result))
+
+    } else {
+        error!("unexpected server response code {}", response_status);
+        Err(anyhow!("unexpected server response"))
+    }
+}
+
+/// Get all items from paginated list at `/api/v4/{path}`.
+pub fn get_all_pages<T: DeserializeOwned + Debug>(client: &Client, instance: &InstanceConfig,
+                                                  path: &str) -> anyhow::Result<Vec<T>> {
+    match get_optional_all_pages(client, instance, path)? {
+        Some(results) => Ok(results),
+        None => {
+            error!("unexpected server response code {}", reqwest::StatusCode::NOT_FOUND);
+            Err(anyhow!("unexpected server response"))
+        }
+    }
+}
+
+/// Get all items from paginated list at `/api/v4/{path}`, `None` if list isn't supported by instance,
+/// e.g. Premium tier features.
+pub fn get_optional_all_pages<T: DeserializeOwned + Debug>(client: &Client, instance: &InstanceConfig,
+                                                           path: &str) -> anyhow::Result<Option<Vec<T>>> {
+    get_all_pages_unless(client, instance, path, &[reqwest::StatusCode::NOT_FOUND])
+}
+
+/// Get all items of project feature list like `get_optional_all_pages`, `None` also if feature is disabled
+/// for the project (`403 Forbidden`), e.g. packages or container registry.
+pub fn get_feature_all_pages<T: DeserializeOwned + Debug>(client: &Client, instance: &InstanceConfig,
+                                                          path: &str) -> anyhow::Result<Option<Vec<T>>> {
+    get_all_pages_unless(client, instance, path,
+                         &[reqwest::StatusCode::NOT_FOUND, reqwest::StatusCode::FORBIDDEN])
+}
+
+/// Get all items, `None` if the first page has one of `unavailable_statuses`.
+fn get_all_pages_unless<T: DeserializeOwned + Debug>(client: &Client, instance: &InstanceConfig, path: &str,
+                                                     unavailable_statuses: &[reqwest::StatusCode])
+                                                     -> anyhow::Result<Option<Vec<T>>> {
+    let mut page = 1;
+
+    let mut results: Vec<T> = vec![];
+
+    let mut items: Vec<T> = match get_json_unless(client, instance, &get_page_path(path, page, PER_PAGE),
+                                                  unavailable_statuses)? {
+        Some(items) => items,
+        None => return Ok(None)
+    };
+
+    while !items.is_empty() {
+        let last_page = items.len() < PER_PAGE as usize;
+
+        results.append(&mut items);
+
+        if last_page {
+            break;
+        }
+
+        page += 1;
+        items = get_json(client, instance, &get_page_path(path, page, PER_PAGE))?;
+    }
+
+    Ok(Some(results))
+}
+
+/// Get total item count of paginated list at `/api/v4/{path}`.
+///
+/// GitLab omits `X-Total` header for large collections, items are counted page by page then.
+pub fn get_total_count(client: &Client, instance: &InstanceConfig, path: &str) -> anyhow::Result<usize> {
+    match get_optional_total_count(client, instance, path)? {
+        Some(total) => Ok(total),
+        None => {
+            error!("list '{path}' isn't available");
+            Err(anyhow!("unexpected server response"))
+        }
+    }
+}
+
+/// Get total item count like `get_total_count`, `None` if list isn't available: feature is disabled
+/// for the project (`403 Forbidden`) or isn't supported by instance (`404 Not Found`).
+pub fn get_optional_total_count(client: &Client, instance: &InstanceConfig,
+                                path: &str) -> anyhow::Result<Option<usize>> {
+    let url = format!("{}/api/v4/{}", instance.public_url, get_page_path(path, 1, 1));
+
+    debug!("url: {url}");
+
+    let response = send_request(client.head(url), instance)?;
+
+    let response_status = response.status();
+
+    if response_status == reqwest::StatusCode::NOT_FOUND || response_status == reqwest::StatusCode::FORBIDDEN {
+        Ok(None)
+
+    } else if response_status == reqwest::StatusCode::OK {
+        let total = response.headers().get(TOTAL_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok());
+
+        match total {
+            Some(total) => Ok(Some(total)),
+            None => {
+                let items: Vec<serde_json::Value> = get_all_pages(client, instance, path)?;
+                Ok(Some(items.len()))
+            }
+        }
+
+    } else {
+        error!("unexpected server response code {}", response_status);
+        Err(anyhow!("unexpected server response"))
+    }
+}
+
+/// POST request with JSON body to `/api/v4/{path}`, `201 Created` is expected.
+pub fn post_json<T: DeserializeOwned + Debug>(client: &Client, instance: &InstanceConfig,
+                                              path: &str, body: &Value) -> anyhow::Result<T> {
+    send_json(client.post(format!("{}/api/v4/{path}", instance.public_url)), instance, body,
+              reqwest::StatusCode::CREATED)
+}
+
+/// PUT request with JSON body to `/api/v4/{path}`.
+pub fn put_json<T: DeserializeOwned + Debug>(client: &Client, instance: &InstanceConfig,
+                                             path: &str, body: &Value) -> anyhow::Result<T> {
+    send_json(client.put(format!("{}/api/v4/{path}", instance.public_url)), instance, body,
+              reqwest::StatusCode::OK)
+}
+
+fn send_json<T: DeserializeOwned + Debug>(request: RequestBuilder, instance: &InstanceConfig, body: &Value,
+                                          expected_status: reqwest::StatusCode) -> anyhow::Result<T> {
+    let response = send_request(request.json(body), instance)?;
+
+    debug!("url: {}", response.url());
+
+    let response_status = response.status();
+
+    if response_status == expected_status {
+        let result: T = decode_json_response(response)?;
+
+        Ok(result)
+
+    } else {
+        error!("unexpected server response code {}", response_status);
+        error!("{}", response.text().unwrap_or_default());
+        Err(anyhow!("unexpected server response"))
+    }
+}
+
+/// POST file as multipart form to `/api/v4/{path}`, `201 Created` is expected.
+pub fn post_file<T: DeserializeOwned + Debug>(client: &Client, instance: &InstanceConfig, path: &str,
+                                              file_name: &str, content: Vec<u8>) -> anyhow::Result<T> {
+    let form = Form::new().part("file", Part::bytes(content).file_name(file_name.to_string()));
+
+    let response = send_request(client.post(format!("{}/api/v4/{path}", instance.public_url)).multipart(form),
+                                instance)?;
+
+    debug!("url: {}", response.url());
+
+    let response_status = response.status();
+
+    if response_status == reqwest::StatusCode::CREATED {
+        let result: T = decode_json_response(response)?;
+
+        Ok(result)
+
+    } else {
+        error!("unexpected server response code {}", response_status);
+        error!("{}", response.text().unwrap_or_default());
+        Err(anyhow!("unexpected server response"))
+    }
+}
+
+/// Download file from instance `url` with instance token, `None` if file wasn't found.
+pub fn get_file(client: &Client, instance: &InstanceConfig, url: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    debug!("url: {url}");
+
+    let response = send_request(client.get(url), instance)?;
+
+    let response_status = response.status();
+
+    if response_status == reqwest::StatusCode::NOT_FOUND {
+        Ok(None)
+
+    } else if response_status == reqwest::StatusCode::OK {
+        let content = response.bytes().context("unable to read server response")?;
+        debug!("downloaded {} bytes", content.len());
+        Ok(Some(content.to_vec()))
+
+    } else {
+        error!("unexpected server response code {}", response_status);
+        Err(anyhow!("unexpected server response"))
+    }
+}
+
+/// DELETE request to `/api/v4/{path}`.
+pub fn delete(client: &Client, instance: &InstanceConfig, path: &str) -> anyhow::Result<()> {
+    let url = format!("{}/api/v4/{path}", instance.public_url);
+
+    debug!("url: {url}");
+
+    let response = send_request(client.delete(url), instance)?;
+
+    let response_status = response.status();
+
+    if response_status == reqwest::StatusCode::NO_CONTENT || response_status == reqwest::StatusCode::ACCEPTED ||
+        response_status == reqwest::StatusCode::OK {
+        Ok(())
+
+    } else {
+        error!("unexpected server response code {}", response_status);
+        Err(anyhow!("unexpected server response"))
+    }
+}
+
+/// Decode JSON body of successful response. Body is logged as received, so log records contain JSON
+/// instead of debug representation of decoded structures.
+pub fn decode_json_response<T: DeserializeOwned>(response: Response) -> anyhow::Result<T> {
+    let body = response.text().context("unable to read server response")?;
+
+    debug!("http response: {body}");
+
+    serde_json::from_str(&body).context("unable to decode server response")
+}
+
+/// Send request to instance API with `PRIVATE-TOKEN` header.
+pub fn send_request(request: RequestBuilder, instance: &InstanceConfig) -> anyhow::Result<Response> {
+    send_authorized_request(request.header(PRIVATE_TOKEN_HEADER, instance.token.to_string()), instance)
+}
+
+/// Send request which already carries credentials, response status is logged with instance and duration.
+pub fn send_authorized_request(request: RequestBuilder, instance: &InstanceConfig) -> anyhow::Result<Response> {
+    let _instance = log_field(LOG_FIELD_INSTANCE, &instance.public_url);
+
+    let started_at = Instant::now();
+    let result = request.send();
+    let duration = started_at.elapsed();
+
+    record_api_request(&instance.public_url, result.as_ref().ok().map(|r| r.status().as_u16()), duration);
+
+    let _duration = log_field(LOG_FIELD_DURATION_MS, &duration.as_millis().to_string());
+
+    match result {
+        Ok(response) => {
+            debug!("response {} from '{}'", response.status(), response.url());
+            Ok(response)
+        }
+        Err(e) => {
+            let e = anyhow::Error::new(e).context("gitlab api communication error");
+
+            let _error_class = log_field(LOG_FIELD_ERROR_CLASS, get_error_class(&e));
+            error!("{}: {}", e, e.root_cause());
+
+            Err(e)
+        }
+    }
+}
+
+fn get_page_path(path: &str, page: u32, per_page: u32) -> String {
+    let separator = if path.contains('?') { "&" } else { "?" };
+    format!("{path}{separator}per_page={per_page}&page={page}")
+}
+
+/// Encode value for usage as single path segment, e.g. `release/*` -> `release%2F*`.
+pub fn encode_path_segment(value: &str) -> String {
+    let mut url = Url::parse("http://localhost").unwrap();
+    url.path_segments_mut().unwrap().push(value);
+    url.path().trim_start_matches('/').to_string()
+}
@@ -0,0 +1,353 @@
+use std::fmt::{Display, Formatter};
+
+use anyhow::Context;
+use log::info;
+use reqwest::blocking::Client;
+
+use crate::config::{AppConfig, CollisionPolicy, InstanceConfig};
+use crate::migration::api::{encode_path_segment, get_optional_json};
+use crate::migration::domain::{GitLabCommit, GitLabProject};
+use crate::migration::namespace::ProjectNamespaces;
+
+/// Commits of default branches compared to match diverged repositories
+const RECENT_COMMITS: u32 = 20;
+
+/// Place of source project on target instance
+#[derive(Debug, Clone)]
+pub enum TargetPlacement {
+    /// Copy of source project exists on target instance
+    Migrated(Box<GitLabProject>),
+
+    /// Project has to be created
+    New(NewProject),
+
+    /// Source project is left out because of collision
+    Skipped(String),
+
+    /// Migration has to stop because of collision
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct NewProject {
+    pub namespace_path: String,
+    pub name: String,
+    pub path: String,
+
+    /// Project is created in user namespace by `namespace_path` username
+    pub user_namespace: bool,
+
+    /// Collision which moved project from its source group path
+    pub collision: Option<String>,
+
+    /// Project with empty repository which already takes the place, e.g. created manually
+    /// or left by interrupted migration. Repository is pushed into it instead of creating project.
+    pub existing_project: Option<Box<GitLabProject>>,
+}
+
+impl NewProject {
+    pub fn get_full_path(&self) -> String {
+        format!("{}/{}", self.namespace_path, self.path)
+    }
+}
+
+impl Display for TargetPlacement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TargetPlacement::Migrated(project) =>
+                write!(f, "already migrated to '{}/{}'", project.namespace.full_path, project.path),
+            TargetPlacement::New(new_project) => {
+                let action = match new_project.existing_project {
+                    Some(_) => "push into empty project",
+                    None => "create"
+                };
+
+                match &new_project.collision {
+                    Some(collision) => write!(f, "{action} '{}' ({collision})", new_project.get_full_path()),
+                    None => write!(f, "{action} '{}'", new_project.get_full_path())
+                }
+            }
+            TargetPlacement::Skipped(collision) => write!(f, "skip, {collision}"),
+            TargetPlacement::Failed(collision) => write!(f, "fail, {collision}"),
+        }
+    }
+}
+
+enum PlacementCheck<'a> {
+    Free,
+    Empty(&'a GitLabProject),
+    Migrated(&'a GitLabProject),
+    Collision(String),
+}
+
+enum RepositoryRelation {
+    Related,
+    Unrelated,
+    /// Target repository is empty while source one has commits
+    EmptyTarget,
+}
+
+/// Find migrated copy of source project or place for a new one.
+///
+/// Target project with the same group, name and path is a copy if repositories are related,
+/// any other project taking the name or path is a collision resolved by `migration.collisions.policy`.
+pub fn get_target_placement(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                            namespaces: &ProjectNamespaces,
+                            target_projects: &[GitLabProject]) -> anyhow::Result<TargetPlacement> {
+    let source_group = &namespaces.source_group;
+
+    let collision = match check_placement(client, config, source_project, &namespaces.target_path,
+                                          &source_project.name, &source_project.path, target_projects)? {
+        PlacementCheck::Free => return Ok(TargetPlacement::New(NewProject {
+            namespace_path: namespaces.target_path.to_string(),
+            name: source_project.name.to_string(),
+            path: source_project.path.to_string(),
+            user_namespace: namespaces.user_namespace,
+            collision: None,
+            existing_project: None,
+        })),
+        PlacementCheck::Empty(target_project) => return Ok(TargetPlacement::New(NewProject {
+            namespace_path: namespaces.target_path.to_string(),
+            name: source_project.name.to_string(),
+            path: source_project.path.to_string(),
+            user_namespace: namespaces.user_namespace,
+            collision: None,
+            existing_project: Some(Box::new(target_project.clone())),
+        })),
+        PlacementCheck::Migrated(target_project) => return Ok(TargetPlacement::Migrated(Box::new(target_project.clone()))),
+        PlacementCheck::Collision(collision) => collision
+    };
+
+    info!("project '{}/{}' collides on target instance: {collision}", source_group.full_path, source_project.path);
+
+    let collisions = &config.migration.collisions;
+
+    let (namespace_path, name, path, user_namespace) = match collisions.policy {
+        CollisionPolicy::Skip => return Ok(TargetPlacement::Skipped(collision)),
+        CollisionPolicy::Fail => return Ok(TargetPlacement::Failed(collision)),
+        CollisionPolicy::Rename => (
+            namespaces.target_path.to_string(),
+            format!("{}{}", source_project.name, collisions.rename_suffix),
+            format!("{}{}", source_project.path, collisions.rename_suffix),
+            namespaces.user_namespace,
+        ),
+        CollisionPolicy::Quarantine => {
+            let path = format!("{}-{}", source_group.full_path.replace('/', "-"), source_project.path);
+            (collisions.quarantine_group.to_string(), path.to_string(), path, false)
+        }
+    };
+
+    match check_placement(client, config, source_project, &namespace_path, &name, &path, target_projects)? {
+        PlacementCheck::Free => Ok(TargetPlacement::New(NewProject {
+            namespace_path, name, path, user_namespace, collision: Some(collision), existing_project: None,
+        })),
+        PlacementCheck::Empty(target_project) => Ok(TargetPlacement::New(NewProject {
+            namespace_path, name, path, user_namespace, collision: Some(collision),
+            existing_project: Some(Box::new(target_project.clone())),
+        })),
+        PlacementCheck::Migrated(target_project) => Ok(TargetPlacement::Migrated(Box::new(target_project.clone()))),
+        PlacementCheck::Collision(alternative_collision) =>
+            Ok(TargetPlacement::Skipped(format!("{collision}, {alternative_collision}")))
+    }
+}
+
+fn check_placement<'a>(client: &Client, config: &AppConfig, source_project: &GitLabProject, namespace_path: &str,
+                       name: &str, path: &str,
+                       target_projects: &'a [GitLabProject]) -> anyhow::Result<PlacementCheck<'a>> {
+    let target_project = match target_projects.iter()
+        .find(|tp| tp.namespace.full_path == namespace_path && (tp.path == path || tp.name == name)) {
+        Some(target_project) => target_project,
+        None => return Ok(PlacementCheck::Free)
+    };
+
+    let target_path = format!("{}/{}", target_project.namespace.full_path, target_project.path);
+
+    if target_project.path != path {
+        return Ok(PlacementCheck::Collision(format!("name '{name}' is taken by project '{target_path}'")))
+    }
+
+    if target_project.name != name {
+        return Ok(PlacementCheck::Collision(
+            format!("path '{target_path}' is taken by project '{}'", target_project.name)))
+    }
+
+    match compare_repositories(client, config, source_project, target_project)
+        .context(format!("unable to compare repositories of project '{target_path}'"))? {
+        RepositoryRelation::Related => Ok(PlacementCheck::Migrated(target_project)),
+        RepositoryRelation::EmptyTarget => Ok(PlacementCheck::Empty(target_project)),
+        RepositoryRelation::Unrelated =>
+            Ok(PlacementCheck::Collision(format!("project '{target_path}' has unrelated repository")))
+    }
+}
+
+/// Repositories are related if head commit of one default branch is known to another repository,
+/// so copies stay related after sync or new commits on target. Branches diverged after migration
+/// share recent commits. Empty source repository is related to any, empty target repository
+/// is a place to push the source one.
+fn compare_repositories(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                        target_project: &GitLabProject) -> anyhow::Result<RepositoryRelation> {
+    let target_commits = get_recent_commits(client, &config.target, target_project)?;
+    let source_commits = get_recent_commits(client, &config.source, source_project)?;
+
+    match (target_commits.first(), source_commits.first()) {
+        (Some(target_head), Some(source_head)) => {
+            if target_commits.iter().any(|c| source_commits.contains(c)) ||
+                commit_exists(client, &config.source, source_project, target_head)? ||
+                commit_exists(client, &config.target, target_project, source_head)? {
+                Ok(RepositoryRelation::Related)
+            } else {
+                Ok(RepositoryRelation::Unrelated)
+            }
+        }
+        (None, Some(_)) => Ok(RepositoryRelation::EmptyTarget),
+        (_, None) => Ok(RepositoryRelation::Related)
+    }
+}
+
+/// Latest commits of default branch, head commit goes first.
+fn get_recent_commits(client: &Client, instance: &InstanceConfig,
+                      project: &GitLabProject) -> anyhow::Result<Vec<String>> {
+    match &project.default_branch {
+        Some(default_branch) => {
+            let commits: Option<Vec<GitLabCommit>> = get_optional_json(
+                client, instance, &format!("projects/{}/repository/commits?ref_name={}&per_page={RECENT_COMMITS}",
+                                           project.id, encode_path_segment(default_branch)))?;
+
+            Ok(commits.unwrap_or_default().into_iter().map(|c| c.id).collect())
+        }
+        None => Ok(vec![])
+    }
+}
+
+fn commit_exists(client: &Client, instance: &InstanceConfig, project: &GitLabProject,
+                 sha: &str) -> anyhow::Result<bool> {
+    let commit: Option<GitLabCommit> = get_optional_json(
+        client, instance, &format!("projects/{}/repository/commits/{sha}", project.id))?;
+
+    Ok(commit.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::config::CollisionPolicy;
+    use crate::migration::collision::TargetPlacement;
+    use crate::migration::migrate_gitlab_instance;
+    use crate::migration::scope::get_scoped_projects;
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    fn get_instances() -> (FakeGitLab, FakeGitLab) {
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        source.add_project(group_id, "api", &["main"]);
+        source.add_project(group_id, "web", &["main"]);
+
+        // unrelated project with the same name and path, project which takes the path under another name
+        let target = FakeGitLab::start();
+        let target_group_id = target.add_group("Backend", None);
+        target.add_project(target_group_id, "api", &["master"]);
+        let web_id = target.add_project(target_group_id, "web", &[]);
+        target.set_project_attribute(web_id, "name", json!("Legacy Web"));
+
+        (source, target)
+    }
+
+    #[test]
+    fn collisions_should_be_skipped_and_shown_in_plan() {
+        init_logging();
+
+        let (source, target) = get_instances();
+        let tmp_dir = TempDir::new().unwrap();
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let plan: Vec<String> = get_scoped_projects(&client, &config).unwrap().iter()
+            .map(|p| format!("{}: {}", p.get_full_path(), p.placement)).collect();
+
+        assert_eq!(vec![
+            "backend/api: skip, project 'backend/api' has unrelated repository",
+            "backend/web: skip, path 'backend/web' is taken by project 'Legacy Web'",
+        ], plan);
+
+        migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(vec!["master"], target.branches("backend/api"));
+        assert_eq!(2, target.project_paths().len());
+
+        let mut config = config;
+        config.migration.collisions.policy = CollisionPolicy::Fail;
+
+        assert!(migrate_gitlab_instance(&config).is_err());
+    }
+
+    #[test]
+    fn collided_projects_should_be_renamed_or_quarantined() {
+        init_logging();
+
+        let (source, target) = get_instances();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.migration.collisions.policy = CollisionPolicy::Rename;
+
+        migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(vec!["main"], target.branches("backend/api-migrated"));
+        assert_eq!(vec!["main"], target.branches("backend/web-migrated"));
+
+        // renamed copies are found on the next run
+        let client = ClientBuilder::new().build().unwrap();
+        let scoped_projects = get_scoped_projects(&client, &config).unwrap();
+
+        assert!(matches!(&scoped_projects[0].placement, TargetPlacement::Migrated(p) if p.path == "api-migrated"));
+        assert_eq!("api-migrated", scoped_projects[0].target_project.as_ref().unwrap().path);
+
+        let (source, target) = get_instances();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.migration.collisions.policy = CollisionPolicy::Quarantine;
+
+        migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(vec!["main"], target.branches("gmt-quarantine/backend-api"));
+        assert_eq!(vec!["main"], target.branches("gmt-quarantine/backend-web"));
+    }
+
+    #[test]
+    fn empty_target_project_should_receive_source_repository() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        source.add_project(group_id, "api", &["main", "develop"]);
+
+        // created manually before migration
+        let target = FakeGitLab::start();
+        let target_group_id = target.add_group("Backend", None);
+        target.add_project(target_group_id, "api", &[]);
+
+        let tmp_dir = TempDir::new().unwrap();
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let scoped_projects = get_scoped_projects(&client, &config).unwrap();
+        assert_eq!("push into empty project 'backend/api'", scoped_projects[0].placement.to_string());
+        assert!(scoped_projects[0].target_project.is_none());
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(1, report.projects.len());
+        assert_eq!(vec!["backend/api"], target.project_paths());
+        assert_eq!(vec!["develop", "main"], target.branches("backend/api"));
+
+        let scoped_projects = get_scoped_projects(&client, &config).unwrap();
+        assert!(matches!(&scoped_projects[0].placement, TargetPlacement::Migrated(_)));
+    }
+}
@@ -18,6 +18,55 @@ pub struct GitLabProject {
     pub visibility: String,
 
     pub namespace: GitLabNamespace,
+
+    /// Absent for projects without branches
+    pub default_branch: Option<String>,
+
+    pub description: Option<String>,
+
+    #[serde(default)]
+    pub archived: bool,
+
+    /// Repository has no commits, absent on older instances
+    pub empty_repo: Option<bool>,
+
+    /// Set by instances with delayed project deletion
+    pub marked_for_deletion_at: Option<String>,
+
+    /// Date of last push, issue or merge request activity
+    pub last_activity_at: Option<String>,
+
+    /// Set for forks, if parent project is visible for token user
+    pub forked_from_project: Option<GitLabProjectReference>,
+
+    /// Repository copy status of forks and imports: `none`, `scheduled`, `started`, `finished`, `failed`
+    pub import_status: Option<String>,
+
+    /// Count of open issues, absent if issues are disabled
+    pub open_issues_count: Option<usize>,
+
+    /// Image path prefix in container registry, e.g. `registry.company.com/backend/api`
+    pub container_registry_image_prefix: Option<String>,
+
+    /// Provided only if requested with `statistics=true`
+    pub statistics: Option<GitLabProjectStatistics>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabProjectStatistics {
+    pub lfs_objects_size: u64,
+
+    #[serde(default)]
+    pub repository_size: u64,
+
+    #[serde(default)]
+    pub wiki_size: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabProjectReference {
+    pub id: u32,
+    pub path_with_namespace: String,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -26,9 +75,31 @@ pub struct GitLabNamespace {
     pub name: String,
     pub path: String,
     pub full_path: String,
+
+    /// `group` or `user` for personal projects
+    #[serde(default)]
+    pub kind: String,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct GitLabRepositoryBranch {
     pub name: String,
-}
\ No newline at end of file
+    pub commit: GitLabCommit,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabRepositoryTag {
+    pub name: String,
+    pub commit: GitLabCommit,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabCommit {
+    pub id: String,
+}
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabUser {
+    pub id: u32,
+    pub username: String,
+    pub name: String,
+}
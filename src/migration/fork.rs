@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use log::info;
+use reqwest::blocking::Client;
+use serde_json::json;
+
+use crate::config::InstanceConfig;
+use crate::migration::api::{delete, encode_path_segment, post_json};
+use crate::migration::domain::GitLabProject;
+use crate::migration::project::{get_project, get_project_branches, get_project_tags};
+
+/// Forks are created asynchronously, repository is copied by background job
+const FORK_WAIT_ATTEMPTS: u32 = 300;
+
+const FORK_WAIT_INTERVAL: Duration = Duration::from_secs(1);
+
+pub enum ForkNamespace<'a> {
+    /// Group id
+    Group(u32),
+
+    /// Username for sudo
+    User(&'a str),
+}
+
+/// Order projects so upstream projects go before their forks, order is kept otherwise.
+pub fn get_upstreams_first(projects: Vec<GitLabProject>) -> Vec<GitLabProject> {
+    let upstream_ids: HashMap<u32, u32> = projects.iter()
+        .filter_map(|p| p.forked_from_project.as_ref().map(|upstream| (p.id, upstream.id)))
+        .collect();
+
+    let mut projects = projects;
+
+    projects.sort_by_key(|p| get_fork_depth(p.id, &upstream_ids));
+
+    projects
+}
+
+/// Count of upstream projects above project in fork network
+fn get_fork_depth(project_id: u32, upstream_ids: &HashMap<u32, u32>) -> usize {
+    let mut depth = 0;
+    let mut current_id = project_id;
+
+    while let Some(upstream_id) = upstream_ids.get(&current_id) {
+        depth += 1;
+        current_id = *upstream_id;
+
+        // broken fork networks can't be cycled, but source data isn't trusted
+        if depth > upstream_ids.len() {
+            break;
+        }
+    }
+
+    depth
+}
+
+/// Fork target upstream project into group or, with sudo, into user namespace and wait until
+/// its repository is copied. Fork has all upstream branches and tags, pushed fork refs don't remove them,
+/// see `remove_upstream_refs`.
+///
+/// API: https://docs.gitlab.com/ee/api/projects.html#fork-project
+pub fn create_gitlab_fork(client: &Client, instance: &InstanceConfig, upstream: &GitLabProject,
+                          namespace: ForkNamespace, name: &str, path: &str) -> anyhow::Result<GitLabProject> {
+    info!("fork project '{}/{}' as '{path}' at instance '{}'..", upstream.namespace.full_path, upstream.path,
+          instance.public_url);
+
+    let (api_path, namespace_id) = match namespace {
+        ForkNamespace::Group(group_id) => (format!("projects/{}/fork", upstream.id), Some(group_id)),
+        ForkNamespace::User(username) => (format!("projects/{}/fork?sudo={username}", upstream.id), None),
+    };
+
+    let mut body = json!({"name": name, "path": path, "visibility": "private"});
+
+    if let Some(namespace_id) = namespace_id {
+        body["namespace_id"] = json!(namespace_id);
+    }
+
+    let fork: GitLabProject = post_json(client, instance, &api_path, &body)
+        .context("unable to fork project")?;
+
+    for _ in 0..FORK_WAIT_ATTEMPTS {
+        let project = get_project(client, instance, fork.id)?
+            .context("fork wasn't found after creation")?;
+
+        match project.import_status.as_deref() {
+            Some("scheduled" | "started") => thread::sleep(FORK_WAIT_INTERVAL),
+            Some("failed") => return Err(anyhow!("fork repository copy has failed")),
+            _ => {
+                info!("project '{path}' has been forked");
+                return Ok(project)
+            }
+        }
+    }
+
+    Err(anyhow!("fork repository wasn't copied in {} seconds",
+                FORK_WAIT_ATTEMPTS as u64 * FORK_WAIT_INTERVAL.as_secs()))
+}
+
+/// Remove branches and tags which target fork got from upstream, but source fork doesn't have.
+/// Default branch can't be removed, so it has to be set to the one of source fork before.
+///
+/// API: https://docs.gitlab.com/ee/api/branches.html#delete-repository-branch
+pub fn remove_upstream_refs(client: &Client, source: &InstanceConfig, target: &InstanceConfig,
+                            source_fork: &GitLabProject, target_fork: &GitLabProject) -> anyhow::Result<()> {
+    let source_branches = get_project_branches(client, source, source_fork.id)?;
+
+    for branch in get_project_branches(client, target, target_fork.id)? {
+        if !source_branches.contains(&branch) {
+            info!("remove upstream branch '{branch}' from fork '{}'", target_fork.path);
+            delete(client, target, &format!("projects/{}/repository/branches/{}", target_fork.id,
+                                            encode_path_segment(&branch)))
+                .context(format!("unable to remove branch '{branch}'"))?;
+        }
+    }
+
+    let source_tags: Vec<String> = get_project_tags(client, source, source_fork.id)?.into_iter()
+        .map(|t| t.name).collect();
+
+    for tag in get_project_tags(client, target, target_fork.id)? {
+        if !source_tags.contains(&tag.name) {
+            info!("remove upstream tag '{}' from fork '{}'", tag.name, target_fork.path);
+            delete(client, target, &format!("projects/{}/repository/tags/{}", target_fork.id,
+                                            encode_path_segment(&tag.name)))
+                .context(format!("unable to remove tag '{}'", tag.name))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::migration::migrate_gitlab_instance;
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    #[test]
+    fn forks_should_be_created_after_upstream_with_fork_api() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let team_id = source.add_group("Team", None);
+
+        // fork is listed before its upstream
+        let fork_id = source.add_project(team_id, "api", &["feature"]);
+        let upstream_id = source.add_project(group_id, "api", &["main"]);
+        source.add_tag(upstream_id, "v1.0", "main");
+        source.set_project_attribute(fork_id, "forked_from_project",
+                                     json!({"id": upstream_id, "path_with_namespace": "backend/api"}));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+
+        migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(vec!["backend/api", "team/api"], target.project_paths());
+        // upstream refs which fork doesn't have are removed
+        assert_eq!(vec!["feature"], target.branches("team/api"));
+        assert!(target.tags("team/api").is_empty());
+        assert_eq!(vec!["v1.0"], target.tags("backend/api"));
+
+        let target_fork_id = target.project_id("team/api").unwrap();
+        let target_upstream_id = target.project_id("backend/api").unwrap();
+        assert_eq!(json!(target_upstream_id), target.project_attribute(target_fork_id, "forked_from_project")["id"]);
+    }
+}
@@ -1,23 +1,23 @@
-use anyhow::{anyhow, Context};
+use anyhow::anyhow;
 use log::{debug, error, info};
 use reqwest::blocking::Client;
 
 use crate::config::InstanceConfig;
+use crate::migration::api::{decode_json_response, send_request};
 use crate::migration::domain::GitLabGroup;
-use crate::migration::PRIVATE_TOKEN_HEADER;
 
 pub fn get_all_groups(client: &Client, instance: &InstanceConfig) -> anyhow::Result<Vec<GitLabGroup>> {
     let mut groups_page = 1;
 
     let mut results: Vec<GitLabGroup> = vec![];
 
-    let mut groups = get_group_list(&client, &instance, groups_page)?;
+    let mut groups = get_group_list(client, instance, groups_page)?;
 
     while !groups.is_empty() {
         results.append(&mut groups);
 
         groups_page += 1;
-        groups = get_group_list(&client, &instance, groups_page)?;
+        groups = get_group_list(client, instance, groups_page)?;
     }
 
     Ok(results)
@@ -30,18 +30,12 @@ pub fn get_group_list(client: &Client, instance: &InstanceConfig, page: u32) ->
 
     debug!("url: {url}");
 
-    let response = client.get(url)
-        .header(PRIVATE_TOKEN_HEADER, instance.token.to_string())
-        .send().context("gitlab api communication error")?;
+    let response = send_request(client.get(url), instance)?;
 
     let response_status = response.status();
 
     if response_status == reqwest::StatusCode::OK {
-        let groups = response.json().context("unable to decode server response")?;
-
-        debug!("---[HTTP RESPONSE]----");
-        debug!("{:?}", groups);
-        debug!("---[/HTTP RESPONSE]----");
+        let groups = decode_json_response(response)?;
 
         Ok(groups)
 
@@ -52,7 +46,7 @@ pub fn get_group_list(client: &Client, instance: &InstanceConfig, page: u32) ->
 }
 
 pub fn create_gitlab_private_group(client: &Client, instance: &InstanceConfig,
-   group_name: &str, path: &str, parent_id: Option<u32>) -> anyhow::Result<Vec<GitLabGroup>> {
+   group_name: &str, path: &str, parent_id: Option<u32>) -> anyhow::Result<GitLabGroup> {
     info!("create group '{group_name}' at instance '{}'..", instance.public_url);
 
     let parent_id_param = if let Some(value) = parent_id {
@@ -67,25 +61,16 @@ pub fn create_gitlab_private_group(client: &Client, instance: &InstanceConfig,
 
     debug!("url: {url}");
 
-    let response = client.post(url)
-        .header(PRIVATE_TOKEN_HEADER, instance.token.to_string())
-        .send().context("gitlab api communication error")?;
+    let response = send_request(client.post(url), instance)?;
 
-    let response_status = response.status().clone();
+    let response_status = response.status();
 
     if response_status == reqwest::StatusCode::CREATED {
-
-        let groups: &Vec<GitLabGroup> = &response.json().unwrap_or(vec![]);
-
-        debug!("---[HTTP RESPONSE]----");
-        debug!("{:?}", groups);
-        debug!("---[/HTTP RESPONSE]----");
+        let group: GitLabGroup = decode_json_response(response)?;
 
         info!("group '{group_name}' has been created");
 
-        let groups = vec![];
-
-        Ok(groups.clone())
+        Ok(group)
 
     } else {
         error!("unexpected server response code {}", response_status);
@@ -95,35 +80,28 @@ pub fn create_gitlab_private_group(client: &Client, instance: &InstanceConfig,
 
 #[cfg(test)]
 mod create_group_tests {
-    use log::{error, info};
     use reqwest::blocking::ClientBuilder;
 
-    use crate::config::InstanceConfig;
     use crate::migration::group::create_gitlab_private_group;
+    use crate::tests::gitlab::FakeGitLab;
     use crate::tests::init_logging;
 
-    #[ignore]
     #[test]
     fn group_have_to_be_created() {
         init_logging();
 
+        let gitlab = FakeGitLab::start();
+        let parent_id = gitlab.add_group("g4000", None);
+
         let client = ClientBuilder::new().build().unwrap();
 
-        let config = InstanceConfig {
-            public_url: "http://localhost:28080".to_string(),
-            git_url: "ssh://localhost:2222".to_string(),
-            token: "CHANGE-ME".to_string(),
-        };
-
-        match create_gitlab_private_group(&client, &config, "g5000", "g5000", None) {
-            Ok(groups) => {
-                info!("groups: {:?}", groups);
-            }
-            Err(e) => {
-                error!("{}", e);
-                error!("{}", e.root_cause());
-                assert!(false)
-            }
-        }
+        let group = create_gitlab_private_group(&client, &gitlab.instance(), "g5000", "g5000", None).unwrap();
+        assert_eq!("g5000", group.full_path);
+
+        let group = create_gitlab_private_group(&client, &gitlab.instance(), "g5001", "g5001", Some(parent_id)).unwrap();
+        assert_eq!("g4000/g5001", group.full_path);
+        assert_eq!(Some(parent_id), group.parent_id);
+
+        assert_eq!(vec!["g4000", "g5000", "g4000/g5001"], gitlab.group_paths());
     }
-}
\ No newline at end of file
+}
@@ -1,135 +1,343 @@
-use anyhow::Context;
-use log::{error, info};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::{anyhow, Context};
+use log::{error, info, warn};
 use reqwest::blocking::{Client, ClientBuilder};
 
-use crate::config::{ErrorHandlersConfig, InstanceConfig, MigrationConfig};
+use crate::config::{AppConfig, ArchivedProjectsPolicy, InstanceConfig};
 use crate::git::clone::copy_git_repo_from_one_remote_to_another;
-use crate::migration::domain::GitLabGroup;
+use crate::logging::{get_error_class, log_field, LOG_FIELD_DURATION_MS, LOG_FIELD_ERROR_CLASS, LOG_FIELD_PROJECT};
+use crate::metrics::MetricsRecorder;
+use crate::migration::collision::{get_target_placement, NewProject, TargetPlacement};
+use crate::migration::domain::{GitLabGroup, GitLabProject};
+use crate::migration::fork::{create_gitlab_fork, ForkNamespace, get_upstreams_first, remove_upstream_refs};
 use crate::migration::group::{create_gitlab_private_group, get_all_groups};
-use crate::migration::project::{create_gitlab_private_project, get_all_projects, get_project_branches, remove_gitlab_project};
-
+use crate::migration::namespace::{get_or_create_target_group, get_project_namespaces};
+use crate::migration::project::{archive_gitlab_project, create_gitlab_private_project, create_gitlab_private_user_project, get_all_projects,
+                                get_project_branches, get_project_statistics, remove_gitlab_project,
+                                set_project_default_branch};
+use crate::migration::user::UserMapper;
+use crate::phases::{PhaseReport, run_enabled_group_phases, run_enabled_instance_phases, run_enabled_phases};
+use crate::progress::{MigrationProgress, NoProgress, ProjectOutcome};
+
+pub mod api;
+pub mod collision;
 pub mod domain;
+pub mod fork;
 pub mod group;
+pub mod namespace;
 pub mod project;
+pub mod scope;
+pub mod user;
 
 pub const PRIVATE_TOKEN_HEADER: &str = "PRIVATE-TOKEN";
 
-pub fn migrate_gitlab_instance(source: &InstanceConfig, target: &InstanceConfig,
-                               migration_config: &MigrationConfig,
-                               error_handlers: &ErrorHandlersConfig) -> anyhow::Result<()> {
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    /// Projects created on target instance
+    pub projects: Vec<MigratedProject>,
+
+    /// Source projects left out with reason, e.g. `'backend/api': archived`
+    pub skipped: Vec<String>,
+
+    /// Results of optional phases executed for migrated projects
+    pub phases: Vec<PhaseReport>,
+
+    /// Already migrated projects archived on target by `migrate-and-archive` policy,
+    /// e.g. `'backend/api' -> 'backend/api'`
+    pub archived: Vec<String>,
+
+    /// Failures which didn't stop migration of the project, e.g. target project couldn't be archived
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigratedProject {
+    pub source_path: String,
+    pub target_path: String,
+
+    /// Source project is archived
+    pub archived: bool,
+
+    /// Target project has been archived after migration
+    pub target_archived: bool,
+}
+
+pub fn migrate_gitlab_instance(config: &AppConfig) -> anyhow::Result<MigrationReport> {
+    migrate_gitlab_instance_with_progress(config, &mut NoProgress)
+}
+
+/// Migration which reports every source project and git transfer sizes to `progress`.
+pub fn migrate_gitlab_instance_with_progress(config: &AppConfig,
+                                             progress: &mut dyn MigrationProgress) -> anyhow::Result<MigrationReport> {
+    let mut progress = MetricsRecorder::new(progress, &config.metrics);
+
+    let result = migrate_projects(config, &mut progress);
+    progress.finish();
+    result
+}
+
+fn migrate_projects(config: &AppConfig, progress: &mut dyn MigrationProgress) -> anyhow::Result<MigrationReport> {
+    let source = &config.source;
+    let target = &config.target;
+    let migration_config = &config.migration;
+    let error_handlers = &config.error_handlers;
+
     info!("migrating groups and projects from '{}' to '{}'..", source.public_url, target.public_url);
 
     let client = ClientBuilder::new().build().unwrap();
 
-    let source_instance_groups = get_all_groups(&client, &source)
+    let source_instance_groups = get_all_groups(&client, source)
         .context("unable to get gitlab groups from source instance")?;
 
-    let source_projects = get_all_projects(&client, &source)
-        .context("cannot get gitlab project list from source instance")?;
+    let source_projects = get_upstreams_first(get_all_projects(&client, source)
+        .context("cannot get gitlab project list from source instance")?);
 
-    create_groups_on_target_instance(&client, &source_instance_groups, &target)?;
+    create_groups_on_target_instance(&client, &source_instance_groups, target)?;
 
-    info!("received groups from source instance:");
-    info!("{:?}", source_instance_groups);
+    info!("received groups from source instance: {}", source_instance_groups.iter()
+        .map(|g| g.full_path.as_str()).collect::<Vec<&str>>().join(", "));
 
-    let target_instance_groups = get_all_groups(&client, &target)
+    let mut target_instance_groups = get_all_groups(&client, target)
         .context("unable to get gitlab groups from target instance")?;
 
-    let target_projects = get_all_projects(&client, &target)
+    let target_projects = get_all_projects(&client, target)
         .context("cannot get gitlab project list from target instance")?;
 
-    let mut progress = 0;
+    let mut report = MigrationReport::default();
+
+    for source_group in &source_instance_groups {
+        if let Some(target_group) = target_instance_groups.iter().find(|tg| tg.full_path == source_group.full_path) {
+            report.phases.append(&mut run_enabled_group_phases(&client, config, source_group, target_group));
+        }
+    }
+
+    let mut user_mapper = UserMapper::new(&client, target, &config.users.mapping);
+
+    // source project id -> migrated project, to fork upstreams
+    let mut migrated_projects: HashMap<u32, GitLabProject> = HashMap::new();
+
+    let mut processed = 0;
+
+    progress.start(source_projects.len());
 
     for source_project in &source_projects {
         info!("source project '{}'", source_project.name);
 
-        let source_group_found = &source_instance_groups.iter()
-            .find(|sg|sg.id == source_project.namespace.id);
-
-        match source_group_found {
-            Some(source_group) => {
-                let target_project_exists = target_projects.iter()
-                    .find(|tp|
-                        tp.name == source_project.name && tp.namespace.name == source_group.name &&
-                            tp.namespace.full_path == source_group.full_path
-                    );
-
-                match target_project_exists {
-                    None => {
-                        info!("project '{}' wasn't found on target instance", source_project.name);
-
-                        let target_group_found = target_instance_groups.iter()
-                            .find(|tg| tg.name == source_group.name &&
-                                tg.full_path == source_group.full_path);
-
-                        match target_group_found {
-                            Some(target_group) => {
-                                let source_project_branches = get_project_branches(
-                                    &client, &source, source_project.id)
-                                    .context("unable to get source project branches")?;
-
-                                if is_migration_allowed(migration_config.ignore_empty_repos,
-                                        source_project_branches.is_empty()) {
-
-                                    let new_projects = create_gitlab_private_project(
-                                        &client, &target, target_group.id,
-                                        &source_project.name, &source_project.path
-                                    ).context("cannot create project on target instance")?;
-
-                                    if !source_project_branches.is_empty() {
-                                        match copy_git_repo_from_one_remote_to_another(
-                                            &source_project.path, &source.git_url,
-                                            &source_group.full_path, &target_group.full_path,
-                                            &target.git_url
-                                        ) {
-                                            Ok(_) => {}
-                                            Err(e) => {
-                                                error!("repo copy error: {}", e);
-                                                error!("{}", e.root_cause());
-
-                                                if error_handlers.remove_target_repo_after_clone_error {
-                                                    let target_project = new_projects.first().unwrap();
-                                                    info!("removing target repo '{}' after git clone/push error(s)..", target_project.path);
-                                                    remove_gitlab_project(&client, &target, target_project.id)
-                                                        .context("unable to remove repository on target instance")?;
-                                                }
-
-                                                break;
-                                            }
-                                        }
+        let project_path = format!("{}/{}", source_project.namespace.full_path, source_project.path);
+        let _project = log_field(LOG_FIELD_PROJECT, &project_path);
+        let started_at = Instant::now();
+        progress.project_started(&project_path);
+
+        let namespaces = match get_project_namespaces(config, &source_instance_groups, &mut user_mapper,
+                                                      source_project)? {
+            Some(namespaces) => namespaces,
+            None => {
+                processed += 1;
+                progress.project_finished(&project_path, ProjectOutcome::Skipped);
+                continue
+            }
+        };
+
+        let source_project_path = format!("{}/{}", namespaces.source_group.full_path, source_project.path);
+
+        if !is_archived_project_allowed(migration_config.archived, source_project.archived) {
+            info!("archived project '{source_project_path}' is excluded from migration, skip");
+            report.skipped.push(format!("'{source_project_path}': archived"));
+            processed += 1;
+            progress.project_finished(&project_path, ProjectOutcome::Skipped);
+            continue
+        }
+
+        let outcome = match get_target_placement(&client, config, source_project, &namespaces, &target_projects)? {
+            TargetPlacement::New(new_project) => {
+                info!("project '{}' wasn't found on target instance", source_project.name);
+
+                let source_project_branches = get_project_branches(
+                    &client, source, source_project.id)
+                    .context("unable to get source project branches")?;
+
+                if is_migration_allowed(migration_config.ignore_empty_repos,
+                        source_project_branches.is_empty()) {
+
+                    let target_upstream = source_project.forked_from_project.as_ref()
+                        .and_then(|upstream| migrated_projects.get(&upstream.id));
+
+                    let forked = target_upstream.is_some() && new_project.existing_project.is_none();
+
+                    let created_project = create_target_project(
+                        &client, target, &mut target_instance_groups, &new_project, target_upstream
+                    ).context("cannot create project on target instance")?;
+
+                    migrated_projects.insert(source_project.id, created_project.clone());
+
+                    if !source_project_branches.is_empty() {
+                        let lfs = get_project_statistics(&client, source, source_project.id)
+                            .context("unable to get source project statistics")?
+                            .map(|statistics| statistics.lfs_objects_size > 0).unwrap_or(false);
+
+                        match copy_git_repo_from_one_remote_to_another(
+                            &config.git_bin_path, Path::new(&config.tmp_dir),
+                            &format!("{}/{source_project_path}", source.git_url),
+                            &target.git_url, &new_project.get_full_path(), lfs,
+                            &mut |transfer, bytes| progress.bytes_transferred(transfer, bytes)
+                        ) {
+                            Ok(_) => {
+                                if let Some(default_branch) = &source_project.default_branch {
+                                    if let Err(e) = set_project_default_branch(&client, target, created_project.id,
+                                                                               default_branch) {
+                                        error!("unable to set default branch '{default_branch}' for target project: {}",
+                                               e.root_cause());
                                     }
+                                }
 
-                                } else {
-                                    info!("migrate is not allowed for empty repo '{}'", source_project.path)
+                                if forked {
+                                    if let Err(e) = remove_upstream_refs(&client, source, target, source_project,
+                                                                         &created_project) {
+                                        error!("unable to remove upstream refs from fork: {}", e.root_cause());
+                                        report.errors.push(format!("'{source_project_path}': unable to remove \
+                                            upstream refs from fork: {e}"));
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                let _error_class = log_field(LOG_FIELD_ERROR_CLASS, get_error_class(&e));
+                                error!("repo copy error: {}", e);
+                                error!("{}", e.root_cause());
+
+                                if error_handlers.remove_target_repo_after_clone_error &&
+                                    new_project.existing_project.is_none() {
+                                    info!("removing target repo '{}' after git clone/push error(s)..", created_project.path);
+                                    remove_gitlab_project(&client, target, &created_project)
+                                        .context("unable to remove repository on target instance")?;
                                 }
 
+                                progress.project_finished(&project_path, ProjectOutcome::Failed);
+                                break;
                             }
-                            None => error!("unexpected error, target group wasn't found")
                         }
-
                     }
-                    Some(_) => info!("project '{}' already migrated, skip", source_project.path)
+
+                    report.phases.append(&mut run_enabled_phases(
+                        &client, config, source_project, &created_project, &source_project_path));
+
+                    let target_archived = source_project.archived &&
+                        migration_config.archived == ArchivedProjectsPolicy::MigrateAndArchive &&
+                        archive_target_project(&client, target, &created_project, &source_project_path, &mut report);
+
+                    report.projects.push(MigratedProject {
+                        source_path: source_project_path.to_string(),
+                        target_path: new_project.get_full_path(),
+                        archived: source_project.archived,
+                        target_archived,
+                    });
+
+                    ProjectOutcome::Migrated
+
+                } else {
+                    info!("migrate is not allowed for empty repo '{}'", source_project.path);
+                    ProjectOutcome::Skipped
                 }
+
+            }
+            TargetPlacement::Migrated(target_project) => {
+                info!("project '{}' already migrated, skip", source_project.path);
+
+                // archived source project could be migrated with another policy or archiving has failed
+                if source_project.archived && !target_project.archived &&
+                    migration_config.archived == ArchivedProjectsPolicy::MigrateAndArchive &&
+                    archive_target_project(&client, target, &target_project, &source_project_path, &mut report) {
+                    report.archived.push(format!("'{source_project_path}' -> '{}/{}'",
+                                                 target_project.namespace.full_path, target_project.path));
+                }
+
+                migrated_projects.insert(source_project.id, *target_project);
+                ProjectOutcome::Skipped
+            }
+            TargetPlacement::Skipped(collision) => {
+                warn!("project '{source_project_path}' collides on target instance, skip: {collision}");
+                report.skipped.push(format!("'{source_project_path}': {collision}"));
+                ProjectOutcome::Skipped
+            }
+            TargetPlacement::Failed(collision) => {
+                progress.project_finished(&project_path, ProjectOutcome::Failed);
+                return Err(anyhow!("project '{source_project_path}' collides on target instance: {collision}"))
             }
-            None => error!("source group wasn't found by id {}", source_project.namespace.id)
+        };
+
+        processed += 1;
+        progress.project_finished(&project_path, outcome);
+
+        let _duration = log_field(LOG_FIELD_DURATION_MS, &started_at.elapsed().as_millis().to_string());
+        info!("project '{project_path}' {}, migration progress: {processed}/{}", outcome.get_name(),
+              source_projects.len())
+    }
+
+    report.phases.append(&mut run_enabled_instance_phases(&client, config));
+
+    Ok(report)
+}
+
+/// Create project in user namespace with sudo or in target group, missing groups are created,
+/// e.g. quarantine group or `personal/{username}`. Forks of migrated upstreams are created with fork API.
+/// Existing empty project is returned as is.
+fn create_target_project(client: &Client, target: &InstanceConfig, target_groups: &mut Vec<GitLabGroup>,
+                         new_project: &NewProject,
+                         upstream: Option<&GitLabProject>) -> anyhow::Result<GitLabProject> {
+    if let Some(existing_project) = &new_project.existing_project {
+        info!("pushing into existing empty project '{}'", new_project.get_full_path());
+        return Ok(*existing_project.clone())
+    }
+
+    if new_project.user_namespace {
+        return match upstream {
+            Some(upstream) => create_gitlab_fork(client, target, upstream,
+                                                 ForkNamespace::User(&new_project.namespace_path),
+                                                 &new_project.name, &new_project.path),
+            None => create_gitlab_private_user_project(client, target, &new_project.namespace_path,
+                                                       &new_project.name, &new_project.path)
         }
+    }
+
+    let target_group = get_or_create_target_group(client, target, target_groups, &new_project.namespace_path)?;
 
-        progress += 1;
-        info!("migration progress: {progress}/{}", source_projects.len())
+    match upstream {
+        Some(upstream) => create_gitlab_fork(client, target, upstream, ForkNamespace::Group(target_group.id),
+                                             &new_project.name, &new_project.path),
+        None => create_gitlab_private_project(client, target, target_group.id, &new_project.name, &new_project.path)
     }
+}
 
-    Ok(())
+/// Archive target project for `migrate-and-archive` policy. Repository is migrated already,
+/// so failure is recorded to report instead of stopping migration.
+fn archive_target_project(client: &Client, target: &InstanceConfig, target_project: &GitLabProject,
+                          source_project_path: &str, report: &mut MigrationReport) -> bool {
+    match archive_gitlab_project(client, target, target_project.id) {
+        Ok(_) => true,
+        Err(e) => {
+            error!("unable to archive target project: {}", e.root_cause());
+            report.errors.push(format!("'{source_project_path}': unable to archive target project: {}",
+                                       e.root_cause()));
+            false
+        }
+    }
 }
 
-fn is_migration_allowed(ignore_empty_repos: bool, source_project_is_empty: bool) -> bool {
-    (ignore_empty_repos && !source_project_is_empty) || !ignore_empty_repos
+pub fn is_migration_allowed(ignore_empty_repos: bool, source_project_is_empty: bool) -> bool {
+    !ignore_empty_repos || !source_project_is_empty
 }
 
-fn create_groups_on_target_instance(client: &Client, source_groups: &Vec<GitLabGroup>,
+pub fn is_archived_project_allowed(policy: ArchivedProjectsPolicy, source_project_is_archived: bool) -> bool {
+    policy != ArchivedProjectsPolicy::Skip || !source_project_is_archived
+}
+
+fn create_groups_on_target_instance(client: &Client, source_groups: &[GitLabGroup],
                                     target: &InstanceConfig) -> anyhow::Result<()> {
     info!("creating groups on target instance..");
 
-    let target_instance_groups = get_all_groups(&client, &target)
+    let target_instance_groups = get_all_groups(client, target)
         .context("unable to get gitlab groups from target instance")?;
 
     let parent_groups: Vec<&GitLabGroup> = source_groups.iter()
@@ -140,18 +348,15 @@ fn create_groups_on_target_instance(client: &Client, source_groups: &Vec<GitLabG
         let group_found = target_instance_groups.iter()
             .find(|tg|tg.full_path == parent_group.full_path);
 
-        match group_found {
-            None => {
-                create_gitlab_private_group(
-                    &client, &target, &parent_group.name,
-                    &parent_group.path, None
-                ).context("cannot create parent group")?;
-            }
-            Some(_) => {}
+        if group_found.is_none() {
+            create_gitlab_private_group(
+                client, target, &parent_group.name,
+                &parent_group.path, None
+            ).context("cannot create parent group")?;
         }
     }
 
-    let target_instance_groups = get_all_groups(&client, &target)
+    let target_instance_groups = get_all_groups(client, target)
         .context("unable to get gitlab groups from target instance")?;
 
     let non_parent_groups: Vec<&GitLabGroup> = source_groups.iter()
@@ -162,34 +367,28 @@ fn create_groups_on_target_instance(client: &Client, source_groups: &Vec<GitLabG
         let group_found = target_instance_groups.iter()
             .find(|tg|tg.full_path == non_parent_group.full_path);
 
-        match group_found {
-            None => {
-                let parent_group_found = parent_groups.iter()
-                    .find(|spg|
-                        spg.id == non_parent_group.parent_id.unwrap_or(0));
+        if group_found.is_none() {
+            let parent_group_found = parent_groups.iter()
+                .find(|spg|
+                    spg.id == non_parent_group.parent_id.unwrap_or(0));
 
-                if let Some(parent_group) = parent_group_found {
+            if let Some(parent_group) = parent_group_found {
 
-                    let target_group_found = target_instance_groups.iter()
-                        .find(|tig|tig.full_path == parent_group.full_path);
+                let target_group_found = target_instance_groups.iter()
+                    .find(|tig|tig.full_path == parent_group.full_path);
 
-                    match target_group_found {
-                        Some(target_group) => {
+                if let Some(target_group) = target_group_found {
 
-                            create_gitlab_private_group(
-                                &client, &target, &non_parent_group.name,
-                                &non_parent_group.path, Some(target_group.id)
-                            ).context("cannot create gitlab group on target instance")?;
+                    create_gitlab_private_group(
+                        client, target, &non_parent_group.name,
+                        &non_parent_group.path, Some(target_group.id)
+                    ).context("cannot create gitlab group on target instance")?;
 
-                        }
-                        None => {}
-                    }
-
-                } else {
-                    error!("parent group wasn't found by id {}", non_parent_group.parent_id.unwrap_or(0))
                 }
+
+            } else {
+                error!("parent group wasn't found by id {}", non_parent_group.parent_id.unwrap_or(0))
             }
-            Some(_) => {}
         }
     }
 
@@ -215,4 +414,241 @@ mod tests_is_migration_allowed {
         assert!(is_migration_allowed(true, false));
         assert!(is_migration_allowed(false, false));
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests_is_archived_project_allowed {
+    use crate::config::ArchivedProjectsPolicy;
+    use crate::migration::is_archived_project_allowed;
+
+    #[test]
+    fn skip_policy_deny_to_migrate_archived_project() {
+        assert!(!is_archived_project_allowed(ArchivedProjectsPolicy::Skip, true));
+        assert!(is_archived_project_allowed(ArchivedProjectsPolicy::Skip, false));
+    }
+
+    #[test]
+    fn archived_projects_should_be_migrated_by_other_policies() {
+        assert!(is_archived_project_allowed(ArchivedProjectsPolicy::Migrate, true));
+        assert!(is_archived_project_allowed(ArchivedProjectsPolicy::MigrateAndArchive, true));
+    }
+}
+
+#[cfg(test)]
+mod migration_tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::config::ArchivedProjectsPolicy;
+    use crate::migration::{create_groups_on_target_instance, migrate_gitlab_instance};
+    use crate::migration::group::get_all_groups;
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    fn get_source_instance() -> FakeGitLab {
+        let source = FakeGitLab::start();
+
+        let backend_id = source.add_group("Backend", None);
+        let libs_id = source.add_group("Libs", Some(backend_id));
+
+        source.add_project(backend_id, "api", &["main", "develop"]);
+        source.add_project(libs_id, "common", &["master"]);
+        source.add_project(backend_id, "drafts", &[]);
+
+        source
+    }
+
+    #[test]
+    fn groups_and_projects_should_be_migrated() {
+        init_logging();
+
+        let source = get_source_instance();
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+
+        migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(vec!["backend", "backend/libs"], target.group_paths());
+        assert_eq!(vec!["backend/api", "backend/libs/common", "backend/drafts"], target.project_paths());
+
+        assert_eq!(vec!["develop", "main"], target.branches("backend/api"));
+        assert_eq!(vec!["master"], target.branches("backend/libs/common"));
+        assert!(target.branches("backend/drafts").is_empty());
+    }
+
+    #[test]
+    fn already_migrated_projects_should_be_skipped() {
+        init_logging();
+
+        let source = get_source_instance();
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let backend_id = target.add_group("Backend", None);
+        target.add_project(backend_id, "api", &["main"]);
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+
+        migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(vec!["main"], target.branches("backend/api"));
+        assert_eq!(3, target.project_paths().len());
+    }
+
+    #[test]
+    fn empty_projects_should_be_ignored_when_configured() {
+        init_logging();
+
+        let source = get_source_instance();
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.migration.ignore_empty_repos = true;
+
+        migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(vec!["backend/api", "backend/libs/common"], target.project_paths());
+    }
+
+    #[test]
+    fn archived_projects_should_be_handled_by_policy() {
+        init_logging();
+
+        let source = get_source_instance();
+        let api_id = source.project_id("backend/api").unwrap();
+        source.set_project_attribute(api_id, "archived", json!(true));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.migration.archived = ArchivedProjectsPolicy::Skip;
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(vec!["backend/libs/common", "backend/drafts"], target.project_paths());
+        assert_eq!(vec!["'backend/api': archived"], report.skipped);
+
+        config.migration.archived = ArchivedProjectsPolicy::MigrateAndArchive;
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+        assert_eq!(vec!["develop", "main"], target.branches("backend/api"));
+        assert_eq!(json!(true), target.project_attribute(target_api_id, "archived"));
+        assert!(report.projects[0].archived && report.projects[0].target_archived);
+
+        // projects migrated with another policy are archived on the next run
+        let target = FakeGitLab::start();
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+
+        migrate_gitlab_instance(&config).unwrap();
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+        assert_eq!(json!(false), target.project_attribute(target_api_id, "archived"));
+
+        config.migration.archived = ArchivedProjectsPolicy::MigrateAndArchive;
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(json!(true), target.project_attribute(target_api_id, "archived"));
+        assert_eq!(vec!["'backend/api' -> 'backend/api'"], report.archived);
+    }
+
+    #[test]
+    fn archive_error_should_be_reported_without_stopping_migration() {
+        init_logging();
+
+        let source = get_source_instance();
+        let api_id = source.project_id("backend/api").unwrap();
+        source.set_project_attribute(api_id, "archived", json!(true));
+
+        let target = FakeGitLab::start();
+        target.disable_archiving();
+
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.migration.archived = ArchivedProjectsPolicy::MigrateAndArchive;
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(vec!["backend/api", "backend/libs/common", "backend/drafts"], target.project_paths());
+
+        let api = report.projects.iter().find(|p| p.source_path == "backend/api").unwrap();
+        assert!(api.archived && !api.target_archived);
+
+        assert_eq!(1, report.errors.len());
+        assert!(report.errors[0].starts_with("'backend/api': unable to archive target project"),
+                "{:?}", report.errors);
+    }
+
+    #[test]
+    fn target_project_should_be_removed_after_push_error() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        source.add_project(group_id, "api", &["main"]);
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut target_instance = target.instance();
+        target_instance.git_url = format!("file://{}/unknown", tmp_dir.path().display());
+
+        let mut config = get_test_config(source.instance(), target_instance, tmp_dir.path());
+        config.error_handlers.remove_target_repo_after_clone_error = true;
+
+        migrate_gitlab_instance(&config).unwrap();
+
+        assert!(target.project_paths().is_empty());
+        assert!(target.requests().iter().any(|r| r.method == "DELETE"));
+    }
+
+    #[test]
+    fn target_project_should_be_kept_after_push_error_by_default() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        source.add_project(group_id, "api", &["main"]);
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut target_instance = target.instance();
+        target_instance.git_url = format!("file://{}/unknown", tmp_dir.path().display());
+
+        let config = get_test_config(source.instance(), target_instance, tmp_dir.path());
+
+        migrate_gitlab_instance(&config).unwrap();
+
+        assert_eq!(vec!["backend/api"], target.project_paths());
+    }
+
+    #[test]
+    fn missing_sub_groups_should_be_created_under_existing_parent() {
+        init_logging();
+
+        let source = get_source_instance();
+        let target = FakeGitLab::start();
+        target.add_group("Backend", None);
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let source_groups = get_all_groups(&client, &source.instance()).unwrap();
+
+        create_groups_on_target_instance(&client, &source_groups, &target.instance()).unwrap();
+
+        assert_eq!(vec!["backend", "backend/libs"], target.group_paths());
+
+        let created_groups = target.requests().iter()
+            .filter(|r| r.method == "POST" && r.url.starts_with("/api/v4/groups")).count();
+        assert_eq!(1, created_groups);
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::Context;
+use log::{info, warn};
+use reqwest::blocking::Client;
+
+use crate::config::{AppConfig, InstanceConfig, PersonalProjectsPolicy};
+use crate::migration::domain::{GitLabGroup, GitLabProject};
+use crate::migration::group::{create_gitlab_private_group, get_all_groups};
+use crate::migration::user::UserMapper;
+
+const USER_NAMESPACE_KIND: &str = "user";
+
+/// Namespace of source project and where it goes on target instance.
+#[derive(Debug, Clone)]
+pub struct ProjectNamespaces {
+    /// Group or user namespace of source project
+    pub source_group: GitLabGroup,
+
+    /// Full path of target group or username
+    pub target_path: String,
+
+    /// Project is created in user namespace on target instance
+    pub user_namespace: bool,
+}
+
+/// Group projects keep their group path, personal projects are handled by `migration.personal-projects.policy`.
+/// Returns `None` if project is out of migration scope.
+pub fn get_project_namespaces(config: &AppConfig, source_groups: &[GitLabGroup], user_mapper: &mut UserMapper,
+                              project: &GitLabProject) -> anyhow::Result<Option<ProjectNamespaces>> {
+    if let Some(source_group) = source_groups.iter().find(|sg| sg.id == project.namespace.id) {
+        return Ok(Some(ProjectNamespaces {
+            source_group: source_group.clone(),
+            target_path: source_group.full_path.to_string(),
+            user_namespace: false,
+        }))
+    }
+
+    if project.namespace.kind != USER_NAMESPACE_KIND {
+        warn!("source group wasn't found by id {}, skip project '{}'", project.namespace.id, project.path);
+        return Ok(None)
+    }
+
+    let project_path = format!("{}/{}", project.namespace.full_path, project.path);
+    let username = &project.namespace.path;
+    let personal_projects = &config.migration.personal_projects;
+
+    let (target_path, user_namespace) = match personal_projects.policy {
+        PersonalProjectsPolicy::Exclude => {
+            info!("personal project '{project_path}' is excluded from migration, skip");
+            return Ok(None)
+        }
+        PersonalProjectsPolicy::UserNamespace => match user_mapper.find_target_user(username)
+            .context(format!("unable to find target user for '{username}'"))? {
+            Some(user) => (user.username, true),
+            None => {
+                warn!("personal project '{project_path}': user '{username}' wasn't found on target instance, skip");
+                return Ok(None)
+            }
+        },
+        PersonalProjectsPolicy::Group => (format!("{}/{username}", personal_projects.group), false)
+    };
+
+    Ok(Some(ProjectNamespaces {
+        source_group: GitLabGroup {
+            id: project.namespace.id,
+            parent_id: None,
+            name: project.namespace.name.to_string(),
+            path: project.namespace.path.to_string(),
+            full_path: project.namespace.full_path.to_string(),
+            visibility: "private".to_string(),
+        },
+        target_path,
+        user_namespace,
+    }))
+}
+
+/// Find target group by full path, missing groups of the path are created as private, e.g. `personal/jdoe`.
+pub fn get_or_create_target_group(client: &Client, target: &InstanceConfig, target_groups: &mut Vec<GitLabGroup>,
+                                  full_path: &str) -> anyhow::Result<GitLabGroup> {
+    let mut parent_id: Option<u32> = None;
+    let mut current_path = String::new();
+
+    for segment in full_path.split('/') {
+        if !current_path.is_empty() {
+            current_path.push('/');
+        }
+        current_path.push_str(segment);
+
+        if !target_groups.iter().any(|tg| tg.full_path == current_path) {
+            create_gitlab_private_group(client, target, segment, segment, parent_id)
+                .context(format!("cannot create group '{current_path}' on target instance"))?;
+
+            *target_groups = get_all_groups(client, target)
+                .context("unable to get gitlab groups from target instance")?;
+        }
+
+        let group = target_groups.iter().find(|tg| tg.full_path == current_path)
+            .context(format!("group '{current_path}' wasn't found on target instance"))?;
+
+        parent_id = Some(group.id);
+    }
+
+    target_groups.iter().find(|tg| tg.full_path == full_path).cloned()
+        .context(format!("group '{full_path}' wasn't found on target instance"))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::config::PersonalProjectsPolicy;
+    use crate::migration::migrate_gitlab_instance;
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    fn get_source_instance() -> FakeGitLab {
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        source.add_project(group_id, "api", &["main"]);
+
+        source.add_user("jdoe");
+        source.add_project(source.group_id("jdoe").unwrap(), "dotfiles", &["main"]);
+
+        source
+    }
+
+    #[test]
+    fn personal_projects_should_be_migrated_by_policy() {
+        init_logging();
+
+        let source = get_source_instance();
+        let target = FakeGitLab::start();
+        target.add_user("john.doe");
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+
+        migrate_gitlab_instance(&config).unwrap();
+        assert_eq!(vec!["backend/api"], target.project_paths());
+
+        config.migration.personal_projects.policy = PersonalProjectsPolicy::UserNamespace;
+        config.users.mapping.insert("jdoe".to_string(), "john.doe".to_string());
+
+        migrate_gitlab_instance(&config).unwrap();
+        assert_eq!(vec!["main"], target.branches("john.doe/dotfiles"));
+
+        let project_request = target.requests().into_iter()
+            .find(|r| r.method == "POST" && r.url.contains("path=dotfiles")).unwrap();
+        assert!(project_request.url.contains("sudo=john.doe"), "{}", project_request.url);
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.migration.personal_projects.policy = PersonalProjectsPolicy::Group;
+
+        migrate_gitlab_instance(&config).unwrap();
+        assert_eq!(vec!["main"], target.branches("personal/jdoe/dotfiles"));
+    }
+}
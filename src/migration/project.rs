@@ -1,23 +1,24 @@
-use anyhow::{anyhow, Context};
+use anyhow::anyhow;
 use log::{debug, error, info};
 use reqwest::blocking::Client;
+use serde_json::{json, Value};
 
 use crate::config::InstanceConfig;
-use crate::migration::domain::{GitLabProject, GitLabRepositoryBranch};
-use crate::migration::PRIVATE_TOKEN_HEADER;
+use crate::migration::api::{decode_json_response, get_all_pages, get_json, post_json, put_json, send_request};
+use crate::migration::domain::{GitLabProject, GitLabProjectStatistics, GitLabRepositoryBranch, GitLabRepositoryTag};
 
 pub fn get_all_projects(client: &Client, instance: &InstanceConfig) -> anyhow::Result<Vec<GitLabProject>> {
     let mut page = 1;
 
     let mut results: Vec<GitLabProject> = vec![];
 
-    let mut groups = get_project_list(&client, &instance, page)?;
+    let mut groups = get_project_list(client, instance, page)?;
 
     while !groups.is_empty() {
         results.append(&mut groups);
 
         page += 1;
-        groups = get_project_list(&client, &instance, page)?;
+        groups = get_project_list(client, instance, page)?;
     }
 
     Ok(results)
@@ -30,18 +31,12 @@ pub fn get_project_list(client: &Client, instance: &InstanceConfig, page: u32) -
 
     debug!("url: {url}");
 
-    let response = client.get(url)
-        .header(PRIVATE_TOKEN_HEADER, instance.token.to_string())
-        .send().context("gitlab api communication error")?;
+    let response = send_request(client.get(url), instance)?;
 
     let response_status = response.status();
 
     if response_status == reqwest::StatusCode::OK {
-        let projects = response.json().context("unable to decode server response")?;
-
-        debug!("---[HTTP RESPONSE]----");
-        debug!("{:?}", projects);
-        debug!("---[/HTTP RESPONSE]----");
+        let projects = decode_json_response(response)?;
 
         Ok(projects)
 
@@ -53,29 +48,54 @@ pub fn get_project_list(client: &Client, instance: &InstanceConfig, page: u32) -
 
 pub fn get_project_branches(client: &Client, instance: &InstanceConfig,
                             project_id: u32) -> anyhow::Result<Vec<String>> {
+    let branches = get_project_branch_list(client, instance, project_id)?;
+
+    Ok(branches.into_iter().map(|b| b.name).collect())
+}
+
+pub fn get_project_branch_list(client: &Client, instance: &InstanceConfig,
+                               project_id: u32) -> anyhow::Result<Vec<GitLabRepositoryBranch>> {
     info!("get project (id {project_id}) branches, for instance '{}'..", instance.public_url);
 
-    let url = format!("{}/api/v4/projects/{project_id}/repository/branches?per_page=100", instance.public_url);
+    get_all_pages(client, instance, &format!("projects/{project_id}/repository/branches"))
+}
+
+pub fn get_project_tags(client: &Client, instance: &InstanceConfig,
+                        project_id: u32) -> anyhow::Result<Vec<GitLabRepositoryTag>> {
+    info!("get project (id {project_id}) tags, for instance '{}'..", instance.public_url);
+
+    get_all_pages(client, instance, &format!("projects/{project_id}/repository/tags"))
+}
+
+pub fn get_project_statistics(client: &Client, instance: &InstanceConfig,
+                              project_id: u32) -> anyhow::Result<Option<GitLabProjectStatistics>> {
+    info!("get project (id {project_id}) statistics, for instance '{}'..", instance.public_url);
+
+    let project: GitLabProject = get_json(client, instance,
+                                          &format!("projects/{project_id}?statistics=true"))?;
+
+    Ok(project.statistics)
+}
+
+pub fn get_project(client: &Client, instance: &InstanceConfig,
+                   project_id: u32) -> anyhow::Result<Option<GitLabProject>> {
+    info!("get project with id {project_id} from instance '{}'..", instance.public_url);
+
+    let url = format!("{}/api/v4/projects/{project_id}", instance.public_url);
 
     debug!("url: {url}");
 
-    let response = client.get(url)
-        .header(PRIVATE_TOKEN_HEADER, instance.token.to_string())
-        .send().context("gitlab api communication error")?;
+    let response = send_request(client.get(url), instance)?;
 
     let response_status = response.status();
 
     if response_status == reqwest::StatusCode::OK {
-        let branches: Vec<GitLabRepositoryBranch> = response.json().context("unable to decode server response")?;
+        let project: GitLabProject = decode_json_response(response)?;
 
-        let branch_names: Vec<String> = branches.clone().into_iter()
-                                    .map(|b| b.name).collect::<Vec<String>>();
+        Ok(Some(project))
 
-        debug!("---[HTTP RESPONSE]----");
-        debug!("{:?}", branches);
-        debug!("---[/HTTP RESPONSE]----");
-
-        Ok(branch_names)
+    } else if response_status == reqwest::StatusCode::NOT_FOUND {
+        Ok(None)
 
     } else {
         error!("unexpected server response code {}", response_status);
@@ -84,30 +104,40 @@ pub fn get_project_branches(client: &Client, instance: &InstanceConfig,
 }
 
 pub fn create_gitlab_private_project(client: &Client, instance: &InstanceConfig,
-                     group_id: u32, name: &str, path: &str) -> anyhow::Result<Vec<GitLabProject>> {
+                     group_id: u32, name: &str, path: &str) -> anyhow::Result<GitLabProject> {
     info!("create project '{name}' with group-id {group_id} at instance '{}'..", instance.public_url);
 
-    let url = format!("{}/api/v4/projects?name={}&path={}&visibility=private&namespace_id={}",
-                      instance.public_url, name, path, group_id);
+    create_project(client, instance, name, &format!("name={}&path={}&visibility=private&namespace_id={}",
+                                                     name, path, group_id))
+}
+
+/// Create project in user namespace, requires admin token.
+///
+/// API: https://docs.gitlab.com/ee/api/index.html#sudo
+pub fn create_gitlab_private_user_project(client: &Client, instance: &InstanceConfig,
+                                          username: &str, name: &str, path: &str) -> anyhow::Result<GitLabProject> {
+    info!("create project '{name}' for user '{username}' at instance '{}'..", instance.public_url);
+
+    create_project(client, instance, name, &format!("name={}&path={}&visibility=private&sudo={}",
+                                                     name, path, username))
+}
+
+fn create_project(client: &Client, instance: &InstanceConfig, name: &str,
+                  query: &str) -> anyhow::Result<GitLabProject> {
+    let url = format!("{}/api/v4/projects?{query}", instance.public_url);
 
     debug!("url: {url}");
 
-    let response = client.post(url)
-        .header(PRIVATE_TOKEN_HEADER, instance.token.to_string())
-        .send().context("gitlab api communication error")?;
+    let response = send_request(client.post(url), instance)?;
 
     let response_status = response.status();
 
     if response_status == reqwest::StatusCode::CREATED {
-        let projects = response.json().unwrap_or(vec![]);
-
-        debug!("---[HTTP RESPONSE]----");
-        debug!("{:?}", projects);
-        debug!("---[/HTTP RESPONSE]----");
+        let project: GitLabProject = decode_json_response(response)?;
 
         info!("project '{name}' has been created");
 
-        Ok(projects)
+        Ok(project)
 
     } else {
         error!("unexpected server response code {}", response_status);
@@ -115,28 +145,157 @@ pub fn create_gitlab_private_project(client: &Client, instance: &InstanceConfig,
     }
 }
 
+/// Branch which is used by default for merge requests and clones.
+///
+/// API: https://docs.gitlab.com/ee/api/projects.html#edit-project
+pub fn set_project_default_branch(client: &Client, instance: &InstanceConfig, project_id: u32,
+                                  branch: &str) -> anyhow::Result<()> {
+    info!("set default branch '{branch}' for project with id {project_id} at instance '{}'..", instance.public_url);
+
+    let _: Value = put_json(client, instance, &format!("projects/{project_id}"), &json!({"default_branch": branch}))?;
+
+    Ok(())
+}
+
+/// API: https://docs.gitlab.com/ee/api/projects.html#archive-a-project
+pub fn archive_gitlab_project(client: &Client, instance: &InstanceConfig, project_id: u32) -> anyhow::Result<()> {
+    info!("archive project with id {project_id} at instance '{}'..", instance.public_url);
+
+    let _: Value = post_json(client, instance, &format!("projects/{project_id}/archive"), &json!({}))?;
+
+    Ok(())
+}
+
+/// Remove project from instance.
+///
+/// Instances with delayed deletion only mark project for deletion on the first call,
+/// so the second call with `permanently_remove` is required to remove it immediately.
+///
 /// API: https://docs.gitlab.com/ee/api/projects.html#delete-project
 pub fn remove_gitlab_project(client: &Client, instance: &InstanceConfig,
-                             project_id: u32) -> anyhow::Result<()> {
-    info!("remove project with id {project_id} at instance '{}'..", instance.public_url);
+                             project: &GitLabProject) -> anyhow::Result<()> {
+    info!("remove project with id {} at instance '{}'..", project.id, instance.public_url);
+
+    send_project_delete_request(client, instance, project.id, &[])?;
+
+    if let Some(marked_project) = get_project(client, instance, project.id)? {
+        if marked_project.marked_for_deletion_at.is_some() {
+            let full_path = format!("{}/{}", project.namespace.full_path, project.path);
+
+            info!("project '{full_path}' has been marked for deletion, removing permanently..");
+
+            send_project_delete_request(client, instance, project.id,
+                &[("permanently_remove", "true"), ("full_path", &full_path)])?;
+        }
+    }
+
+    info!("project '{}' has been removed", project.id);
 
+    Ok(())
+}
+
+fn send_project_delete_request(client: &Client, instance: &InstanceConfig,
+                               project_id: u32, params: &[(&str, &str)]) -> anyhow::Result<()> {
     let url = format!("{}/api/v4/projects/{project_id}", instance.public_url);
 
     debug!("url: {url}");
 
-    let response = client.post(url)
-        .header(PRIVATE_TOKEN_HEADER, instance.token.to_string())
-        .send().context("gitlab api communication error")?;
+    let response = send_request(client.delete(url).query(params), instance)?;
 
     let response_status = response.status();
 
     if response_status == reqwest::StatusCode::ACCEPTED {
-        info!("project '{project_id}' has been removed");
-
         Ok(())
 
     } else {
         error!("unexpected server response code {}", response_status);
         Err(anyhow!("unexpected server response"))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod project_tests {
+    use reqwest::blocking::ClientBuilder;
+
+    use crate::migration::project::{create_gitlab_private_project, remove_gitlab_project};
+    use crate::tests::init_logging;
+    use crate::tests::mock::MockServer;
+
+    const PROJECT_JSON: &str = r#"{"id":15,"name":"demo","path":"demo","visibility":"private",
+        "namespace":{"id":3,"name":"Backend","path":"backend","full_path":"backend"}}"#;
+
+    const MARKED_PROJECT_JSON: &str = r#"{"id":15,"name":"demo","path":"demo","visibility":"private",
+        "marked_for_deletion_at":"2023-07-20",
+        "namespace":{"id":3,"name":"Backend","path":"backend","full_path":"backend"}}"#;
+
+    #[test]
+    fn created_project_should_be_returned() {
+        init_logging();
+
+        let server = MockServer::start();
+        server.mock("POST", "/api/v4/projects", 201, PROJECT_JSON);
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let project = create_gitlab_private_project(&client, &server.instance(),
+                                                    3, "demo", "demo").unwrap();
+
+        assert_eq!(15, project.id);
+        assert_eq!("backend", project.namespace.full_path);
+    }
+
+    #[test]
+    fn project_should_be_removed_with_delete_request() {
+        init_logging();
+
+        let server = MockServer::start();
+        server.mock("DELETE", "/api/v4/projects/15", 202, r#"{"message":"202 Accepted"}"#);
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let project = serde_json::from_str(PROJECT_JSON).unwrap();
+
+        remove_gitlab_project(&client, &server.instance(), &project).unwrap();
+
+        let requests = server.requests();
+
+        let delete_requests = requests.iter().filter(|r| r.method == "DELETE").count();
+        assert_eq!(1, delete_requests);
+        assert_eq!("/api/v4/projects/15", requests.first().unwrap().url);
+    }
+
+    #[test]
+    fn project_marked_for_deletion_should_be_removed_permanently() {
+        init_logging();
+
+        let server = MockServer::start();
+        server.mock("DELETE", "/api/v4/projects/15", 202, r#"{"message":"202 Accepted"}"#);
+        server.mock("GET", "/api/v4/projects/15", 200, MARKED_PROJECT_JSON);
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let project = serde_json::from_str(PROJECT_JSON).unwrap();
+
+        remove_gitlab_project(&client, &server.instance(), &project).unwrap();
+
+        let requests = server.requests();
+
+        let last_request = requests.last().unwrap();
+        assert_eq!("DELETE", last_request.method);
+        assert_eq!("/api/v4/projects/15?permanently_remove=true&full_path=backend%2Fdemo", last_request.url);
+    }
+
+    #[test]
+    fn return_error_for_unexpected_delete_response() {
+        init_logging();
+
+        let server = MockServer::start();
+        server.mock("DELETE", "/api/v4/projects/15", 403, r#"{"message":"403 Forbidden"}"#);
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let project = serde_json::from_str(PROJECT_JSON).unwrap();
+
+        assert!(remove_gitlab_project(&client, &server.instance(), &project).is_err());
+    }
+}
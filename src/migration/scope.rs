@@ -0,0 +1,93 @@
+use anyhow::Context;
+use log::info;
+use reqwest::blocking::Client;
+
+use crate::config::AppConfig;
+use crate::migration::domain::{GitLabGroup, GitLabProject};
+use crate::migration::group::get_all_groups;
+use crate::migration::project::{get_all_projects, get_project_branches};
+use crate::migration::collision::{get_target_placement, TargetPlacement};
+use crate::migration::{is_archived_project_allowed, is_migration_allowed};
+use crate::migration::namespace::get_project_namespaces;
+use crate::migration::user::UserMapper;
+
+/// Source project in migration scope with its copy on target instance.
+#[derive(Debug, Clone)]
+pub struct ScopedProject {
+    pub source_project: GitLabProject,
+
+    /// Group of source project, user namespace for personal projects
+    pub source_group: GitLabGroup,
+
+    /// `None` if project wasn't migrated yet
+    pub target_project: Option<GitLabProject>,
+
+    /// Planned or existing place on target instance
+    pub placement: TargetPlacement,
+}
+
+impl ScopedProject {
+    pub fn get_full_path(&self) -> String {
+        format!("{}/{}", self.source_group.full_path, self.source_project.path)
+    }
+}
+
+/// Get source projects which `migrate` command would process, matched with target projects
+/// the same way as `migrate_gitlab_instance` does.
+pub fn get_scoped_projects(client: &Client, config: &AppConfig) -> anyhow::Result<Vec<ScopedProject>> {
+    let source = &config.source;
+    let target = &config.target;
+
+    info!("get projects in migration scope..");
+
+    let source_groups = get_all_groups(client, source)
+        .context("unable to get gitlab groups from source instance")?;
+
+    let source_projects = get_all_projects(client, source)
+        .context("cannot get gitlab project list from source instance")?;
+
+    let target_projects = get_all_projects(client, target)
+        .context("cannot get gitlab project list from target instance")?;
+
+    let mut user_mapper = UserMapper::new(client, target, &config.users.mapping);
+
+    let mut results: Vec<ScopedProject> = vec![];
+
+    for source_project in source_projects {
+        let namespaces = match get_project_namespaces(config, &source_groups, &mut user_mapper, &source_project)? {
+            Some(namespaces) => namespaces,
+            None => continue
+        };
+
+        if !is_archived_project_allowed(config.migration.archived, source_project.archived) {
+            info!("archived project '{}' is out of migration scope, skip", source_project.path);
+            continue;
+        }
+
+        if config.migration.ignore_empty_repos {
+            let source_branches = get_project_branches(client, source, source_project.id)
+                .context("unable to get source project branches")?;
+
+            if !is_migration_allowed(true, source_branches.is_empty()) {
+                info!("empty project '{}' is out of migration scope, skip", source_project.path);
+                continue;
+            }
+        }
+
+        let placement = get_target_placement(client, config, &source_project, &namespaces, &target_projects)?;
+
+        let target_project = match &placement {
+            TargetPlacement::Migrated(target_project) => Some(*target_project.clone()),
+            _ => None
+        };
+
+        results.push(ScopedProject {
+            source_group: namespaces.source_group,
+            source_project,
+            target_project,
+            placement,
+        });
+    }
+
+    Ok(results)
+}
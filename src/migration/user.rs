@@ -0,0 +1,102 @@
+use std::collections::{BTreeMap, HashMap};
+
+use log::{debug, info};
+use reqwest::blocking::Client;
+
+use crate::config::InstanceConfig;
+use crate::migration::api::{get_json, get_optional_json};
+use crate::migration::domain::GitLabUser;
+
+/// Find target instance users for source usernames.
+///
+/// Usernames from `users.mapping` config section are replaced first,
+/// other users are matched by the same username. Lookups are cached.
+pub struct UserMapper<'a> {
+    client: &'a Client,
+    target: &'a InstanceConfig,
+    mapping: &'a BTreeMap<String, String>,
+    cache: HashMap<String, Option<GitLabUser>>,
+}
+
+impl<'a> UserMapper<'a> {
+    pub fn new(client: &'a Client, target: &'a InstanceConfig,
+               mapping: &'a BTreeMap<String, String>) -> UserMapper<'a> {
+        UserMapper { client, target, mapping, cache: HashMap::new() }
+    }
+
+    /// API: https://docs.gitlab.com/ee/api/users.html#list-users
+    pub fn find_target_user(&mut self, source_username: &str) -> anyhow::Result<Option<GitLabUser>> {
+        let username = self.mapping.get(source_username).map(|u| u.as_str()).unwrap_or(source_username);
+
+        if let Some(user) = self.cache.get(username) {
+            return Ok(user.clone())
+        }
+
+        debug!("find target user '{username}' for source user '{source_username}'");
+
+        let users: Vec<GitLabUser> = get_json(self.client, self.target, &format!("users?username={username}"))?;
+
+        let user = users.into_iter().find(|u| u.username == username);
+
+        if user.is_none() {
+            info!("user '{username}' wasn't found on target instance");
+        }
+
+        self.cache.insert(username.to_string(), user.clone());
+
+        Ok(user)
+    }
+
+    /// Target user ids for source usernames, users without target account are omitted.
+    pub fn find_target_user_ids(&mut self, source_usernames: &[&str]) -> anyhow::Result<Vec<u32>> {
+        let mut ids: Vec<u32> = vec![];
+
+        for source_username in source_usernames {
+            if let Some(user) = self.find_target_user(source_username)? {
+                ids.push(user.id);
+            }
+        }
+
+        Ok(ids)
+    }
+}
+
+/// API: https://docs.gitlab.com/ee/api/users.html#single-user
+pub fn get_user(client: &Client, instance: &InstanceConfig, user_id: u32) -> anyhow::Result<Option<GitLabUser>> {
+    get_optional_json(client, instance, &format!("users/{user_id}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use reqwest::blocking::ClientBuilder;
+
+    use crate::migration::user::UserMapper;
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::init_logging;
+
+    #[test]
+    fn users_should_be_matched_by_mapping_or_username() {
+        init_logging();
+
+        let target = FakeGitLab::start();
+        let john_id = target.add_user("john");
+        let jane_id = target.add_user("jane.doe");
+
+        let mut mapping = BTreeMap::new();
+        mapping.insert("jane".to_string(), "jane.doe".to_string());
+
+        let client = ClientBuilder::new().build().unwrap();
+        let instance = target.instance();
+
+        let mut mapper = UserMapper::new(&client, &instance, &mapping);
+
+        assert_eq!(vec![john_id, jane_id], mapper.find_target_user_ids(&["john", "jane", "bob"]).unwrap());
+
+        mapper.find_target_user("john").unwrap();
+
+        let user_requests = target.requests().into_iter().filter(|r| r.url.contains("/users?")).count();
+        assert_eq!(3, user_requests);
+    }
+}
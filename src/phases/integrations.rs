@@ -0,0 +1,289 @@
+use anyhow::Context;
+use log::info;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::config::AppConfig;
+use crate::migration::api::{get_all_pages, get_optional_all_pages, post_json};
+use crate::migration::domain::{GitLabGroup, GitLabProject};
+use crate::phases::PhaseReport;
+
+/// Webhook settings copied from source besides `*_events` flags
+const WEBHOOK_FIELDS: [&str; 5] = [
+    "url", "name", "description", "enable_ssl_verification", "push_events_branch_filter",
+];
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabDeployKey {
+    pub id: u32,
+    pub title: String,
+    pub key: String,
+
+    #[serde(default)]
+    pub can_push: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabDeployToken {
+    pub id: u32,
+    pub name: String,
+    pub username: String,
+    pub expires_at: Option<String>,
+
+    #[serde(default)]
+    pub scopes: Vec<String>,
+
+    #[serde(default)]
+    pub revoked: bool,
+
+    #[serde(default)]
+    pub expired: bool,
+}
+
+/// Copy project webhooks and deploy keys, list deploy tokens which have to be re-issued.
+pub fn migrate_project_integrations(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                                    target_project: &GitLabProject, dry_run: bool,
+                                    report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_path = format!("projects/{}", source_project.id);
+    let target_path = format!("projects/{}", target_project.id);
+
+    migrate_webhooks(client, config, &source_path, &target_path, dry_run, report)
+        .context("unable to migrate webhooks")?;
+
+    migrate_deploy_keys(client, config, &source_path, &target_path, dry_run, report)
+        .context("unable to migrate deploy keys")?;
+
+    report_deploy_tokens(client, config, &source_path, report)
+        .context("unable to get deploy tokens")
+}
+
+/// Copy group webhooks (Premium tier), list group deploy tokens which have to be re-issued.
+pub fn migrate_group_integrations(client: &Client, config: &AppConfig, source_group: &GitLabGroup,
+                                  target_group: &GitLabGroup, dry_run: bool,
+                                  report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_path = format!("groups/{}", source_group.id);
+    let target_path = format!("groups/{}", target_group.id);
+
+    migrate_webhooks(client, config, &source_path, &target_path, dry_run, report)
+        .context("unable to migrate webhooks")?;
+
+    report_deploy_tokens(client, config, &source_path, report)
+        .context("unable to get deploy tokens")
+}
+
+/// Webhooks are matched by url. Secret tokens can't be read with API, they are taken from
+/// `phases.integrations.webhook-tokens` config section.
+///
+/// API: https://docs.gitlab.com/ee/api/projects.html#hooks
+fn migrate_webhooks(client: &Client, config: &AppConfig, source_owner_path: &str, target_owner_path: &str,
+                    dry_run: bool, report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_hooks: Vec<Value> = match get_optional_all_pages(
+        client, &config.source, &format!("{source_owner_path}/hooks"))? {
+        Some(hooks) => hooks,
+        None => return Ok(())
+    };
+
+    if source_hooks.is_empty() {
+        return Ok(())
+    }
+
+    let target_hooks: Option<Vec<Value>> = get_optional_all_pages(
+        client, &config.target, &format!("{target_owner_path}/hooks"))?;
+
+    let target_hooks = match target_hooks {
+        Some(target_hooks) => target_hooks,
+        None => {
+            for source_hook in &source_hooks {
+                report.manual.push(format!("webhook '{}': webhooks aren't supported by target instance",
+                                           source_hook["url"].as_str().unwrap_or_default()));
+            }
+            return Ok(())
+        }
+    };
+
+    let webhook_tokens = &config.phases.integrations.webhook_tokens;
+
+    for source_hook in &source_hooks {
+        let url = source_hook["url"].as_str().unwrap_or_default();
+        let hook_name = format!("webhook '{url}'");
+
+        if target_hooks.iter().any(|th| th["url"] == url) {
+            continue;
+        }
+
+        let mut hook_settings = get_webhook_settings(source_hook);
+
+        match webhook_tokens.get(url) {
+            Some(token) => { hook_settings.insert("token".to_string(), json!(token)); }
+            None => report.manual.push(
+                format!("{hook_name}: secret token isn't readable, set it manually if hook uses it"))
+        }
+
+        if !dry_run {
+            let _: Value = post_json(client, &config.target, &format!("{target_owner_path}/hooks"),
+                                     &Value::Object(hook_settings))
+                .context(format!("unable to create {hook_name}"))?;
+
+            info!("{hook_name} has been created");
+        }
+
+        report.created.push(hook_name);
+    }
+
+    Ok(())
+}
+
+/// Deploy keys are added by public key, GitLab enables already existing instance key for project
+/// instead of creating a new one.
+///
+/// API: https://docs.gitlab.com/ee/api/deploy_keys.html#add-deploy-key-for-a-project
+fn migrate_deploy_keys(client: &Client, config: &AppConfig, source_owner_path: &str, target_owner_path: &str,
+                       dry_run: bool, report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_keys: Vec<GitLabDeployKey> = get_all_pages(
+        client, &config.source, &format!("{source_owner_path}/deploy_keys"))?;
+
+    if source_keys.is_empty() {
+        return Ok(())
+    }
+
+    let target_keys: Vec<GitLabDeployKey> = get_all_pages(
+        client, &config.target, &format!("{target_owner_path}/deploy_keys"))?;
+
+    for source_key in &source_keys {
+        let key_name = format!("deploy key '{}'", source_key.title);
+
+        if target_keys.iter().any(|tk| get_public_key_body(&tk.key) == get_public_key_body(&source_key.key)) {
+            continue;
+        }
+
+        if !dry_run {
+            let _: Value = post_json(client, &config.target, &format!("{target_owner_path}/deploy_keys"), &json!({
+                "title": source_key.title,
+                "key": source_key.key,
+                "can_push": source_key.can_push,
+            })).context(format!("unable to add {key_name}"))?;
+
+            info!("{key_name} has been added");
+        }
+
+        report.created.push(key_name);
+    }
+
+    Ok(())
+}
+
+/// Deploy token secrets can't be read with API, active tokens are reported for re-issue.
+///
+/// API: https://docs.gitlab.com/ee/api/deploy_tokens.html
+fn report_deploy_tokens(client: &Client, config: &AppConfig, source_owner_path: &str,
+                        report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_tokens: Vec<GitLabDeployToken> = get_optional_all_pages(
+        client, &config.source, &format!("{source_owner_path}/deploy_tokens"))?.unwrap_or_default();
+
+    for token in source_tokens.iter().filter(|t| !t.revoked && !t.expired) {
+        report.manual.push(format!("deploy token '{}' (username '{}', scopes: {}, expires: {}): has to be re-issued",
+                                   token.name, token.username, token.scopes.join(","),
+                                   token.expires_at.as_deref().unwrap_or("never")));
+    }
+
+    Ok(())
+}
+
+fn get_webhook_settings(hook: &Value) -> Map<String, Value> {
+    hook.as_object().cloned().unwrap_or_default().into_iter()
+        .filter(|(name, value)|
+            !value.is_null() && (name.ends_with("_events") || WEBHOOK_FIELDS.contains(&name.as_str())))
+        .collect()
+}
+
+/// Key type and body without comment
+fn get_public_key_body(key: &str) -> String {
+    key.split_whitespace().take(2).collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::migration::migrate_gitlab_instance;
+    use crate::phases::{Phase, run_phase_on_migrated_projects};
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    #[test]
+    fn webhooks_and_deploy_keys_should_be_migrated_and_tokens_reported() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        source.add_project_resource(api_id, "hooks", json!({
+            "id": 1, "project_id": api_id, "url": "https://jenkins.company.com/project/api",
+            "push_events": true, "merge_requests_events": true, "tag_push_events": false,
+            "enable_ssl_verification": false, "push_events_branch_filter": null,
+            "created_at": "2022-10-01T10:00:00Z"
+        }));
+        source.add_project_resource(api_id, "hooks", json!({
+            "id": 2, "project_id": api_id, "url": "https://chat.company.com/hook",
+            "push_events": false, "note_events": true, "enable_ssl_verification": true
+        }));
+        source.add_project_resource(api_id, "deploy_keys", json!({
+            "id": 5, "title": "deploy server", "key": "ssh-ed25519 AAAAC3Nza deploy@server", "can_push": true
+        }));
+        source.add_project_resource(api_id, "deploy_tokens", json!({
+            "id": 7, "name": "k8s", "username": "gitlab+deploy-token-7", "expires_at": null,
+            "scopes": ["read_registry"], "revoked": false, "expired": false
+        }));
+        source.add_project_resource(api_id, "deploy_tokens", json!({
+            "id": 8, "name": "old", "username": "gitlab+deploy-token-8", "scopes": ["read_repository"],
+            "revoked": true, "expired": false
+        }));
+        source.add_resource(&format!("groups/{group_id}/hooks"), json!({
+            "id": 3, "group_id": group_id, "url": "https://audit.company.com/hook", "push_events": true
+        }));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.phases.integrations.enabled = true;
+        config.phases.integrations.webhook_tokens.insert(
+            "https://jenkins.company.com/project/api".to_string(), "jenkins-secret".to_string());
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        let group_report = report.phases.iter().find(|r| r.path == "backend").unwrap();
+        assert_eq!(vec!["webhook 'https://audit.company.com/hook'"], group_report.created);
+
+        let project_report = report.phases.iter().find(|r| r.path == "backend/api").unwrap();
+        assert_eq!(vec!["webhook 'https://jenkins.company.com/project/api'", "webhook 'https://chat.company.com/hook'",
+                        "deploy key 'deploy server'"], project_report.created);
+        assert_eq!(vec![
+            "webhook 'https://chat.company.com/hook': secret token isn't readable, set it manually if hook uses it",
+            "deploy token 'k8s' (username 'gitlab+deploy-token-7', scopes: read_registry, expires: never): \
+            has to be re-issued"
+        ], project_report.manual);
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+
+        let hooks = target.project_resources(target_api_id, "hooks");
+        assert_eq!("jenkins-secret", hooks[0]["token"]);
+        assert_eq!(true, hooks[0]["merge_requests_events"]);
+        assert_eq!(false, hooks[0]["enable_ssl_verification"]);
+        assert!(hooks[0].get("project_id").is_none());
+        assert!(hooks[0].get("push_events_branch_filter").is_none());
+        assert!(hooks[1].get("token").is_none());
+
+        let keys = target.project_resources(target_api_id, "deploy_keys");
+        assert_eq!(true, keys[0]["can_push"]);
+
+        let client = ClientBuilder::new().build().unwrap();
+        let reports = run_phase_on_migrated_projects(&client, &config, Phase::Integrations, false).unwrap();
+
+        assert!(reports.iter().all(|r| r.created.is_empty()));
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use log::info;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use crate::config::AppConfig;
+use crate::migration::api::{get_file, post_file};
+use crate::migration::domain::GitLabProject;
+use crate::phases::PhaseReport;
+
+const UPLOADS_PATH: &str = "/uploads/";
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabUpload {
+    /// Markdown url `/uploads/{secret}/{filename}`
+    pub url: String,
+}
+
+/// Rewrites markdown of source project for target project:
+/// - files uploaded to source project and referenced in text are copied to target project, every file once
+/// - absolute urls of source project and source instance point to target project and target instance
+pub struct MarkdownRewriter<'a> {
+    client: &'a Client,
+    config: &'a AppConfig,
+    source_project: &'a GitLabProject,
+    target_project: &'a GitLabProject,
+
+    /// Source upload url -> target upload url
+    copied_uploads: HashMap<String, String>,
+}
+
+impl<'a> MarkdownRewriter<'a> {
+    pub fn new(client: &'a Client, config: &'a AppConfig, source_project: &'a GitLabProject,
+               target_project: &'a GitLabProject) -> MarkdownRewriter<'a> {
+        MarkdownRewriter { client, config, source_project, target_project, copied_uploads: HashMap::new() }
+    }
+
+    /// Returns text with links pointing to target instance.
+    /// Uploads which can't be downloaded are reported, their links are kept.
+    pub fn rewrite(&mut self, text: &str, report: &mut PhaseReport) -> anyhow::Result<String> {
+        let result = self.copy_uploads(text, report)?;
+
+        let source_project_url = get_project_url(&self.config.source.public_url, self.source_project);
+        let target_project_url = get_project_url(&self.config.target.public_url, self.target_project);
+
+        let result = replace_url_prefix(&result, &source_project_url, &target_project_url);
+
+        Ok(replace_url_prefix(&result, &self.config.source.public_url, &self.config.target.public_url))
+    }
+
+    fn copy_uploads(&mut self, text: &str, report: &mut PhaseReport) -> anyhow::Result<String> {
+        let mut result = text.to_string();
+
+        for upload_url in find_upload_urls(text) {
+            let target_upload_url = match self.copied_uploads.get(&upload_url) {
+                Some(target_upload_url) => target_upload_url.to_string(),
+                None => match self.copy_upload(&upload_url)? {
+                    Some(target_upload_url) => {
+                        self.copied_uploads.insert(upload_url.to_string(), target_upload_url.to_string());
+                        target_upload_url
+                    }
+                    None => {
+                        report.manual.push(format!("upload '{upload_url}' wasn't found on source instance"));
+                        continue;
+                    }
+                }
+            };
+
+            result = result
+                .replace(&format!("{}{upload_url}", get_project_url(&self.config.source.public_url, self.source_project)),
+                         &format!("{}{target_upload_url}", get_project_url(&self.config.target.public_url, self.target_project)))
+                .replace(&upload_url, &target_upload_url);
+        }
+
+        Ok(result)
+    }
+
+    /// Uploads are downloaded with API available since GitLab 17.x, older instances serve them
+    /// from project web url.
+    ///
+    /// API: https://docs.gitlab.com/ee/api/project_markdown_uploads.html
+    fn copy_upload(&self, upload_url: &str) -> anyhow::Result<Option<String>> {
+        let source = &self.config.source;
+
+        let mut content = get_file(self.client, source, &format!("{}/api/v4/projects/{}{upload_url}",
+                                                                 source.public_url, self.source_project.id))?;
+
+        if content.is_none() {
+            content = get_file(self.client, source,
+                               &format!("{}{upload_url}", get_project_url(&source.public_url, self.source_project)))?;
+        }
+
+        match content {
+            Some(content) => {
+                let file_name = upload_url.rsplit('/').next().unwrap_or_default();
+
+                let upload: GitLabUpload = post_file(
+                    self.client, &self.config.target, &format!("projects/{}/uploads", self.target_project.id),
+                    file_name, content).context(format!("unable to upload file '{file_name}'"))?;
+
+                info!("upload '{upload_url}' has been copied to '{}'", upload.url);
+
+                Ok(Some(upload.url))
+            }
+            None => Ok(None)
+        }
+    }
+}
+
+/// Markdown upload urls `/uploads/{secret}/{filename}` found in text.
+pub fn find_upload_urls(text: &str) -> Vec<String> {
+    let mut results: Vec<String> = vec![];
+
+    for (index, _) in text.match_indices(UPLOADS_PATH) {
+        let upload_path = &text[index + UPLOADS_PATH.len()..];
+
+        let secret: String = upload_path.chars().take_while(|c| c.is_ascii_hexdigit()).collect();
+
+        if secret.is_empty() || !upload_path[secret.len()..].starts_with('/') {
+            continue;
+        }
+
+        let filename: String = upload_path[secret.len() + 1..].chars()
+            .take_while(|c| !c.is_whitespace() && !['(', ')', '[', ']', '"', '\'', '<', '>', '?', '#'].contains(c))
+            .collect();
+
+        // punctuation after url in text
+        let filename = filename.trim_end_matches(&[',', '.', ';', ':', '!'][..]);
+
+        if filename.is_empty() {
+            continue;
+        }
+
+        let upload_url = format!("{UPLOADS_PATH}{secret}/{filename}");
+
+        if !results.contains(&upload_url) {
+            results.push(upload_url);
+        }
+    }
+
+    results
+}
+
+fn get_project_url(public_url: &str, project: &GitLabProject) -> String {
+    format!("{}/{}/{}", public_url.trim_end_matches('/'), project.namespace.full_path, project.path)
+}
+
+/// Replace url prefix only if it's followed by path, query or end of url,
+/// e.g. `https://gitlab.company.com` isn't replaced in `https://gitlab.company.com.backup`.
+fn replace_url_prefix(text: &str, source_url: &str, target_url: &str) -> String {
+    let source_url = source_url.trim_end_matches('/');
+    let target_url = target_url.trim_end_matches('/');
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(index) = rest.find(source_url) {
+        let after = &rest[index + source_url.len()..];
+
+        result.push_str(&rest[..index]);
+
+        if after.is_empty() || after.starts_with(|c: char| !c.is_alphanumeric() && !['.', '-', '_'].contains(&c)) {
+            result.push_str(target_url);
+        } else {
+            result.push_str(source_url);
+        }
+
+        rest = after;
+    }
+
+    result.push_str(rest);
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::phases::markdown::{find_upload_urls, replace_url_prefix};
+
+    #[test]
+    fn upload_urls_should_be_found_in_markdown() {
+        let text = "![screen](/uploads/0a1b2c/screen.png) and [log](https://gitlab.company.com/backend/api\
+                    /uploads/ff00/build.log), again /uploads/0a1b2c/screen.png, not /uploads/xyz/file.txt";
+
+        assert_eq!(vec!["/uploads/0a1b2c/screen.png", "/uploads/ff00/build.log"], find_upload_urls(text));
+    }
+
+    #[test]
+    fn url_prefix_should_be_replaced_only_for_whole_host() {
+        let text = "see https://gitlab.company.com/backend/api/-/issues/1, https://gitlab.company.com \
+                    and https://gitlab.company.com.backup/x";
+
+        assert_eq!("see https://git.new.com/backend/api/-/issues/1, https://git.new.com \
+                    and https://gitlab.company.com.backup/x",
+                   replace_url_prefix(text, "https://gitlab.company.com/", "https://git.new.com"));
+    }
+}
@@ -0,0 +1,507 @@
+use anyhow::Context;
+use log::info;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config::AppConfig;
+use crate::migration::api::{get_all_pages, post_json, put_json};
+use crate::migration::domain::{GitLabProject, GitLabUser};
+use crate::migration::project::get_project_branches;
+use crate::migration::user::UserMapper;
+use crate::phases::markdown::MarkdownRewriter;
+use crate::phases::PhaseReport;
+
+const DRAFT_PREFIX: &str = "Draft: ";
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabMergeRequest {
+    pub iid: u32,
+    pub title: String,
+    pub description: Option<String>,
+    pub state: String,
+    pub source_branch: String,
+    pub target_branch: String,
+
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    #[serde(default)]
+    pub draft: bool,
+
+    /// Deprecated predecessor of `draft`
+    #[serde(default)]
+    pub work_in_progress: bool,
+
+    pub author: Option<GitLabUser>,
+
+    #[serde(default)]
+    pub assignees: Vec<GitLabUser>,
+
+    #[serde(default)]
+    pub reviewers: Vec<GitLabUser>,
+
+    pub created_at: Option<String>,
+    pub web_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabDiscussion {
+    pub id: String,
+
+    #[serde(default)]
+    pub notes: Vec<GitLabNote>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabNote {
+    pub id: u32,
+    pub body: String,
+    pub author: Option<GitLabUser>,
+    pub created_at: Option<String>,
+
+    /// Notes generated by GitLab, e.g. "added 1 commit"
+    #[serde(default)]
+    pub system: bool,
+}
+
+/// Recreate source project merge requests on target project, states are taken from
+/// `phases.merge-requests.states` config.
+///
+/// Notes are copied as plain comments with original author and date in text,
+/// diff positions of code review comments are not preserved.
+/// Descriptions and notes are rewritten with `MarkdownRewriter`, referenced uploads are copied.
+/// Merge requests already existing on target (same branches and title) aren't created again, only their
+/// missing notes are copied, so phase can be executed again after an error. Merge can't be recreated,
+/// merged merge requests are closed and reported. Nothing is created in `dry_run` mode.
+///
+/// API: https://docs.gitlab.com/ee/api/merge_requests.html
+pub fn migrate_merge_requests(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                              target_project: &GitLabProject, dry_run: bool,
+                              report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source = &config.source;
+    let target = &config.target;
+
+    let mut source_merge_requests: Vec<GitLabMergeRequest> = vec![];
+
+    for state in &config.phases.merge_requests.states {
+        let mut merge_requests: Vec<GitLabMergeRequest> = get_all_pages(
+            client, source, &format!("projects/{}/merge_requests?state={state}", source_project.id))
+            .context("unable to get merge requests from source project")?;
+
+        source_merge_requests.append(&mut merge_requests);
+    }
+
+    source_merge_requests.sort_by_key(|mr| mr.iid);
+
+    if source_merge_requests.is_empty() {
+        return Ok(())
+    }
+
+    let target_branches = get_project_branches(client, target, target_project.id)
+        .context("unable to get target project branches")?;
+
+    let target_merge_requests: Vec<GitLabMergeRequest> = get_all_pages(
+        client, target, &format!("projects/{}/merge_requests?state=all", target_project.id))
+        .context("unable to get merge requests from target project")?;
+
+    let mut user_mapper = UserMapper::new(client, target, &config.users.mapping);
+
+    let mut markdown_rewriter = MarkdownRewriter::new(client, config, source_project, target_project);
+
+    for merge_request in &source_merge_requests {
+        let merge_request_name = format!("!{} '{}'", merge_request.iid, merge_request.title);
+
+        let missing_branches: Vec<&String> = [&merge_request.source_branch, &merge_request.target_branch]
+            .into_iter().filter(|b| !target_branches.contains(b)).collect();
+
+        if !missing_branches.is_empty() {
+            info!("merge request {merge_request_name}: branch '{}' doesn't exist, skip", missing_branches[0]);
+            report.skipped.push(format!("{merge_request_name}: branch '{}' doesn't exist", missing_branches[0]));
+            continue;
+        }
+
+        let title = get_target_title(merge_request);
+
+        let existing_merge_request = target_merge_requests.iter().find(|tmr|
+            tmr.title == title && tmr.source_branch == merge_request.source_branch &&
+                tmr.target_branch == merge_request.target_branch);
+
+        if let Some(existing_merge_request) = existing_merge_request {
+            // discussions could be copied partially by previous run
+            let notes_count = if dry_run {
+                0
+            } else {
+                copy_discussions(
+                    client, config,
+                    &format!("projects/{}/merge_requests/{}/discussions", source_project.id, merge_request.iid),
+                    &format!("projects/{}/merge_requests/{}/discussions", target_project.id,
+                             existing_merge_request.iid),
+                    &mut markdown_rewriter, report)
+                    .context(format!("unable to copy discussions of merge request {merge_request_name}"))?
+            };
+
+            if notes_count > 0 {
+                info!("merge request {merge_request_name}: {notes_count} missing notes have been copied");
+                report.updated.push(format!("{merge_request_name} -> !{} ({notes_count} missing notes)",
+                                            existing_merge_request.iid));
+            } else {
+                info!("merge request {merge_request_name} already exists on target, skip");
+                report.skipped.push(format!("{merge_request_name}: already exists"));
+            }
+
+            continue;
+        }
+
+        if dry_run {
+            report.created.push(merge_request_name);
+            continue;
+        }
+
+        let description = markdown_rewriter.rewrite(
+            merge_request.description.as_deref().unwrap_or_default(), report)?;
+
+        let assignees: Vec<&str> = merge_request.assignees.iter().map(|u| u.username.as_str()).collect();
+        let reviewers: Vec<&str> = merge_request.reviewers.iter().map(|u| u.username.as_str()).collect();
+
+        let new_merge_request: GitLabMergeRequest = post_json(
+            client, target, &format!("projects/{}/merge_requests", target_project.id),
+            &json!({
+                "source_branch": merge_request.source_branch,
+                "target_branch": merge_request.target_branch,
+                "title": title,
+                "description": get_target_description(merge_request, &description),
+                "labels": merge_request.labels.join(","),
+                "assignee_ids": user_mapper.find_target_user_ids(&assignees)?,
+                "reviewer_ids": user_mapper.find_target_user_ids(&reviewers)?,
+            })).context(format!("unable to create merge request {merge_request_name}"))?;
+
+        if merge_request.state != "opened" {
+            let _: Value = put_json(
+                client, target,
+                &format!("projects/{}/merge_requests/{}", target_project.id, new_merge_request.iid),
+                &json!({"state_event": "close"}))
+                .context(format!("unable to close merge request {merge_request_name}"))?;
+
+            if merge_request.state == "merged" {
+                report.manual.push(format!("{merge_request_name}: merged on source, created as closed \
+                                            merge request !{}", new_merge_request.iid));
+            }
+        }
+
+        let notes_count = copy_discussions(
+            client, config,
+            &format!("projects/{}/merge_requests/{}/discussions", source_project.id, merge_request.iid),
+            &format!("projects/{}/merge_requests/{}/discussions", target_project.id, new_merge_request.iid),
+            &mut markdown_rewriter, report)
+            .context(format!("unable to copy discussions of merge request {merge_request_name}"))?;
+
+        info!("merge request {merge_request_name} has been created as !{}", new_merge_request.iid);
+
+        report.created.push(format!("{merge_request_name} -> !{} ({notes_count} notes)", new_merge_request.iid));
+    }
+
+    Ok(())
+}
+
+/// Copy discussion threads without system notes, returns count of copied notes.
+///
+/// Notes already copied to target are recognized by their author and date header and skipped,
+/// so discussions copied partially are completed.
+///
+/// API: https://docs.gitlab.com/ee/api/discussions.html#merge-requests
+fn copy_discussions(client: &Client, config: &AppConfig, source_discussions_path: &str,
+                    target_discussions_path: &str, markdown_rewriter: &mut MarkdownRewriter,
+                    report: &mut PhaseReport) -> anyhow::Result<usize> {
+    let discussions: Vec<GitLabDiscussion> = get_all_pages(client, &config.source, source_discussions_path)?;
+    let target_discussions: Vec<GitLabDiscussion> = get_all_pages(client, &config.target, target_discussions_path)?;
+
+    let mut notes_count = 0;
+
+    for discussion in &discussions {
+        let notes: Vec<&GitLabNote> = discussion.notes.iter().filter(|n| !n.system).collect();
+
+        if let Some((first_note, replies)) = notes.split_first() {
+            let target_discussion = target_discussions.iter()
+                .find(|td| td.notes.first().is_some_and(|n| n.body.starts_with(&get_note_header(first_note))));
+
+            let (discussion_id, copied_notes) = match target_discussion {
+                Some(target_discussion) => (target_discussion.id.to_string(), target_discussion.notes.as_slice()),
+                None => {
+                    let new_discussion: GitLabDiscussion = post_json(
+                        client, &config.target, target_discussions_path,
+                        &json!({"body": get_note_body(first_note, markdown_rewriter, report)?}))?;
+
+                    notes_count += 1;
+
+                    (new_discussion.id, [].as_slice())
+                }
+            };
+
+            for reply in replies {
+                if copied_notes.iter().any(|n| n.body.starts_with(&get_note_header(reply))) {
+                    continue;
+                }
+
+                let _: Value = post_json(
+                    client, &config.target,
+                    &format!("{target_discussions_path}/{discussion_id}/notes"),
+                    &json!({"body": get_note_body(reply, markdown_rewriter, report)?}))?;
+
+                notes_count += 1;
+            }
+        }
+    }
+
+    Ok(notes_count)
+}
+
+fn get_target_title(merge_request: &GitLabMergeRequest) -> String {
+    let is_draft = merge_request.draft || merge_request.work_in_progress;
+
+    if is_draft && !merge_request.title.starts_with("Draft:") && !merge_request.title.starts_with("WIP:") {
+        format!("{DRAFT_PREFIX}{}", merge_request.title)
+
+    } else {
+        merge_request.title.to_string()
+    }
+}
+
+fn get_target_description(merge_request: &GitLabMergeRequest, description: &str) -> String {
+    let mut header = format!("*Migrated merge request, created by {} on {}",
+                             get_author_name(&merge_request.author),
+                             merge_request.created_at.as_deref().unwrap_or("unknown date"));
+
+    if let Some(web_url) = &merge_request.web_url {
+        header.push_str(&format!(", original: {web_url}"));
+    }
+
+    if merge_request.state != "opened" {
+        header.push_str(&format!(", original state: {}", merge_request.state));
+    }
+
+    header.push('*');
+
+    if description.is_empty() {
+        header
+
+    } else {
+        format!("{header}\n\n{description}")
+    }
+}
+
+fn get_note_body(note: &GitLabNote, markdown_rewriter: &mut MarkdownRewriter,
+                 report: &mut PhaseReport) -> anyhow::Result<String> {
+    Ok(format!("{}\n\n{}", get_note_header(note), markdown_rewriter.rewrite(&note.body, report)?))
+}
+
+/// Original author and date of note, e.g. `**@john** commented on 2022-10-02T10:00:00Z:`
+fn get_note_header(note: &GitLabNote) -> String {
+    format!("**{}** commented on {}:", get_author_name(&note.author),
+            note.created_at.as_deref().unwrap_or("unknown date"))
+}
+
+fn get_author_name(author: &Option<GitLabUser>) -> String {
+    match author {
+        Some(author) => format!("@{}", author.username),
+        None => "unknown author".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::migration::migrate_gitlab_instance;
+    use crate::phases::{Phase, run_phase_on_migrated_projects};
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    fn get_merge_request(iid: u32, title: &str, state: &str, source_branch: &str) -> serde_json::Value {
+        json!({
+            "id": 1000 + iid, "iid": iid, "title": title, "description": "Some changes", "state": state,
+            "source_branch": source_branch, "target_branch": "main", "labels": ["backend", "bug"],
+            "draft": false, "author": {"id": 50, "username": "john", "name": "John"},
+            "assignees": [{"id": 50, "username": "john", "name": "John"}],
+            "reviewers": [{"id": 51, "username": "jane", "name": "Jane"}],
+            "created_at": "2022-10-01T10:00:00Z",
+            "web_url": format!("https://source.company.com/backend/api/-/merge_requests/{iid}")
+        })
+    }
+
+    #[test]
+    fn merge_requests_should_be_migrated_with_discussions() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main", "feature", "fix", "hotfix"]);
+
+        source.add_project_resource(api_id, "merge_requests", get_merge_request(1, "Add feature", "opened", "feature"));
+        source.add_project_resource(api_id, "merge_requests", get_merge_request(2, "Old fix", "closed", "fix"));
+        source.add_project_resource(api_id, "merge_requests", get_merge_request(3, "Removed", "opened", "gone"));
+        source.add_project_resource(api_id, "merge_requests", get_merge_request(4, "Hotfix", "merged", "hotfix"));
+
+        source.add_resource(&format!("projects/{api_id}/merge_requests/1/discussions"), json!({
+            "id": "abc", "notes": [
+                {"id": 1, "body": "Looks good", "system": false, "created_at": "2022-10-02T10:00:00Z",
+                 "author": {"id": 51, "username": "jane", "name": "Jane"}},
+                {"id": 2, "body": "Thanks", "system": false, "created_at": "2022-10-02T11:00:00Z",
+                 "author": {"id": 50, "username": "john", "name": "John"}}
+            ]
+        }));
+        source.add_resource(&format!("projects/{api_id}/merge_requests/1/discussions"), json!({
+            "id": "def", "notes": [
+                {"id": 3, "body": "added 1 commit", "system": true,
+                 "author": {"id": 50, "username": "john", "name": "John"}}
+            ]
+        }));
+
+        let target = FakeGitLab::start();
+        let john_id = target.add_user("john");
+        let jane_id = target.add_user("jane.doe");
+
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.phases.merge_requests.enabled = true;
+        config.phases.merge_requests.states = vec!["opened".to_string(), "closed".to_string(), "merged".to_string()];
+        config.users.mapping.insert("jane".to_string(), "jane.doe".to_string());
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        let phase_report = report.phases.first().unwrap();
+        assert_eq!(Phase::MergeRequests, phase_report.phase);
+        assert_eq!(3, phase_report.created.len());
+        assert_eq!(vec!["!3 'Removed': branch 'gone' doesn't exist"], phase_report.skipped);
+        assert_eq!(vec!["!4 'Hotfix': merged on source, created as closed merge request !3"], phase_report.manual);
+        assert!(phase_report.error.is_none());
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+        let merge_requests = target.project_resources(target_api_id, "merge_requests");
+        assert_eq!(3, merge_requests.len());
+
+        let feature = &merge_requests[0];
+        assert_eq!("Add feature", feature["title"]);
+        assert_eq!("feature", feature["source_branch"]);
+        assert_eq!(json!(["backend", "bug"]), feature["labels"]);
+        assert_eq!(json!([john_id]), feature["assignee_ids"]);
+        assert_eq!(json!([jane_id]), feature["reviewer_ids"]);
+        assert_eq!("opened", feature["state"]);
+
+        let description = feature["description"].as_str().unwrap();
+        assert!(description.contains("created by @john"), "{description}");
+        assert!(description.ends_with("Some changes"));
+
+        assert_eq!("closed", merge_requests[1]["state"]);
+        assert_eq!("closed", merge_requests[2]["state"]);
+
+        let discussions = target.project_resources(target_api_id, "merge_requests/1/discussions");
+        assert_eq!(1, discussions.len());
+        assert!(discussions[0]["body"].as_str().unwrap().starts_with("**@jane** commented on"));
+
+        let discussion_id = discussions[0]["id"].as_str().unwrap();
+        let replies = target.project_resources(
+            target_api_id, &format!("merge_requests/1/discussions/{discussion_id}/notes"));
+        assert_eq!(1, replies.len());
+        assert!(replies[0]["body"].as_str().unwrap().ends_with("Thanks"));
+
+        // notes missing on target, e.g. after error, are copied to existing merge request
+        source.add_resource(&format!("projects/{api_id}/merge_requests/1/discussions"), json!({
+            "id": "ghi", "notes": [
+                {"id": 4, "body": "One more thing", "system": false, "created_at": "2022-10-03T10:00:00Z",
+                 "author": {"id": 51, "username": "jane", "name": "Jane"}}
+            ]
+        }));
+
+        let client = ClientBuilder::new().build().unwrap();
+        let reports = run_phase_on_migrated_projects(&client, &config, Phase::MergeRequests, false).unwrap();
+
+        let phase_report = reports.first().unwrap();
+        assert!(phase_report.created.is_empty());
+        assert_eq!(vec!["!1 'Add feature' -> !1 (1 missing notes)"], phase_report.updated);
+        assert_eq!(3, phase_report.skipped.len());
+        assert_eq!(3, target.project_resources(target_api_id, "merge_requests").len());
+        assert_eq!(2, target.project_resources(target_api_id, "merge_requests/1/discussions").len());
+        assert_eq!(1, target.project_resources(
+            target_api_id, &format!("merge_requests/1/discussions/{discussion_id}/notes")).len());
+    }
+
+    #[test]
+    fn merge_request_uploads_should_be_copied_and_links_rewritten() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main", "feature"]);
+
+        let screenshot_url = source.add_upload(api_id, "screen.png", "png-content");
+        let log_url = source.add_upload(api_id, "build.log", "log-content");
+        let source_url = source.instance().public_url;
+
+        let mut merge_request = get_merge_request(1, "Add feature", "opened", "feature");
+        merge_request["description"] = json!(format!(
+            "![screen]({screenshot_url}), see {source_url}/backend/api/-/issues/5, lost /uploads/0f0f/old.txt"));
+        source.add_project_resource(api_id, "merge_requests", merge_request);
+
+        source.add_resource(&format!("projects/{api_id}/merge_requests/1/discussions"), json!({
+            "id": "abc", "notes": [
+                {"id": 1, "body": format!("[log]({source_url}/backend/api{log_url})"), "system": false,
+                 "author": {"id": 51, "username": "jane", "name": "Jane"}}
+            ]
+        }));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.phases.merge_requests.enabled = true;
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        let phase_report = report.phases.first().unwrap();
+        assert_eq!(vec!["upload '/uploads/0f0f/old.txt' wasn't found on source instance"], phase_report.manual);
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+        let uploads = target.uploads(target_api_id);
+        assert_eq!(2, uploads.len());
+
+        let target_screenshot_url = uploads.iter().find(|(_, c)| *c == "png-content").unwrap().0;
+        let target_log_url = uploads.iter().find(|(_, c)| *c == "log-content").unwrap().0;
+        let target_url = target.instance().public_url;
+
+        let merge_requests = target.project_resources(target_api_id, "merge_requests");
+        let description = merge_requests[0]["description"].as_str().unwrap();
+        assert!(description.ends_with(&format!(
+            "![screen]({target_screenshot_url}), see {target_url}/backend/api/-/issues/5, lost /uploads/0f0f/old.txt")),
+                "{description}");
+
+        let discussions = target.project_resources(target_api_id, "merge_requests/1/discussions");
+        let note = discussions[0]["body"].as_str().unwrap();
+        assert!(note.ends_with(&format!("[log]({target_url}/backend/api{target_log_url})")), "{note}");
+    }
+
+    #[test]
+    fn merge_requests_should_be_skipped_if_phase_is_disabled() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main", "feature"]);
+        source.add_project_resource(api_id, "merge_requests", get_merge_request(1, "Add feature", "opened", "feature"));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        assert!(report.phases.is_empty());
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+        assert!(target.project_resources(target_api_id, "merge_requests").is_empty());
+    }
+}
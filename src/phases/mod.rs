@@ -0,0 +1,300 @@
+use std::time::Instant;
+
+use anyhow::Context;
+use log::{error, info};
+use reqwest::blocking::Client;
+
+use crate::config::AppConfig;
+use crate::logging::{get_error_class, log_field, LOG_FIELD_DURATION_MS, LOG_FIELD_ERROR_CLASS, LOG_FIELD_PHASE,
+                     LOG_FIELD_PROJECT};
+use crate::migration::domain::{GitLabGroup, GitLabProject};
+use crate::migration::group::get_all_groups;
+use crate::migration::scope::get_scoped_projects;
+use crate::phases::integrations::{migrate_group_integrations, migrate_project_integrations};
+use crate::phases::merge_requests::migrate_merge_requests;
+use crate::phases::packages::migrate_packages;
+use crate::phases::protections::migrate_protections;
+use crate::phases::registry::migrate_registry;
+use crate::phases::releases::migrate_releases;
+use crate::phases::snippets::{migrate_personal_snippets, migrate_project_snippets};
+use crate::phases::variables::{migrate_group_variables, migrate_project_variables};
+
+pub mod integrations;
+pub mod markdown;
+pub mod merge_requests;
+pub mod packages;
+pub mod protections;
+pub mod registry;
+pub mod releases;
+pub mod snippets;
+pub mod variables;
+
+/// Optional migration step executed for a project after its repository has been pushed to target instance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    MergeRequests,
+    CiVariables,
+    Protections,
+    Integrations,
+    Releases,
+    Snippets,
+    Registry,
+    Packages,
+}
+
+pub const PHASES: [Phase; 8] = [
+    Phase::Protections, Phase::Releases, Phase::Registry, Phase::Packages, Phase::MergeRequests, Phase::Snippets,
+    Phase::CiVariables, Phase::Integrations,
+];
+
+impl Phase {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Phase::MergeRequests => "merge-requests",
+            Phase::CiVariables => "ci-variables",
+            Phase::Protections => "protections",
+            Phase::Integrations => "integrations",
+            Phase::Releases => "releases",
+            Phase::Snippets => "snippets",
+            Phase::Registry => "registry",
+            Phase::Packages => "packages",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Phase> {
+        PHASES.iter().find(|p| p.get_name() == name).copied()
+    }
+
+    pub fn is_enabled(&self, config: &AppConfig) -> bool {
+        match self {
+            Phase::MergeRequests => config.phases.merge_requests.enabled,
+            Phase::CiVariables => config.phases.ci_variables.enabled,
+            Phase::Protections => config.phases.protections.enabled,
+            Phase::Integrations => config.phases.integrations.enabled,
+            Phase::Releases => config.phases.releases.enabled,
+            Phase::Snippets => config.phases.snippets.enabled,
+            Phase::Registry => config.phases.registry.enabled,
+            Phase::Packages => config.phases.packages.enabled,
+        }
+    }
+
+    /// Phase has to be executed for groups too
+    pub fn has_group_level(&self) -> bool {
+        matches!(self, Phase::CiVariables | Phase::Integrations)
+    }
+
+    /// Phase has to be executed once for whole instance too, e.g. for personal snippets
+    pub fn has_instance_level(&self, config: &AppConfig) -> bool {
+        matches!(self, Phase::Snippets) && config.phases.snippets.personal
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseReport {
+    pub phase: Phase,
+
+    /// Project or group full path, source instance url for instance level phases
+    pub path: String,
+
+    /// Nothing has been changed on target instance, items are planned changes
+    pub dry_run: bool,
+
+    /// Items created on target instance
+    pub created: Vec<String>,
+
+    /// Items changed on target instance
+    pub updated: Vec<String>,
+
+    /// Items which weren't migrated, with reason
+    pub skipped: Vec<String>,
+
+    /// Items which can't be reproduced automatically and have to be set up on target manually
+    pub manual: Vec<String>,
+
+    /// Phase has been interrupted, items created before are kept
+    pub error: Option<String>,
+}
+
+impl PhaseReport {
+    pub fn new(phase: Phase, path: &str, dry_run: bool) -> PhaseReport {
+        PhaseReport {
+            phase,
+            path: path.to_string(),
+            dry_run,
+            created: vec![],
+            updated: vec![],
+            skipped: vec![],
+            manual: vec![],
+            error: None,
+        }
+    }
+}
+
+/// Execute phases enabled in config for migrated project. Phase errors don't interrupt migration.
+pub fn run_enabled_phases(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                          target_project: &GitLabProject, project_path: &str) -> Vec<PhaseReport> {
+    PHASES.iter()
+        .filter(|phase| phase.is_enabled(config))
+        .map(|phase| run_project_phase(client, config, *phase, source_project, target_project,
+                                       project_path, false))
+        .collect()
+}
+
+/// Execute group level phases enabled in config for group existing on both instances.
+pub fn run_enabled_group_phases(client: &Client, config: &AppConfig, source_group: &GitLabGroup,
+                                target_group: &GitLabGroup) -> Vec<PhaseReport> {
+    PHASES.iter()
+        .filter(|phase| phase.is_enabled(config) && phase.has_group_level())
+        .map(|phase| run_group_phase(client, config, *phase, source_group, target_group, false))
+        .collect()
+}
+
+/// Execute instance level phases enabled in config, after all projects have been migrated.
+pub fn run_enabled_instance_phases(client: &Client, config: &AppConfig) -> Vec<PhaseReport> {
+    PHASES.iter()
+        .filter(|phase| phase.is_enabled(config) && phase.has_instance_level(config))
+        .map(|phase| run_instance_phase(client, config, *phase, false))
+        .collect()
+}
+
+/// Execute phase for every already migrated group and project, regardless of `phases` config section.
+pub fn run_phase_on_migrated_projects(client: &Client, config: &AppConfig, phase: Phase,
+                                      dry_run: bool) -> anyhow::Result<Vec<PhaseReport>> {
+    info!("run phase '{}' for migrated projects (dry-run: {dry_run})..", phase.get_name());
+
+    let mut results: Vec<PhaseReport> = vec![];
+
+    if phase.has_group_level() {
+        let source_groups = get_all_groups(client, &config.source)
+            .context("unable to get gitlab groups from source instance")?;
+
+        let target_groups = get_all_groups(client, &config.target)
+            .context("unable to get gitlab groups from target instance")?;
+
+        for source_group in &source_groups {
+            match target_groups.iter().find(|tg| tg.full_path == source_group.full_path) {
+                Some(target_group) =>
+                    results.push(run_group_phase(client, config, phase, source_group, target_group, dry_run)),
+                None => info!("group '{}' wasn't migrated yet, skip", source_group.full_path)
+            }
+        }
+    }
+
+    let scoped_projects = get_scoped_projects(client, config)?;
+
+    for scoped_project in &scoped_projects {
+        let project_path = scoped_project.get_full_path();
+
+        let report = match &scoped_project.target_project {
+            Some(target_project) =>
+                run_project_phase(client, config, phase, &scoped_project.source_project, target_project,
+                                  &project_path, dry_run),
+            None => {
+                info!("project '{project_path}' wasn't migrated yet, skip");
+                let mut report = PhaseReport::new(phase, &project_path, dry_run);
+                report.skipped.push("project wasn't migrated yet".to_string());
+                report
+            }
+        };
+
+        results.push(report);
+    }
+
+    if phase.has_instance_level(config) {
+        results.push(run_instance_phase(client, config, phase, dry_run));
+    }
+
+    Ok(results)
+}
+
+fn run_project_phase(client: &Client, config: &AppConfig, phase: Phase, source_project: &GitLabProject,
+                     target_project: &GitLabProject, project_path: &str, dry_run: bool) -> PhaseReport {
+    let _phase = log_field(LOG_FIELD_PHASE, phase.get_name());
+    let _project = log_field(LOG_FIELD_PROJECT, project_path);
+    let started_at = Instant::now();
+
+    info!("phase '{}' for project '{project_path}'..", phase.get_name());
+
+    let mut report = PhaseReport::new(phase, project_path, dry_run);
+
+    let result = match phase {
+        Phase::MergeRequests =>
+            migrate_merge_requests(client, config, source_project, target_project, dry_run, &mut report),
+        Phase::CiVariables =>
+            migrate_project_variables(client, config, source_project, target_project, dry_run, &mut report),
+        Phase::Protections =>
+            migrate_protections(client, config, source_project, target_project, dry_run, &mut report),
+        Phase::Integrations =>
+            migrate_project_integrations(client, config, source_project, target_project, dry_run, &mut report),
+        Phase::Releases =>
+            migrate_releases(client, config, source_project, target_project, dry_run, &mut report),
+        Phase::Snippets =>
+            migrate_project_snippets(client, config, source_project, target_project, dry_run, &mut report),
+        Phase::Registry =>
+            migrate_registry(client, config, source_project, target_project, dry_run, &mut report),
+        Phase::Packages =>
+            migrate_packages(client, config, source_project, target_project, dry_run, &mut report),
+    };
+
+    finish_phase(&mut report, result, started_at);
+
+    report
+}
+
+fn run_group_phase(client: &Client, config: &AppConfig, phase: Phase, source_group: &GitLabGroup,
+                   target_group: &GitLabGroup, dry_run: bool) -> PhaseReport {
+    let _phase = log_field(LOG_FIELD_PHASE, phase.get_name());
+    let started_at = Instant::now();
+
+    info!("phase '{}' for group '{}'..", phase.get_name(), source_group.full_path);
+
+    let mut report = PhaseReport::new(phase, &source_group.full_path, dry_run);
+
+    let result = match phase {
+        Phase::CiVariables =>
+            migrate_group_variables(client, config, source_group, target_group, dry_run, &mut report),
+        Phase::Integrations =>
+            migrate_group_integrations(client, config, source_group, target_group, dry_run, &mut report),
+        Phase::MergeRequests | Phase::Protections | Phase::Releases | Phase::Snippets |
+        Phase::Registry | Phase::Packages => Ok(()),
+    };
+
+    finish_phase(&mut report, result, started_at);
+
+    report
+}
+
+fn run_instance_phase(client: &Client, config: &AppConfig, phase: Phase, dry_run: bool) -> PhaseReport {
+    let _phase = log_field(LOG_FIELD_PHASE, phase.get_name());
+    let started_at = Instant::now();
+
+    info!("phase '{}' for instance '{}'..", phase.get_name(), config.source.public_url);
+
+    let mut report = PhaseReport::new(phase, &config.source.public_url, dry_run);
+
+    let result = match phase {
+        Phase::Snippets => migrate_personal_snippets(client, config, dry_run, &mut report),
+        Phase::MergeRequests | Phase::CiVariables | Phase::Protections | Phase::Integrations |
+        Phase::Releases | Phase::Registry | Phase::Packages => Ok(()),
+    };
+
+    finish_phase(&mut report, result, started_at);
+
+    report
+}
+
+fn finish_phase(report: &mut PhaseReport, result: anyhow::Result<()>, started_at: Instant) {
+    let phase_name = report.phase.get_name();
+
+    let _duration = log_field(LOG_FIELD_DURATION_MS, &started_at.elapsed().as_millis().to_string());
+
+    if let Err(e) = result {
+        let _error_class = log_field(LOG_FIELD_ERROR_CLASS, get_error_class(&e));
+        error!("phase '{phase_name}' error for '{}': {}", report.path, e);
+        error!("{}", e.root_cause());
+        report.error = Some(format!("{}: {}", e, e.root_cause()));
+    }
+
+    info!("phase '{phase_name}' for '{}': created {}, updated {}, skipped {}, manual {}", report.path,
+          report.created.len(), report.updated.len(), report.skipped.len(), report.manual.len());
+}
@@ -0,0 +1,418 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use base64::engine::general_purpose::STANDARD;
+use base64::write::EncoderWriter;
+use log::{debug, error, info};
+use reqwest::blocking::multipart::{Form, Part};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::config::{AppConfig, InstanceConfig};
+use crate::migration::api::{encode_path_segment, get_all_pages, get_feature_all_pages, send_authorized_request};
+use crate::migration::domain::GitLabProject;
+use crate::migration::PRIVATE_TOKEN_HEADER;
+use crate::phases::PhaseReport;
+
+const SUPPORTED_PACKAGE_TYPES: [&str; 4] = ["generic", "maven", "npm", "pypi"];
+
+const PACKAGE_FILES_DIR: &str = "package-files";
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabPackage {
+    pub id: u32,
+    pub name: String,
+    pub version: String,
+    pub package_type: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabPackageFile {
+    pub file_name: String,
+    pub file_sha256: Option<String>,
+}
+
+/// Republish package registry packages through their format endpoints, every package version
+/// is a separate package in GitLab. Versions existing on target are skipped, packages of other formats
+/// are reported for manual migration. Package files are stored temporarily in `tmp-dir`.
+///
+/// API: https://docs.gitlab.com/ee/api/packages.html
+pub fn migrate_packages(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                        target_project: &GitLabProject, dry_run: bool,
+                        report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_packages: Vec<GitLabPackage> = match get_feature_all_pages(
+        client, &config.source, &format!("projects/{}/packages", source_project.id))
+        .context("unable to get packages of source project")? {
+        Some(packages) => packages,
+        None => return Ok(())
+    };
+
+    if source_packages.is_empty() {
+        return Ok(())
+    }
+
+    let target_packages: Vec<GitLabPackage> = match get_feature_all_pages(
+        client, &config.target, &format!("projects/{}/packages", target_project.id))
+        .context("unable to get packages of target project")? {
+        Some(packages) => packages,
+        None => {
+            report.manual.push(format!("package registry isn't available on target project, \
+                                        {} package versions have to be migrated manually", source_packages.len()));
+            return Ok(())
+        }
+    };
+
+    for package in &source_packages {
+        let package_name = format!("{} package '{}' {}", package.package_type, package.name, package.version);
+
+        if target_packages.iter().any(|tp| tp.package_type == package.package_type &&
+            tp.name == package.name && tp.version == package.version) {
+            continue;
+        }
+
+        if !SUPPORTED_PACKAGE_TYPES.contains(&package.package_type.as_str()) {
+            report.manual.push(format!("{package_name}: package format isn't supported"));
+            continue;
+        }
+
+        if !dry_run {
+            copy_package(client, config, source_project, target_project, package)
+                .context(format!("unable to copy {package_name}"))?;
+
+            info!("{package_name} has been published");
+        }
+
+        report.created.push(package_name);
+    }
+
+    Ok(())
+}
+
+fn copy_package(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                target_project: &GitLabProject, package: &GitLabPackage) -> anyhow::Result<()> {
+    let files: Vec<GitLabPackageFile> = get_all_pages(
+        client, &config.source, &format!("projects/{}/packages/{}/package_files", source_project.id, package.id))?;
+
+    let source_path = format!("projects/{}/packages/{}", source_project.id, package.package_type);
+    let target_path = format!("projects/{}/packages/{}", target_project.id, package.package_type);
+
+    let files_dir = Path::new(&config.tmp_dir).join(PACKAGE_FILES_DIR);
+
+    fs::create_dir_all(&files_dir).context("unable to create directory for package files")?;
+
+    match package.package_type.as_str() {
+        "npm" => copy_npm_package(client, config, &files_dir, &source_path, &target_path, package, &files),
+        "pypi" => copy_pypi_package(client, config, &files_dir, &source_path, &target_path, package, &files),
+        // generic and maven packages are uploaded file by file to the same path
+        _ => {
+            for file in &files {
+                let file_path = format!("{}/{}/{}", get_package_path(package), encode_path_segment(&package.version),
+                                        encode_path_segment(&file.file_name));
+
+                let local_file = get_local_file_path(&files_dir, package, &file.file_name);
+
+                download_file(client, &config.source, package, &format!("{source_path}/{file_path}"), &local_file)?;
+
+                let result = File::open(&local_file).context("unable to open package file")
+                    .and_then(|content| {
+                        let request = client.put(get_api_url(&config.target, &format!("{target_path}/{file_path}")))
+                            .body(content);
+
+                        send(request, &config.target, package, StatusCode::CREATED)
+                    })
+                    .context(format!("unable to upload file '{}'", file.file_name));
+
+                let _ = fs::remove_file(&local_file);
+
+                result?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Package is published with its `package.json` metadata from source registry and tarball as attachment.
+/// Request body is written to a file, so the base64 encoded tarball isn't kept in memory.
+///
+/// API: https://docs.gitlab.com/ee/user/packages/npm_registry/
+fn copy_npm_package(client: &Client, config: &AppConfig, files_dir: &Path, source_path: &str, target_path: &str,
+                    package: &GitLabPackage, files: &[GitLabPackageFile]) -> anyhow::Result<()> {
+    let package_name = encode_path_segment(&package.name);
+
+    let metadata: Value = send(client.get(get_api_url(&config.source, &format!("{source_path}/{package_name}"))),
+                               &config.source, package, StatusCode::OK)?
+        .json().context("unable to decode package metadata")?;
+
+    let version_metadata = metadata["versions"][&package.version].clone();
+
+    if version_metadata.is_null() {
+        return Err(anyhow!("version metadata wasn't found"))
+    }
+
+    let file = files.iter().find(|f| f.file_name.ends_with(".tgz"))
+        .context("package tarball wasn't found")?;
+
+    let tarball_file = get_local_file_path(files_dir, package, &file.file_name);
+    let body_file = get_local_file_path(files_dir, package, "publish.json");
+
+    let dist_tags: Map<String, Value> = metadata["dist-tags"].as_object().cloned().unwrap_or_default()
+        .into_iter().filter(|(_, version)| *version == package.version.as_str()).collect();
+
+    let result = download_file(client, &config.source, package,
+                               &format!("{source_path}/{package_name}/-/{}", file.file_name), &tarball_file)
+        .and_then(|size| {
+            let publish = json!({
+                "_id": package.name,
+                "name": package.name,
+                "versions": {&package.version: version_metadata},
+                "dist-tags": dist_tags,
+            });
+
+            write_npm_publish_body(&body_file, &publish, &file.file_name, &tarball_file, size)
+                .context("unable to write package publish request")
+        })
+        .and_then(|_| {
+            let body = File::open(&body_file).context("unable to open package publish request")?;
+
+            let request = client.put(get_api_url(&config.target, &format!("{target_path}/{package_name}")))
+                .header(CONTENT_TYPE, "application/json")
+                .body(body);
+
+            send(request, &config.target, package, StatusCode::OK)
+        });
+
+    let _ = fs::remove_file(&tarball_file);
+    let _ = fs::remove_file(&body_file);
+
+    result?;
+
+    Ok(())
+}
+
+/// `publish` object is extended with `_attachments`, tarball is base64 encoded while it's copied to the file.
+fn write_npm_publish_body(body_file: &Path, publish: &Value, file_name: &str, tarball_file: &Path,
+                          size: u64) -> anyhow::Result<()> {
+    let publish = serde_json::to_string(publish)?;
+    let publish = publish.strip_suffix('}').context("publish request isn't an object")?;
+
+    let mut body = File::create(body_file)?;
+
+    let file_name = serde_json::to_string(file_name)?;
+
+    write!(body, "{publish},\"_attachments\":{{{file_name}:{{")?;
+    write!(body, "\"content_type\":\"application/octet-stream\",\"length\":{size},\"data\":\"")?;
+
+    let mut encoder = EncoderWriter::new(body, &STANDARD);
+    io::copy(&mut File::open(tarball_file)?, &mut encoder)?;
+    let mut body = encoder.finish()?;
+
+    write!(body, "\"}}}}}}")?;
+
+    Ok(())
+}
+
+/// Every distribution file (wheel, sdist) is uploaded separately.
+///
+/// API: https://docs.gitlab.com/ee/api/packages/pypi.html
+fn copy_pypi_package(client: &Client, config: &AppConfig, files_dir: &Path, source_path: &str, target_path: &str,
+                     package: &GitLabPackage, files: &[GitLabPackageFile]) -> anyhow::Result<()> {
+    for file in files {
+        let sha256 = file.file_sha256.as_deref()
+            .context(format!("checksum of file '{}' is unknown", file.file_name))?;
+
+        let local_file = get_local_file_path(files_dir, package, &file.file_name);
+
+        let size = download_file(
+            client, &config.source, package,
+            &format!("{source_path}/files/{sha256}/{}", encode_path_segment(&file.file_name)), &local_file)?;
+
+        let result = File::open(&local_file).context("unable to open package file")
+            .and_then(|content| {
+                let form = Form::new()
+                    .text("name", package.name.to_string())
+                    .text("version", package.version.to_string())
+                    .text("sha256_digest", sha256.to_string())
+                    .part("content", Part::reader_with_length(content, size).file_name(file.file_name.to_string()));
+
+                let request = client.post(get_api_url(&config.target, target_path)).multipart(form);
+
+                send(request, &config.target, package, StatusCode::CREATED)
+            })
+            .context(format!("unable to upload file '{}'", file.file_name));
+
+        let _ = fs::remove_file(&local_file);
+
+        result?;
+    }
+
+    Ok(())
+}
+
+/// Maven package names are group and artifact paths, e.g. `com/company/lib`.
+fn get_package_path(package: &GitLabPackage) -> String {
+    if package.package_type == "maven" {
+        package.name.split('/').map(encode_path_segment).collect::<Vec<String>>().join("/")
+    } else {
+        encode_path_segment(&package.name)
+    }
+}
+
+/// Temporary file for package file, file names are unique within package only.
+fn get_local_file_path(files_dir: &Path, package: &GitLabPackage, file_name: &str) -> PathBuf {
+    files_dir.join(format!("{}-{}", package.id, encode_path_segment(file_name)))
+}
+
+/// Download package file to `file_path`, returns its size.
+fn download_file(client: &Client, instance: &InstanceConfig, package: &GitLabPackage,
+                 path: &str, file_path: &Path) -> anyhow::Result<u64> {
+    let mut response = send(client.get(get_api_url(instance, path)), instance, package, StatusCode::OK)?;
+
+    let mut file = File::create(file_path).context("unable to create package file")?;
+
+    let size = response.copy_to(&mut file).context("unable to download package file")?;
+
+    debug!("downloaded {size} bytes");
+
+    Ok(size)
+}
+
+/// Package format endpoints authorize like their clients do: npm with bearer token,
+/// PyPI with basic auth (username isn't checked for access tokens), others with `PRIVATE-TOKEN` header.
+fn send(request: RequestBuilder, instance: &InstanceConfig, package: &GitLabPackage,
+        expected_status: StatusCode) -> anyhow::Result<reqwest::blocking::Response> {
+    let request = match package.package_type.as_str() {
+        "npm" => request.bearer_auth(&instance.token),
+        "pypi" => request.basic_auth("gmt", Some(&instance.token)),
+        _ => request.header(PRIVATE_TOKEN_HEADER, instance.token.to_string())
+    };
+
+    let response = send_authorized_request(request, instance)?;
+
+    debug!("url: {}", response.url());
+
+    if response.status() == expected_status {
+        Ok(response)
+
+    } else {
+        error!("unexpected server response code {}", response.status());
+        error!("{}", response.text().unwrap_or_default());
+        Err(anyhow!("unexpected server response"))
+    }
+}
+
+fn get_api_url(instance: &InstanceConfig, path: &str) -> String {
+    format!("{}/api/v4/{path}", instance.public_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::migration::migrate_gitlab_instance;
+    use crate::phases::packages::PACKAGE_FILES_DIR;
+    use crate::phases::{Phase, run_phase_on_migrated_projects};
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    #[test]
+    fn packages_should_be_republished_by_format() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        source.add_package(api_id, "generic", "tools", "1.0.0", &[("tools.tar.gz", "tools-content")]);
+        source.add_package(api_id, "maven", "com/company/lib", "2.1", &[
+            ("lib-2.1.jar", "jar-content"), ("lib-2.1.pom", "<project/>")
+        ]);
+        source.add_package(api_id, "npm", "@company/ui", "3.0.0", &[("ui-3.0.0.tgz", "npm-content")]);
+        source.add_package(api_id, "pypi", "client", "0.5", &[("client-0.5-py3-none-any.whl", "wheel-content")]);
+        source.add_package(api_id, "conan", "boost", "1.80", &[("conanfile.py", "conan")]);
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.phases.packages.enabled = true;
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        let phase_report = report.phases.first().unwrap();
+        assert!(phase_report.error.is_none(), "{:?}", phase_report.error);
+        assert_eq!(vec![
+            "generic package 'tools' 1.0.0", "maven package 'com/company/lib' 2.1", "npm package '@company/ui' 3.0.0",
+            "pypi package 'client' 0.5"
+        ], phase_report.created);
+        assert_eq!(vec!["conan package 'boost' 1.80: package format isn't supported"], phase_report.manual);
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+
+        assert_eq!(vec![("tools.tar.gz".to_string(), "tools-content".to_string())],
+                   target.package_files(target_api_id, "generic", "tools", "1.0.0"));
+        assert_eq!(2, target.package_files(target_api_id, "maven", "com/company/lib", "2.1").len());
+        assert_eq!(vec![("ui-3.0.0.tgz".to_string(), "npm-content".to_string())],
+                   target.package_files(target_api_id, "npm", "@company/ui", "3.0.0"));
+        assert_eq!(vec![("client-0.5-py3-none-any.whl".to_string(), "wheel-content".to_string())],
+                   target.package_files(target_api_id, "pypi", "client", "0.5"));
+
+        let npm_package = target.project_resources(target_api_id, "packages").into_iter()
+            .find(|p| p["package_type"] == "npm").unwrap();
+        assert_eq!(json!("3.0.0"), npm_package["metadata"]["version"]);
+
+        assert_eq!(0, fs::read_dir(tmp_dir.path().join(PACKAGE_FILES_DIR)).unwrap().count());
+
+        let client = ClientBuilder::new().build().unwrap();
+        let reports = run_phase_on_migrated_projects(&client, &config, Phase::Packages, false).unwrap();
+
+        assert!(reports[0].created.is_empty());
+        assert_eq!(1, reports[0].manual.len());
+    }
+
+    #[test]
+    fn disabled_package_registry_should_be_reported() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        source.add_package(api_id, "generic", "tools", "1.0.0", &[("tools.tar.gz", "tools-content")]);
+        source.add_package(api_id, "generic", "tools", "1.1.0", &[("tools.tar.gz", "tools-content")]);
+
+        let web_id = source.add_project(group_id, "web", &["main"]);
+        source.set_project_attribute(web_id, "packages_enabled", json!(false));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        migrate_gitlab_instance(&config).unwrap();
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+        target.set_project_attribute(target_api_id, "packages_enabled", json!(false));
+
+        let client = ClientBuilder::new().build().unwrap();
+        let reports = run_phase_on_migrated_projects(&client, &config, Phase::Packages, false).unwrap();
+
+        let api = reports.iter().find(|r| r.path == "backend/api").unwrap();
+        assert!(api.error.is_none(), "{:?}", api.error);
+        assert_eq!(vec![
+            "package registry isn't available on target project, 2 package versions have to be migrated manually"
+        ], api.manual);
+
+        let web = reports.iter().find(|r| r.path == "backend/web").unwrap();
+        assert!(web.error.is_none(), "{:?}", web.error);
+        assert!(web.manual.is_empty() && web.created.is_empty());
+    }
+}
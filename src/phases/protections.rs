@@ -0,0 +1,419 @@
+use anyhow::Context;
+use log::info;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::config::AppConfig;
+use crate::migration::api::{delete, encode_path_segment, get_all_pages, get_optional_json, post_json, put_json};
+use crate::migration::domain::GitLabProject;
+use crate::migration::user::{get_user, UserMapper};
+use crate::phases::PhaseReport;
+
+/// Access level `No one`
+const NO_ACCESS_LEVEL: u32 = 0;
+
+/// Push rule settings copied from source project
+const PUSH_RULE_FIELDS: [&str; 12] = [
+    "commit_message_regex", "commit_message_negative_regex", "branch_name_regex", "author_email_regex",
+    "file_name_regex", "max_file_size", "deny_delete_tag", "member_check", "prevent_secrets",
+    "commit_committer_check", "commit_committer_name_check", "reject_unsigned_commits",
+];
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct GitLabAccessLevel {
+    pub access_level: Option<u32>,
+    pub user_id: Option<u32>,
+    pub group_id: Option<u32>,
+    pub access_level_description: Option<String>,
+}
+
+impl GitLabAccessLevel {
+    /// Access is granted by role, not to specific user or group
+    pub fn is_role(&self) -> bool {
+        self.user_id.is_none() && self.group_id.is_none()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabProtectedBranch {
+    pub name: String,
+
+    #[serde(default)]
+    pub push_access_levels: Vec<GitLabAccessLevel>,
+
+    #[serde(default)]
+    pub merge_access_levels: Vec<GitLabAccessLevel>,
+
+    #[serde(default)]
+    pub allow_force_push: bool,
+
+    /// Premium tier
+    #[serde(default)]
+    pub code_owner_approval_required: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabProtectedTag {
+    pub name: String,
+
+    #[serde(default)]
+    pub create_access_levels: Vec<GitLabAccessLevel>,
+}
+
+/// Copy protected branches, protected tags and push rule of source project.
+///
+/// Protection rules which differ from source are recreated, rules existing only on target are kept.
+/// Access granted to specific users is mapped with `users` config section,
+/// access granted to groups is reported for manual setup.
+pub fn migrate_protections(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                           target_project: &GitLabProject, dry_run: bool,
+                           report: &mut PhaseReport) -> anyhow::Result<()> {
+    let mut access_mapper = AccessLevelMapper {
+        client, config, user_mapper: UserMapper::new(client, &config.target, &config.users.mapping),
+    };
+
+    migrate_protected_branches(client, config, source_project, target_project, dry_run,
+                               &mut access_mapper, report).context("unable to migrate protected branches")?;
+
+    migrate_protected_tags(client, config, source_project, target_project, dry_run,
+                           &mut access_mapper, report).context("unable to migrate protected tags")?;
+
+    migrate_push_rule(client, config, source_project, target_project, dry_run, report)
+        .context("unable to migrate push rule")?;
+
+    Ok(())
+}
+
+/// API: https://docs.gitlab.com/ee/api/protected_branches.html
+fn migrate_protected_branches(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                              target_project: &GitLabProject, dry_run: bool,
+                              access_mapper: &mut AccessLevelMapper, report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_branches: Vec<GitLabProtectedBranch> = get_all_pages(
+        client, &config.source, &format!("projects/{}/protected_branches", source_project.id))?;
+
+    if source_branches.is_empty() {
+        return Ok(())
+    }
+
+    let target_path = format!("projects/{}/protected_branches", target_project.id);
+
+    let target_branches: Vec<GitLabProtectedBranch> = get_all_pages(client, &config.target, &target_path)?;
+
+    for branch in &source_branches {
+        let rule_name = format!("protected branch '{}'", branch.name);
+
+        let target_branch = target_branches.iter().find(|tb| tb.name == branch.name);
+
+        if let Some(target_branch) = target_branch {
+            if is_same_protected_branch(branch, target_branch) {
+                continue;
+            }
+        }
+
+        let allowed_to_push = access_mapper.map_specific_access(&branch.push_access_levels, &rule_name, report)?;
+        let allowed_to_merge = access_mapper.map_specific_access(&branch.merge_access_levels, &rule_name, report)?;
+
+        if !dry_run {
+            if target_branch.is_some() {
+                delete(client, &config.target, &format!("{target_path}/{}", encode_path_segment(&branch.name)))?;
+            }
+
+            let _: Value = post_json(client, &config.target, &target_path, &json!({
+                "name": branch.name,
+                "push_access_level": get_role_access_level(&branch.push_access_levels),
+                "merge_access_level": get_role_access_level(&branch.merge_access_levels),
+                "allow_force_push": branch.allow_force_push,
+                "code_owner_approval_required": branch.code_owner_approval_required,
+                "allowed_to_push": allowed_to_push,
+                "allowed_to_merge": allowed_to_merge,
+            })).context(format!("unable to create {rule_name}"))?;
+
+            info!("{rule_name} has been copied");
+        }
+
+        match target_branch {
+            Some(_) => report.updated.push(rule_name),
+            None => report.created.push(rule_name)
+        }
+    }
+
+    Ok(())
+}
+
+/// API: https://docs.gitlab.com/ee/api/protected_tags.html
+fn migrate_protected_tags(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                          target_project: &GitLabProject, dry_run: bool,
+                          access_mapper: &mut AccessLevelMapper, report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_tags: Vec<GitLabProtectedTag> = get_all_pages(
+        client, &config.source, &format!("projects/{}/protected_tags", source_project.id))?;
+
+    if source_tags.is_empty() {
+        return Ok(())
+    }
+
+    let target_path = format!("projects/{}/protected_tags", target_project.id);
+
+    let target_tags: Vec<GitLabProtectedTag> = get_all_pages(client, &config.target, &target_path)?;
+
+    for tag in &source_tags {
+        let rule_name = format!("protected tag '{}'", tag.name);
+
+        let target_tag = target_tags.iter().find(|tt| tt.name == tag.name);
+
+        if let Some(target_tag) = target_tag {
+            if get_role_access_level(&tag.create_access_levels) ==
+                get_role_access_level(&target_tag.create_access_levels) {
+                continue;
+            }
+        }
+
+        let allowed_to_create = access_mapper.map_specific_access(&tag.create_access_levels, &rule_name, report)?;
+
+        if !dry_run {
+            if target_tag.is_some() {
+                delete(client, &config.target, &format!("{target_path}/{}", encode_path_segment(&tag.name)))?;
+            }
+
+            let _: Value = post_json(client, &config.target, &target_path, &json!({
+                "name": tag.name,
+                "create_access_level": get_role_access_level(&tag.create_access_levels),
+                "allowed_to_create": allowed_to_create,
+            })).context(format!("unable to create {rule_name}"))?;
+
+            info!("{rule_name} has been copied");
+        }
+
+        match target_tag {
+            Some(_) => report.updated.push(rule_name),
+            None => report.created.push(rule_name)
+        }
+    }
+
+    Ok(())
+}
+
+/// Push rules are available on Premium tier, instances without them respond with `404`.
+///
+/// API: https://docs.gitlab.com/ee/api/projects.html#push-rules
+fn migrate_push_rule(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                     target_project: &GitLabProject, dry_run: bool, report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_push_rule: Option<Option<Value>> = get_optional_json(
+        client, &config.source, &format!("projects/{}/push_rule", source_project.id))?;
+
+    let source_push_rule = match source_push_rule.flatten() {
+        Some(push_rule) => get_push_rule_settings(&push_rule),
+        None => return Ok(())
+    };
+
+    let target_path = format!("projects/{}/push_rule", target_project.id);
+
+    let target_push_rule: Option<Option<Value>> = get_optional_json(client, &config.target, &target_path)?;
+
+    match target_push_rule {
+        None => report.manual.push("push rule: push rules aren't supported by target instance".to_string()),
+        Some(None) => {
+            if !dry_run {
+                let _: Value = post_json(client, &config.target, &target_path, &Value::Object(source_push_rule))?;
+                info!("push rule has been copied");
+            }
+
+            report.created.push("push rule".to_string());
+        }
+        Some(Some(target_push_rule)) => {
+            let differences: Vec<&str> = PUSH_RULE_FIELDS.iter()
+                .filter(|field| source_push_rule.contains_key(**field) &&
+                    source_push_rule.get(**field) != target_push_rule.get(**field))
+                .copied()
+                .collect();
+
+            if !differences.is_empty() {
+                if !dry_run {
+                    let _: Value = put_json(client, &config.target, &target_path, &Value::Object(source_push_rule))?;
+                    info!("push rule has been updated");
+                }
+
+                report.updated.push(format!("push rule: {}", differences.join(", ")));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves access granted to specific users of source instance into target users.
+struct AccessLevelMapper<'a> {
+    client: &'a Client,
+    config: &'a AppConfig,
+    user_mapper: UserMapper<'a>,
+}
+
+impl AccessLevelMapper<'_> {
+    /// Entries for `allowed_to_*` parameters, unresolved users and groups are reported.
+    fn map_specific_access(&mut self, access_levels: &[GitLabAccessLevel], rule_name: &str,
+                           report: &mut PhaseReport) -> anyhow::Result<Vec<Value>> {
+        let mut results: Vec<Value> = vec![];
+
+        for access_level in access_levels.iter().filter(|al| !al.is_role()) {
+            if let Some(group_id) = access_level.group_id {
+                report.manual.push(format!("{rule_name}: access for group {group_id} has to be set manually"));
+            }
+
+            if let Some(user_id) = access_level.user_id {
+                let target_user = match get_user(self.client, &self.config.source, user_id)? {
+                    Some(source_user) => self.user_mapper.find_target_user(&source_user.username)?,
+                    None => None
+                };
+
+                match target_user {
+                    Some(target_user) => results.push(json!({"user_id": target_user.id})),
+                    None => report.manual.push(
+                        format!("{rule_name}: access for user {user_id} has to be set manually"))
+                }
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+/// Lowest role allowed by access levels, higher roles are included by GitLab.
+pub fn get_role_access_level(access_levels: &[GitLabAccessLevel]) -> u32 {
+    access_levels.iter()
+        .filter(|al| al.is_role())
+        .filter_map(|al| al.access_level)
+        .min()
+        .unwrap_or(NO_ACCESS_LEVEL)
+}
+
+fn is_same_protected_branch(source: &GitLabProtectedBranch, target: &GitLabProtectedBranch) -> bool {
+    get_role_access_level(&source.push_access_levels) == get_role_access_level(&target.push_access_levels) &&
+        get_role_access_level(&source.merge_access_levels) == get_role_access_level(&target.merge_access_levels) &&
+        source.allow_force_push == target.allow_force_push &&
+        source.code_owner_approval_required == target.code_owner_approval_required
+}
+
+fn get_push_rule_settings(push_rule: &Value) -> Map<String, Value> {
+    PUSH_RULE_FIELDS.iter()
+        .filter_map(|field| push_rule.get(*field).map(|value| (field.to_string(), value.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::migration::migrate_gitlab_instance;
+    use crate::phases::{Phase, run_phase_on_migrated_projects};
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    #[test]
+    fn protected_refs_and_push_rule_should_be_migrated() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+        let john_id = source.add_user("john");
+
+        source.add_project_resource(api_id, "protected_branches", json!({
+            "id": 1, "name": "main", "allow_force_push": false, "code_owner_approval_required": true,
+            "push_access_levels": [
+                {"access_level": 40, "access_level_description": "Maintainers"},
+                {"access_level": 40, "user_id": john_id, "access_level_description": "John"}
+            ],
+            "merge_access_levels": [
+                {"access_level": 30, "access_level_description": "Developers + Maintainers"},
+                {"access_level": 30, "group_id": 99, "access_level_description": "QA"}
+            ]
+        }));
+        source.add_project_resource(api_id, "protected_branches", json!({
+            "id": 2, "name": "release/*", "allow_force_push": true,
+            "push_access_levels": [{"access_level": 0}],
+            "merge_access_levels": [{"access_level": 40}]
+        }));
+        source.add_project_resource(api_id, "protected_tags", json!({
+            "name": "v*", "create_access_levels": [{"access_level": 40}]
+        }));
+        source.add_project_resource(api_id, "push_rule", json!({
+            "id": 1, "project_id": api_id, "commit_message_regex": "^JIRA-\\d+", "prevent_secrets": true,
+            "max_file_size": 50, "created_at": "2022-10-01T10:00:00Z"
+        }));
+
+        let target = FakeGitLab::start();
+        let target_john_id = target.add_user("john");
+
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.phases.protections.enabled = true;
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        let phase_report = report.phases.first().unwrap();
+        assert_eq!(Phase::Protections, phase_report.phase);
+        assert_eq!(vec!["protected branch 'main'", "protected branch 'release/*'",
+                        "protected tag 'v*'", "push rule"], phase_report.created);
+        assert_eq!(vec!["protected branch 'main': access for group 99 has to be set manually"],
+                   phase_report.manual);
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+
+        let branches = target.project_resources(target_api_id, "protected_branches");
+        assert_eq!(40, branches[0]["push_access_level"]);
+        assert_eq!(30, branches[0]["merge_access_level"]);
+        assert_eq!(true, branches[0]["code_owner_approval_required"]);
+        assert_eq!(json!([{"user_id": target_john_id}]), branches[0]["allowed_to_push"]);
+        assert_eq!(0, branches[1]["push_access_level"]);
+        assert_eq!(true, branches[1]["allow_force_push"]);
+
+        let tags = target.project_resources(target_api_id, "protected_tags");
+        assert_eq!(40, tags[0]["create_access_level"]);
+
+        let push_rule = target.project_resources(target_api_id, "push_rule");
+        assert_eq!(json!({"commit_message_regex": "^JIRA-\\d+", "prevent_secrets": true, "max_file_size": 50}),
+                   push_rule[0]);
+    }
+
+    #[test]
+    fn changed_protected_branch_should_be_recreated() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        source.add_project_resource(api_id, "protected_branches", json!({
+            "id": 1, "name": "main", "push_access_levels": [{"access_level": 30}],
+            "merge_access_levels": [{"access_level": 30}]
+        }));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        migrate_gitlab_instance(&config).unwrap();
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+        target.add_project_resource(target_api_id, "protected_branches", json!({
+            "id": 100, "name": "main", "push_access_levels": [{"access_level": 40}],
+            "merge_access_levels": [{"access_level": 40}]
+        }));
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let reports = run_phase_on_migrated_projects(&client, &config, Phase::Protections, false).unwrap();
+
+        assert_eq!(vec!["protected branch 'main'"], reports.first().unwrap().updated);
+
+        let branches = target.project_resources(target_api_id, "protected_branches");
+        assert_eq!(1, branches.len());
+        assert_eq!(30, branches[0]["push_access_level"]);
+
+        let reports = run_phase_on_migrated_projects(&client, &config, Phase::Protections, false).unwrap();
+        assert!(reports.first().unwrap().updated.is_empty());
+    }
+}
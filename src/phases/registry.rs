@@ -0,0 +1,665 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context};
+use log::{debug, error, info};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{ACCEPT, CONTENT_TYPE, LOCATION, WWW_AUTHENTICATE};
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::config::{AppConfig, InstanceConfig};
+use crate::migration::api::{get_all_pages, get_feature_all_pages, get_json};
+use crate::migration::domain::{GitLabProject, GitLabUser};
+use crate::phases::PhaseReport;
+
+/// Manifest formats accepted from source registry, image lists are copied with all referenced images
+const MANIFEST_MEDIA_TYPES: [&str; 4] = [
+    "application/vnd.docker.distribution.manifest.v2+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.oci.image.index.v1+json",
+];
+
+const BLOBS_DIR: &str = "registry-blobs";
+
+/// Token lifetime if token service doesn't return `expires_in`, see token authentication specification
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(60);
+
+/// Tokens are refreshed this long before expiration, so requests don't start with token about to expire
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabRegistryRepository {
+    pub id: u32,
+
+    /// Image path, e.g. `backend/api/worker`
+    pub path: String,
+
+    /// Image path with registry host, e.g. `registry.company.com/backend/api/worker`
+    pub location: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabRegistryTag {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct ImageManifest {
+    config: Option<Descriptor>,
+
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+
+    /// Images of multi-platform image list
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Descriptor {
+    digest: String,
+
+    #[serde(default)]
+    size: u64,
+
+    /// Foreign layers are downloaded from these urls by clients, they aren't stored in registry
+    #[serde(default)]
+    urls: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Manifest {
+    media_type: String,
+    content: Vec<u8>,
+    image: ImageManifest,
+}
+
+impl Manifest {
+    fn get_blobs(&self) -> Vec<&Descriptor> {
+        self.image.config.iter().chain(self.image.layers.iter()).filter(|d| d.urls.is_empty()).collect()
+    }
+}
+
+/// Copy container registry images of project with registry HTTP API, Docker daemon isn't required.
+///
+/// Images are pushed to the same path relative to target project. Tags already existing on target are skipped,
+/// tags beyond `phases.registry.max-size-mb` budget are reported as skipped.
+///
+/// API: https://docs.gitlab.com/ee/api/container_registry.html
+pub fn migrate_registry(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                        target_project: &GitLabProject, dry_run: bool,
+                        report: &mut PhaseReport) -> anyhow::Result<()> {
+    let registry_config = &config.phases.registry;
+
+    let repositories: Vec<GitLabRegistryRepository> = match get_feature_all_pages(
+        client, &config.source, &format!("projects/{}/registry/repositories", source_project.id))
+        .context("unable to get registry repositories of source project")? {
+        Some(repositories) => repositories,
+        None => return Ok(())
+    };
+
+    if repositories.is_empty() {
+        return Ok(())
+    }
+
+    let source_url = get_registry_url(&registry_config.source_url, Some(&repositories[0].location))
+        .context("source registry url is unknown, set `phases.registry.source-url`")?;
+
+    let target_url = get_registry_url(&registry_config.target_url,
+                                      target_project.container_registry_image_prefix.as_deref())
+        .context("target registry url is unknown, set `phases.registry.target-url`")?;
+
+    let mut source_registry = RegistryClient::new(client, &source_url, &config.source)?;
+    let mut target_registry = RegistryClient::new(client, &target_url, &config.target)?;
+
+    let blobs_dir = Path::new(&config.tmp_dir).join(BLOBS_DIR);
+
+    let size_budget = registry_config.max_size_mb * 1024 * 1024;
+    let mut copied_size: u64 = 0;
+
+    for repository in &repositories {
+        let target_path = get_target_image_path(source_project, target_project, &repository.path);
+
+        let tags: Vec<GitLabRegistryTag> = get_all_pages(
+            client, &config.source,
+            &format!("projects/{}/registry/repositories/{}/tags", source_project.id, repository.id))
+            .context(format!("unable to get tags of image '{}'", repository.path))?;
+
+        for tag in &tags {
+            let image_name = format!("image '{}:{}'", repository.path, tag.name);
+
+            if !is_tag_included(&tag.name, &registry_config.tags, &registry_config.exclude_tags) {
+                debug!("{image_name} is excluded by tag filters, skip");
+                continue;
+            }
+
+            if target_registry.manifest_exists(&target_path, &tag.name)? {
+                continue;
+            }
+
+            let manifest = source_registry.get_manifest(&repository.path, &tag.name)?
+                .context(format!("{image_name}: manifest wasn't found in source registry"))?;
+
+            let mut child_manifests: Vec<(String, Manifest)> = vec![];
+
+            for child in &manifest.image.manifests {
+                let child_manifest = source_registry.get_manifest(&repository.path, &child.digest)?
+                    .context(format!("{image_name}: manifest '{}' wasn't found in source registry", child.digest))?;
+
+                child_manifests.push((child.digest.to_string(), child_manifest));
+            }
+
+            let image_size: u64 = manifest.get_blobs().into_iter()
+                .chain(child_manifests.iter().flat_map(|(_, m)| m.get_blobs()))
+                .map(|d| d.size).sum();
+
+            if size_budget > 0 && copied_size + image_size > size_budget {
+                info!("{image_name}: size budget exceeded, skip");
+                report.skipped.push(
+                    format!("{image_name}: size budget of {} MB exceeded", registry_config.max_size_mb));
+                continue;
+            }
+
+            if !dry_run {
+                for (digest, child_manifest) in &child_manifests {
+                    copy_blobs(&mut source_registry, &mut target_registry, &blobs_dir, &repository.path,
+                               &target_path, child_manifest)?;
+                    target_registry.put_manifest(&target_path, digest, child_manifest)?;
+                }
+
+                copy_blobs(&mut source_registry, &mut target_registry, &blobs_dir, &repository.path,
+                           &target_path, &manifest)?;
+                target_registry.put_manifest(&target_path, &tag.name, &manifest)
+                    .context(format!("unable to push {image_name}"))?;
+
+                info!("{image_name} has been copied ({image_size} bytes)");
+            }
+
+            copied_size += image_size;
+
+            report.created.push(image_name);
+        }
+    }
+
+    Ok(())
+}
+
+fn copy_blobs(source_registry: &mut RegistryClient, target_registry: &mut RegistryClient, blobs_dir: &Path,
+              source_path: &str, target_path: &str, manifest: &Manifest) -> anyhow::Result<()> {
+    for blob in manifest.get_blobs() {
+        if target_registry.blob_exists(target_path, &blob.digest)? {
+            continue;
+        }
+
+        fs::create_dir_all(blobs_dir).context("unable to create directory for registry blobs")?;
+
+        let blob_file = blobs_dir.join(blob.digest.replace(':', "-"));
+
+        source_registry.download_blob(source_path, &blob.digest, &blob_file)
+            .context(format!("unable to download blob '{}'", blob.digest))?;
+
+        let result = target_registry.upload_blob(target_path, &blob.digest, &blob_file)
+            .context(format!("unable to upload blob '{}'", blob.digest));
+
+        let _ = fs::remove_file(&blob_file);
+
+        result?;
+    }
+
+    Ok(())
+}
+
+/// Configured url or `https://` url of registry host from image location.
+fn get_registry_url(configured_url: &str, image_location: Option<&str>) -> Option<String> {
+    if !configured_url.is_empty() {
+        return Some(configured_url.trim_end_matches('/').to_string())
+    }
+
+    image_location.and_then(|location| location.split('/').next())
+        .filter(|host| !host.is_empty())
+        .map(|host| format!("https://{host}"))
+}
+
+/// Image path relative to project is kept, registry paths are lower case.
+fn get_target_image_path(source_project: &GitLabProject, target_project: &GitLabProject, image_path: &str) -> String {
+    let source_project_path = format!("{}/{}", source_project.namespace.full_path, source_project.path).to_lowercase();
+    let target_project_path = format!("{}/{}", target_project.namespace.full_path, target_project.path).to_lowercase();
+
+    match image_path.to_lowercase().strip_prefix(&source_project_path) {
+        Some(image_name) => format!("{target_project_path}{image_name}"),
+        None => image_path.to_lowercase()
+    }
+}
+
+fn is_tag_included(tag: &str, include_patterns: &[String], exclude_patterns: &[String]) -> bool {
+    (include_patterns.is_empty() || include_patterns.iter().any(|p| matches_pattern(tag, p))) &&
+        !exclude_patterns.iter().any(|p| matches_pattern(tag, p))
+}
+
+/// Pattern with `*` wildcard matching any characters, e.g. `v1.*`.
+fn matches_pattern(value: &str, pattern: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+
+    if parts.len() == 1 {
+        return value == pattern
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+
+    if value.len() < first.len() + last.len() || !value.starts_with(first) || !value.ends_with(last) {
+        return false
+    }
+
+    let mut rest = &value[first.len()..value.len() - last.len()];
+
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false
+        }
+    }
+
+    true
+}
+
+/// Registry HTTP API v2 client, authorized with GitLab JWT token issued for instance access token.
+///
+/// API: https://docs.docker.com/registry/spec/api/
+struct RegistryClient<'a> {
+    client: &'a Client,
+    url: String,
+    username: String,
+    password: String,
+
+    /// Token service `(realm, service)`, `None` if registry doesn't require authorization
+    token_service: Option<Option<(String, String)>>,
+
+    /// Bearer tokens by scope
+    tokens: HashMap<String, RegistryToken>,
+}
+
+struct RegistryToken {
+    token: String,
+
+    /// Token is refreshed after this moment
+    refresh_at: Instant,
+}
+
+impl<'a> RegistryClient<'a> {
+    fn new(client: &'a Client, url: &str, instance: &InstanceConfig) -> anyhow::Result<RegistryClient<'a>> {
+        let user: GitLabUser = get_json(client, instance, "user")
+            .context("unable to get token user")?;
+
+        Ok(RegistryClient {
+            client,
+            url: url.to_string(),
+            username: user.username,
+            password: instance.token.to_string(),
+            token_service: None,
+            tokens: HashMap::new(),
+        })
+    }
+
+    fn get_manifest(&mut self, path: &str, reference: &str) -> anyhow::Result<Option<Manifest>> {
+        let request = self.client.get(format!("{}/v2/{path}/manifests/{reference}", self.url))
+            .header(ACCEPT, MANIFEST_MEDIA_TYPES.join(", "));
+
+        let response = self.send(request, path, "pull")?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            StatusCode::OK => {
+                let media_type = response.headers().get(CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+
+                let content = response.bytes().context("unable to read manifest")?.to_vec();
+
+                let image: ImageManifest = serde_json::from_slice(&content)
+                    .context("unable to decode manifest")?;
+
+                Ok(Some(Manifest { media_type, content, image }))
+            }
+            status => get_unexpected_status_error(status)
+        }
+    }
+
+    fn manifest_exists(&mut self, path: &str, reference: &str) -> anyhow::Result<bool> {
+        let request = self.client.head(format!("{}/v2/{path}/manifests/{reference}", self.url))
+            .header(ACCEPT, MANIFEST_MEDIA_TYPES.join(", "));
+
+        self.exists(request, path)
+    }
+
+    fn put_manifest(&mut self, path: &str, reference: &str, manifest: &Manifest) -> anyhow::Result<()> {
+        let request = self.client.put(format!("{}/v2/{path}/manifests/{reference}", self.url))
+            .header(CONTENT_TYPE, manifest.media_type.to_string())
+            .body(manifest.content.clone());
+
+        match self.send(request, path, "pull,push")?.status() {
+            StatusCode::CREATED => Ok(()),
+            status => get_unexpected_status_error(status)
+        }
+    }
+
+    fn blob_exists(&mut self, path: &str, digest: &str) -> anyhow::Result<bool> {
+        let request = self.client.head(format!("{}/v2/{path}/blobs/{digest}", self.url));
+        self.exists(request, path)
+    }
+
+    fn download_blob(&mut self, path: &str, digest: &str, file_path: &Path) -> anyhow::Result<()> {
+        let request = self.client.get(format!("{}/v2/{path}/blobs/{digest}", self.url));
+
+        let mut response = self.send(request, path, "pull")?;
+
+        if response.status() != StatusCode::OK {
+            return get_unexpected_status_error(response.status())
+        }
+
+        let mut file = File::create(file_path).context("unable to create blob file")?;
+
+        let size = response.copy_to(&mut file).context("unable to download blob")?;
+
+        debug!("blob '{digest}' downloaded, {size} bytes");
+
+        Ok(())
+    }
+
+    /// Monolithic upload: https://docs.docker.com/registry/spec/api/#monolithic-upload
+    fn upload_blob(&mut self, path: &str, digest: &str, file_path: &Path) -> anyhow::Result<()> {
+        let request = self.client.post(format!("{}/v2/{path}/blobs/uploads/", self.url));
+
+        let response = self.send(request, path, "pull,push")?;
+
+        if response.status() != StatusCode::ACCEPTED {
+            return get_unexpected_status_error(response.status())
+        }
+
+        let location = response.headers().get(LOCATION).and_then(|v| v.to_str().ok())
+            .context("upload location is missing")?;
+
+        let mut upload_url = Url::parse(&self.url)?.join(location).context("invalid upload location")?;
+        upload_url.query_pairs_mut().append_pair("digest", digest);
+
+        let file = File::open(file_path).context("unable to open blob file")?;
+
+        let request = self.client.put(upload_url)
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(file);
+
+        match self.send(request, path, "pull,push")?.status() {
+            StatusCode::CREATED => Ok(()),
+            status => get_unexpected_status_error(status)
+        }
+    }
+
+    fn exists(&mut self, request: RequestBuilder, path: &str) -> anyhow::Result<bool> {
+        match self.send(request, path, "pull")?.status() {
+            StatusCode::OK => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            status => get_unexpected_status_error(status)
+        }
+    }
+
+    /// Request is sent once more with new token if token has been rejected, e.g. expired earlier than expected.
+    /// Requests with streamed body can't be repeated, tokens are refreshed before expiration for them.
+    fn send(&mut self, request: RequestBuilder, path: &str, actions: &str) -> anyhow::Result<Response> {
+        let retry_request = request.try_clone();
+
+        let response = self.send_with_token(request, path, actions)?;
+
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response)
+        }
+
+        let rejected_token = self.tokens.remove(&get_token_scope(path, actions));
+
+        match (retry_request, rejected_token) {
+            (Some(retry_request), Some(_)) => {
+                debug!("registry token has been rejected, retry with new token");
+                self.send_with_token(retry_request, path, actions)
+            }
+            _ => Ok(response)
+        }
+    }
+
+    fn send_with_token(&mut self, request: RequestBuilder, path: &str, actions: &str) -> anyhow::Result<Response> {
+        let request = match self.get_token(path, actions)? {
+            Some(token) => request.bearer_auth(token),
+            None => request
+        };
+
+        let response = request.send().context("registry communication error")?;
+
+        debug!("registry url: {} {}", response.url(), response.status());
+
+        Ok(response)
+    }
+
+    /// Token for `repository:{path}:{actions}` scope, `None` if registry doesn't require authorization.
+    fn get_token(&mut self, path: &str, actions: &str) -> anyhow::Result<Option<String>> {
+        if self.token_service.is_none() {
+            self.token_service = Some(self.get_token_service()?);
+        }
+
+        let (realm, service) = match self.token_service.as_ref().and_then(|s| s.as_ref()) {
+            Some(token_service) => token_service.clone(),
+            None => return Ok(None)
+        };
+
+        let scope = get_token_scope(path, actions);
+
+        if let Some(token) = self.tokens.get(&scope) {
+            if Instant::now() < token.refresh_at {
+                return Ok(Some(token.token.to_string()))
+            }
+
+            debug!("registry token for scope '{scope}' is about to expire, refresh");
+        }
+
+        let requested_at = Instant::now();
+
+        let response = self.client.get(&realm)
+            .query(&[("service", service.as_str()), ("scope", scope.as_str())])
+            .basic_auth(&self.username, Some(&self.password))
+            .send().context("registry token service communication error")?;
+
+        if response.status() != StatusCode::OK {
+            error!("registry token request for scope '{scope}' has failed");
+            return get_unexpected_status_error(response.status())
+        }
+
+        let token_response: Value = response.json().context("unable to decode registry token")?;
+
+        let token = token_response["token"].as_str()
+            .or_else(|| token_response["access_token"].as_str())
+            .context("registry token is missing")?.to_string();
+
+        // lifetime is counted from request time, so clock difference with `issued_at` doesn't matter
+        let lifetime = token_response["expires_in"].as_u64().map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TOKEN_LIFETIME);
+
+        let refresh_at = requested_at + lifetime.saturating_sub(TOKEN_REFRESH_MARGIN);
+
+        self.tokens.insert(scope, RegistryToken { token: token.to_string(), refresh_at });
+
+        Ok(Some(token))
+    }
+
+    /// Token service from `WWW-Authenticate: Bearer realm="...",service="..."` challenge of `/v2/`.
+    fn get_token_service(&self) -> anyhow::Result<Option<(String, String)>> {
+        let response = self.client.get(format!("{}/v2/", self.url))
+            .send().context("registry communication error")?;
+
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(None)
+        }
+
+        let challenge = response.headers().get(WWW_AUTHENTICATE).and_then(|v| v.to_str().ok())
+            .context("registry authorization challenge is missing")?;
+
+        let get_param = |name: &str| challenge.split(',')
+            .filter_map(|param| param.trim().trim_start_matches("Bearer ").split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.trim_matches('"').to_string());
+
+        let realm = get_param("realm").context("registry token realm is missing")?;
+
+        Ok(Some((realm, get_param("service").unwrap_or_default())))
+    }
+}
+
+fn get_token_scope(path: &str, actions: &str) -> String {
+    format!("repository:{path}:{actions}")
+}
+
+fn get_unexpected_status_error<T>(status: StatusCode) -> anyhow::Result<T> {
+    error!("unexpected registry response code {status}");
+    Err(anyhow!("unexpected registry response"))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::migration::migrate_gitlab_instance;
+    use crate::phases::registry::{matches_pattern, RegistryClient};
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::registry::{FakeRegistry, MANIFEST_LIST_MEDIA_TYPE};
+    use crate::tests::{get_test_config, init_logging};
+
+    #[test]
+    fn tag_patterns_should_support_wildcards() {
+        assert!(matches_pattern("v1.2", "v1.2"));
+        assert!(matches_pattern("v1.2", "v*"));
+        assert!(matches_pattern("release-1-rc", "release-*-rc"));
+        assert!(matches_pattern("dev", "*"));
+        assert!(!matches_pattern("v1.2", "v1"));
+        assert!(!matches_pattern("ab", "a*b*b"));
+    }
+
+    #[test]
+    fn registry_tokens_should_be_refreshed() {
+        init_logging();
+
+        let gitlab = FakeGitLab::start();
+        let registry = FakeRegistry::start();
+        registry.add_image("backend/api", "v1.0", &["layer-1"], 1024);
+
+        let client = ClientBuilder::new().build().unwrap();
+        let mut registry_client = RegistryClient::new(&client, &registry.url(), &gitlab.instance()).unwrap();
+
+        let get_token_requests = || registry.requests().iter().filter(|r| r.url.starts_with("/jwt/auth")).count();
+
+        assert!(registry_client.manifest_exists("backend/api", "v1.0").unwrap());
+        assert!(registry_client.manifest_exists("backend/api", "v1.0").unwrap());
+        assert_eq!(1, get_token_requests());
+
+        // rejected token is replaced
+        registry.expire_tokens();
+        assert!(registry_client.manifest_exists("backend/api", "v1.0").unwrap());
+        assert_eq!(2, get_token_requests());
+
+        // token is replaced before it expires
+        registry.set_token_lifetime(10);
+        registry_client.tokens.clear();
+        assert!(registry_client.manifest_exists("backend/api", "v1.0").unwrap());
+        assert!(registry_client.manifest_exists("backend/api", "v1.0").unwrap());
+        assert_eq!(4, get_token_requests());
+    }
+
+    #[test]
+    fn registry_images_should_be_copied_with_filters_and_size_budget() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        let source_registry = FakeRegistry::start();
+        let amd64_digest = source_registry.add_image("backend/api", "amd64", &["layer-1", "layer-amd64"], 1024);
+        let arm64_digest = source_registry.add_image("backend/api", "arm64", &["layer-1", "layer-arm64"], 1024);
+        source_registry.add_image_list("backend/api", "v1.0", &[&amd64_digest, &arm64_digest]);
+        source_registry.add_image("backend/api/worker", "v1.0", &["worker-layer"], 1024);
+        source_registry.add_image("backend/api/worker", "dev-5", &["dev-layer"], 1024);
+        source_registry.add_image("backend/api/worker", "v2.0", &["huge-layer"], 4 * 1024 * 1024);
+
+        let registry_host = source_registry.url().replace("http://", "");
+
+        source.add_project_resource(api_id, "registry/repositories", json!({
+            "id": 1, "name": "", "path": "backend/api", "location": format!("{registry_host}/backend/api")
+        }));
+        source.add_project_resource(api_id, "registry/repositories", json!({
+            "id": 2, "name": "worker", "path": "backend/api/worker",
+            "location": format!("{registry_host}/backend/api/worker")
+        }));
+        source.add_resource(&format!("projects/{api_id}/registry/repositories/1/tags"), json!({"name": "v1.0"}));
+        for tag in ["v1.0", "dev-5", "v2.0"] {
+            source.add_resource(&format!("projects/{api_id}/registry/repositories/2/tags"), json!({"name": tag}));
+        }
+
+        let target = FakeGitLab::start();
+        let target_registry = FakeRegistry::start();
+
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.phases.registry.enabled = true;
+        config.phases.registry.source_url = source_registry.url();
+        config.phases.registry.target_url = target_registry.url();
+        config.phases.registry.exclude_tags = vec!["dev-*".to_string()];
+        config.phases.registry.max_size_mb = 2;
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        let phase_report = report.phases.first().unwrap();
+        assert!(phase_report.error.is_none(), "{:?}", phase_report.error);
+        assert_eq!(vec!["image 'backend/api:v1.0'", "image 'backend/api/worker:v1.0'"], phase_report.created);
+        assert_eq!(vec!["image 'backend/api/worker:v2.0': size budget of 2 MB exceeded"], phase_report.skipped);
+
+        let (media_type, _) = target_registry.manifest("backend/api", "v1.0").unwrap();
+        assert_eq!(MANIFEST_LIST_MEDIA_TYPE, media_type);
+        assert!(target_registry.manifest("backend/api", &arm64_digest).is_some());
+        assert!(target_registry.manifest("backend/api/worker", "dev-5").is_none());
+
+        let blobs = target_registry.blobs("backend/api");
+        assert!(blobs.contains(&"layer-arm64".to_string()));
+        assert_eq!(5, blobs.len());
+
+        assert_eq!(vec!["worker-layer"], target_registry.blobs("backend/api/worker").into_iter()
+            .filter(|b| b.contains("layer")).collect::<Vec<String>>());
+
+        let layer_uploads = target_registry.requests().iter()
+            .filter(|r| r.method == "PUT" && r.url.contains("/blobs/uploads/")).count();
+        assert_eq!(7, layer_uploads);
+    }
+
+    #[test]
+    fn projects_with_disabled_registry_should_be_skipped() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+        source.set_project_attribute(api_id, "container_registry_enabled", json!(false));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.phases.registry.enabled = true;
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        let phase_report = report.phases.first().unwrap();
+        assert!(phase_report.error.is_none(), "{:?}", phase_report.error);
+        assert!(phase_report.created.is_empty() && phase_report.skipped.is_empty());
+    }
+}
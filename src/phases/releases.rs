@@ -0,0 +1,247 @@
+use anyhow::Context;
+use log::info;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config::AppConfig;
+use crate::migration::api::{get_all_pages, post_json};
+use crate::migration::domain::GitLabProject;
+use crate::migration::project::get_project_tags;
+use crate::phases::markdown::MarkdownRewriter;
+use crate::phases::PhaseReport;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabRelease {
+    pub tag_name: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub released_at: Option<String>,
+
+    #[serde(default)]
+    pub milestones: Vec<GitLabMilestone>,
+
+    #[serde(default)]
+    pub assets: GitLabReleaseAssets,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct GitLabReleaseAssets {
+    #[serde(default)]
+    pub links: Vec<GitLabReleaseLink>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabReleaseLink {
+    pub name: String,
+    pub url: String,
+    pub link_type: Option<String>,
+
+    /// `{project_url}/-/releases/{tag}/downloads{direct_asset_path}`
+    pub direct_asset_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabMilestone {
+    pub id: u32,
+    pub title: String,
+    pub description: Option<String>,
+    pub start_date: Option<String>,
+    pub due_date: Option<String>,
+}
+
+/// Copy releases with their milestones and asset links. Tags have to be pushed before.
+///
+/// Release notes and asset links are rewritten with `MarkdownRewriter`, referenced uploads are copied.
+/// Missing milestones are created on target project.
+/// Releases already existing on target are skipped.
+///
+/// API: https://docs.gitlab.com/ee/api/releases/
+pub fn migrate_releases(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                        target_project: &GitLabProject, dry_run: bool,
+                        report: &mut PhaseReport) -> anyhow::Result<()> {
+    let mut source_releases: Vec<GitLabRelease> = get_all_pages(
+        client, &config.source, &format!("projects/{}/releases", source_project.id))
+        .context("unable to get releases from source project")?;
+
+    if source_releases.is_empty() {
+        return Ok(())
+    }
+
+    // newest releases come first
+    source_releases.reverse();
+
+    let target_releases_path = format!("projects/{}/releases", target_project.id);
+
+    let target_releases: Vec<GitLabRelease> = get_all_pages(client, &config.target, &target_releases_path)
+        .context("unable to get releases from target project")?;
+
+    let target_tags = get_project_tags(client, &config.target, target_project.id)
+        .context("unable to get target project tags")?;
+
+    let mut markdown_rewriter = MarkdownRewriter::new(client, config, source_project, target_project);
+
+    for release in &source_releases {
+        let release_name = format!("release '{}'", release.tag_name);
+
+        if target_releases.iter().any(|tr| tr.tag_name == release.tag_name) {
+            continue;
+        }
+
+        if !target_tags.iter().any(|t| t.name == release.tag_name) {
+            info!("{release_name}: tag doesn't exist on target, skip");
+            report.skipped.push(format!("{release_name}: tag doesn't exist on target"));
+            continue;
+        }
+
+        if dry_run {
+            report.created.push(release_name);
+            continue;
+        }
+
+        for milestone in &release.milestones {
+            if create_milestone_if_missing(client, config, target_project, milestone)? {
+                report.created.push(format!("milestone '{}'", milestone.title));
+            }
+        }
+
+        let description = markdown_rewriter.rewrite(release.description.as_deref().unwrap_or_default(), report)?;
+
+        let mut links: Vec<Value> = vec![];
+
+        for link in &release.assets.links {
+            let mut link_json = json!({
+                "name": link.name,
+                "url": markdown_rewriter.rewrite(&link.url, report)?,
+                "link_type": link.link_type.as_deref().unwrap_or("other"),
+            });
+
+            if let Some(direct_asset_path) = link.direct_asset_url.as_deref()
+                .and_then(|url| url.split_once("/downloads/")).map(|(_, path)| format!("/{path}")) {
+                link_json["direct_asset_path"] = json!(direct_asset_path);
+            }
+
+            links.push(link_json);
+        }
+
+        let _: Value = post_json(client, &config.target, &target_releases_path, &json!({
+            "tag_name": release.tag_name,
+            "name": release.name,
+            "description": description,
+            "released_at": release.released_at,
+            "milestones": release.milestones.iter().map(|m| m.title.to_string()).collect::<Vec<String>>(),
+            "assets": {"links": links},
+        })).context(format!("unable to create {release_name}"))?;
+
+        info!("{release_name} has been created");
+
+        report.created.push(release_name);
+    }
+
+    Ok(())
+}
+
+/// Returns `true` if milestone has been created.
+///
+/// API: https://docs.gitlab.com/ee/api/milestones.html
+fn create_milestone_if_missing(client: &Client, config: &AppConfig, target_project: &GitLabProject,
+                               milestone: &GitLabMilestone) -> anyhow::Result<bool> {
+    let milestones_path = format!("projects/{}/milestones", target_project.id);
+
+    let target_milestones: Vec<GitLabMilestone> = get_all_pages(client, &config.target, &milestones_path)?;
+
+    if target_milestones.iter().any(|m| m.title == milestone.title) {
+        return Ok(false)
+    }
+
+    let _: Value = post_json(client, &config.target, &milestones_path, &json!({
+        "title": milestone.title,
+        "description": milestone.description,
+        "start_date": milestone.start_date,
+        "due_date": milestone.due_date,
+    })).context(format!("unable to create milestone '{}'", milestone.title))?;
+
+    info!("milestone '{}' has been created", milestone.title);
+
+    Ok(true)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::migration::migrate_gitlab_instance;
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    #[test]
+    fn releases_should_be_migrated_with_uploads() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+        source.add_tag(api_id, "v1.0", "main");
+        source.add_tag(api_id, "v1.1", "main");
+
+        let screenshot_url = source.add_upload(api_id, "screen.png", "png-content");
+        let binary_url = source.add_upload(api_id, "api.tar.gz", "tar-content");
+        let source_project_url = format!("{}/backend/api", source.instance().public_url);
+
+        source.add_project_resource(api_id, "releases", json!({
+            "tag_name": "v1.1", "name": "Version 1.1", "released_at": "2022-11-01T10:00:00Z",
+            "description": format!("## Changes\n\n![screen]({screenshot_url})"),
+            "milestones": [{"id": 30, "title": "1.1", "description": "Second", "due_date": "2022-11-01"}],
+            "assets": {"links": [{
+                "id": 1, "name": "api.tar.gz", "url": format!("{source_project_url}{binary_url}"),
+                "link_type": "package",
+                "direct_asset_url": format!("{source_project_url}/-/releases/v1.1/downloads/bin/api.tar.gz")
+            }]}
+        }));
+        source.add_project_resource(api_id, "releases", json!({
+            "tag_name": "v1.0", "name": "Version 1.0", "description": "First", "released_at": "2022-10-01T10:00:00Z"
+        }));
+        source.add_project_resource(api_id, "releases", json!({
+            "tag_name": "v0.9", "name": "Version 0.9", "description": "Tag was removed"
+        }));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.phases.releases.enabled = true;
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        let phase_report = report.phases.first().unwrap();
+        assert_eq!(vec!["release 'v1.0'", "milestone '1.1'", "release 'v1.1'"], phase_report.created);
+        assert_eq!(vec!["release 'v0.9': tag doesn't exist on target"], phase_report.skipped);
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+
+        let uploads = target.uploads(target_api_id);
+        assert_eq!(2, uploads.len());
+
+        let target_screenshot_url = uploads.iter().find(|(_, c)| *c == "png-content").unwrap().0;
+        let target_binary_url = uploads.iter().find(|(_, c)| *c == "tar-content").unwrap().0;
+
+        let releases = target.project_resources(target_api_id, "releases");
+        assert_eq!("v1.0", releases[0]["tag_name"]);
+
+        let release = &releases[1];
+        assert_eq!("Version 1.1", release["name"]);
+        assert_eq!("2022-11-01T10:00:00Z", release["released_at"]);
+        assert_eq!(json!(["1.1"]), release["milestones"]);
+        assert_eq!(format!("## Changes\n\n![screen]({target_screenshot_url})"), release["description"]);
+
+        let link = &release["assets"]["links"][0];
+        assert_eq!(format!("{}/backend/api{target_binary_url}", target.instance().public_url), link["url"]);
+        assert_eq!("package", link["link_type"]);
+        assert_eq!("/bin/api.tar.gz", link["direct_asset_path"]);
+
+        let milestones = target.project_resources(target_api_id, "milestones");
+        assert_eq!("2022-11-01", milestones[0]["due_date"]);
+    }
+}
@@ -0,0 +1,337 @@
+use anyhow::Context;
+use log::info;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config::{AppConfig, InstanceConfig};
+use crate::migration::api::{encode_path_segment, get_all_pages, get_file, post_json};
+use crate::migration::domain::{GitLabProject, GitLabUser};
+use crate::migration::user::UserMapper;
+use crate::phases::PhaseReport;
+
+const DEFAULT_SNIPPET_REF: &str = "main";
+
+/// From the most to the least restrictive.
+const VISIBILITY_LEVELS: [&str; 3] = ["private", "internal", "public"];
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabSnippet {
+    pub id: u32,
+    pub title: String,
+    pub description: Option<String>,
+    pub visibility: Option<String>,
+    pub file_name: Option<String>,
+    pub project_id: Option<u32>,
+    pub author: Option<GitLabUser>,
+
+    /// Multiple files are supported since GitLab 13.5, older versions have `file_name` only
+    #[serde(default)]
+    pub files: Vec<GitLabSnippetFile>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitLabSnippetFile {
+    pub path: String,
+
+    /// `{web_url}/raw/{ref}/{path}`
+    pub raw_url: Option<String>,
+}
+
+/// Snippet API accepts file contents as text only.
+enum SnippetFiles {
+    /// File path and content for every snippet file
+    Text(Vec<(String, String)>),
+
+    /// Path of the first file which isn't valid UTF-8, snippet isn't copied
+    Binary(String),
+}
+
+/// Copy project snippets with all their files. Snippets already existing on target are matched by title.
+/// Snippet visibility is lowered to visibility of target project, target projects are created as private.
+///
+/// API: https://docs.gitlab.com/ee/api/project_snippets.html
+pub fn migrate_project_snippets(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                                target_project: &GitLabProject, dry_run: bool,
+                                report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_path = format!("projects/{}/snippets", source_project.id);
+    let target_path = format!("projects/{}/snippets", target_project.id);
+
+    let source_snippets: Vec<GitLabSnippet> = get_all_pages(client, &config.source, &source_path)
+        .context("unable to get snippets from source project")?;
+
+    if source_snippets.is_empty() {
+        return Ok(())
+    }
+
+    let target_snippets: Vec<GitLabSnippet> = get_all_pages(client, &config.target, &target_path)
+        .context("unable to get snippets from target project")?;
+
+    for snippet in &source_snippets {
+        let snippet_name = format!("snippet '{}'", snippet.title);
+
+        if target_snippets.iter().any(|ts| ts.title == snippet.title) {
+            continue;
+        }
+
+        let files = match get_snippet_files(client, &config.source, snippet, &source_path)
+            .context(format!("unable to get files of {snippet_name}"))? {
+            SnippetFiles::Text(files) => files,
+            SnippetFiles::Binary(path) => {
+                report.manual.push(format!("{snippet_name}: binary file '{path}' has to be copied manually"));
+                continue;
+            }
+        };
+
+        let source_visibility = snippet.visibility.as_deref().unwrap_or("private");
+        let visibility = get_capped_visibility(source_visibility, &target_project.visibility);
+
+        if visibility != source_visibility {
+            report.manual.push(format!("{snippet_name}: visibility lowered from {source_visibility} to {visibility}, \
+                                        raise it when target project visibility is set"));
+        }
+
+        if !dry_run {
+            create_snippet(client, config, snippet, visibility, files, &target_path)
+                .context(format!("unable to copy {snippet_name}"))?;
+
+            info!("{snippet_name} has been created");
+        }
+
+        report.created.push(snippet_name);
+    }
+
+    Ok(())
+}
+
+/// Copy personal snippets of all users, snippets are created on behalf of mapped target users
+/// with `sudo`, so admin tokens are required on both instances.
+///
+/// API: https://docs.gitlab.com/ee/api/snippets.html
+pub fn migrate_personal_snippets(client: &Client, config: &AppConfig, dry_run: bool,
+                                 report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_snippets = get_personal_snippets(client, &config.source)
+        .context("unable to get personal snippets from source instance")?;
+
+    if source_snippets.is_empty() {
+        return Ok(())
+    }
+
+    let target_snippets = get_personal_snippets(client, &config.target)
+        .context("unable to get personal snippets from target instance")?;
+
+    let mut user_mapper = UserMapper::new(client, &config.target, &config.users.mapping);
+
+    for snippet in &source_snippets {
+        let author = get_author_username(snippet);
+        let snippet_name = format!("snippet '{}' of @{author}", snippet.title);
+
+        let target_user = match user_mapper.find_target_user(author)? {
+            Some(target_user) => target_user,
+            None => {
+                report.skipped.push(format!("{snippet_name}: user wasn't found on target instance"));
+                continue;
+            }
+        };
+
+        if target_snippets.iter()
+            .any(|ts| ts.title == snippet.title && get_author_username(ts) == target_user.username) {
+            continue;
+        }
+
+        let files = match get_snippet_files(client, &config.source, snippet, "snippets")
+            .context(format!("unable to get files of {snippet_name}"))? {
+            SnippetFiles::Text(files) => files,
+            SnippetFiles::Binary(path) => {
+                report.manual.push(format!("{snippet_name}: binary file '{path}' has to be copied manually"));
+                continue;
+            }
+        };
+
+        if !dry_run {
+            let visibility = snippet.visibility.as_deref().unwrap_or("private");
+
+            create_snippet(client, config, snippet, visibility, files,
+                           &format!("snippets?sudo={}", target_user.username))
+                .context(format!("unable to copy {snippet_name}"))?;
+
+            info!("{snippet_name} has been created for user '{}'", target_user.username);
+        }
+
+        report.created.push(snippet_name);
+    }
+
+    Ok(())
+}
+
+/// Admins get snippets of all users, project snippets are filtered out.
+fn get_personal_snippets(client: &Client, instance: &InstanceConfig) -> anyhow::Result<Vec<GitLabSnippet>> {
+    let snippets: Vec<GitLabSnippet> = get_all_pages(client, instance, "snippets/all")?;
+    Ok(snippets.into_iter().filter(|s| s.project_id.is_none()).collect())
+}
+
+/// `target_path` is snippet collection, e.g. `projects/5/snippets`.
+fn create_snippet(client: &Client, config: &AppConfig, snippet: &GitLabSnippet, visibility: &str,
+                  files: Vec<(String, String)>, target_path: &str) -> anyhow::Result<()> {
+    let _: Value = post_json(client, &config.target, target_path, &json!({
+        "title": snippet.title,
+        "description": snippet.description,
+        "visibility": visibility,
+        "files": files.iter()
+            .map(|(path, content)| json!({"file_path": path, "content": content}))
+            .collect::<Vec<Value>>(),
+    }))?;
+
+    Ok(())
+}
+
+/// Snippet can't be more visible than its project, unknown levels are treated as private.
+fn get_capped_visibility<'a>(visibility: &'a str, project_visibility: &'a str) -> &'a str {
+    let level = |v: &str| VISIBILITY_LEVELS.iter().position(|l| *l == v).unwrap_or(0);
+
+    if level(visibility) > level(project_visibility) {
+        project_visibility
+    } else {
+        visibility
+    }
+}
+
+/// Contents of snippet files, `snippets_path` is snippet collection, e.g. `projects/5/snippets`.
+fn get_snippet_files(client: &Client, instance: &InstanceConfig, snippet: &GitLabSnippet,
+                     snippets_path: &str) -> anyhow::Result<SnippetFiles> {
+    let snippet_url = format!("{}/api/v4/{snippets_path}/{}", instance.public_url, snippet.id);
+
+    let mut files: Vec<(String, Vec<u8>)> = vec![];
+
+    if snippet.files.is_empty() {
+        let content = get_file(client, instance, &format!("{snippet_url}/raw"))?
+            .context("snippet content wasn't found")?;
+
+        files.push((snippet.file_name.clone().unwrap_or_else(|| snippet.title.to_string()), content));
+    }
+
+    for file in &snippet.files {
+        let file_url = format!("{snippet_url}/files/{}/{}/raw", get_file_ref(file), encode_path_segment(&file.path));
+
+        let content = get_file(client, instance, &file_url)?
+            .context(format!("snippet file '{}' wasn't found", file.path))?;
+
+        files.push((file.path.to_string(), content));
+    }
+
+    let mut text_files: Vec<(String, String)> = vec![];
+
+    for (path, content) in files {
+        match String::from_utf8(content) {
+            Ok(content) => text_files.push((path, content)),
+            Err(_) => return Ok(SnippetFiles::Binary(path))
+        }
+    }
+
+    Ok(SnippetFiles::Text(text_files))
+}
+
+/// Snippet repository ref taken from raw url, default branch name differs between instances.
+fn get_file_ref(file: &GitLabSnippetFile) -> String {
+    file.raw_url.as_deref()
+        .and_then(|url| url.split_once("/raw/"))
+        .and_then(|(_, ref_and_path)| ref_and_path.split_once('/'))
+        .map(|(file_ref, _)| file_ref.to_string())
+        .unwrap_or_else(|| DEFAULT_SNIPPET_REF.to_string())
+}
+
+fn get_author_username(snippet: &GitLabSnippet) -> &str {
+    snippet.author.as_ref().map(|a| a.username.as_str()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::migration::migrate_gitlab_instance;
+    use crate::phases::{Phase, run_phase_on_migrated_projects};
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    #[test]
+    fn project_and_personal_snippets_should_be_migrated() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        source.add_snippet(Some(api_id), json!({
+            "title": "Restart runbook", "description": "How to restart api", "visibility": "internal",
+            "author": {"id": 50, "username": "john", "name": "John"},
+            "files": [
+                {"path": "restart.md", "content": "# Restart", "raw_url": "http://source/-/snippets/1/raw/master/restart.md"},
+                {"path": "scripts/restart.sh", "content": "systemctl restart api"}
+            ]
+        }));
+        source.add_snippet(Some(api_id), json!({
+            "title": "Logo", "visibility": "private",
+            "files": [
+                {"path": "README.md", "content": "Logo sources"},
+                {"path": "logo.png", "content_bytes": [137, 80, 78, 71, 13, 10, 26, 10, 0, 255]}
+            ]
+        }));
+        source.add_snippet(None, json!({
+            "title": "Notes", "visibility": "private", "author": {"id": 51, "username": "jane", "name": "Jane"},
+            "file_name": "notes.txt", "content": "my notes"
+        }));
+        source.add_snippet(None, json!({
+            "title": "Old notes", "visibility": "private", "author": {"id": 52, "username": "bob", "name": "Bob"},
+            "file_name": "old.txt", "content": "bob notes"
+        }));
+
+        let target = FakeGitLab::start();
+        target.add_user("jane.doe");
+
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.phases.snippets.enabled = true;
+        config.phases.snippets.personal = true;
+        config.users.mapping.insert("jane".to_string(), "jane.doe".to_string());
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        let project_report = report.phases.iter().find(|r| r.path == "backend/api").unwrap();
+        assert_eq!(vec!["snippet 'Restart runbook'"], project_report.created);
+        assert_eq!(vec![
+            "snippet 'Restart runbook': visibility lowered from internal to private, \
+             raise it when target project visibility is set",
+            "snippet 'Logo': binary file 'logo.png' has to be copied manually",
+        ], project_report.manual);
+
+        let instance_report = report.phases.iter().find(|r| r.path == source.instance().public_url).unwrap();
+        assert_eq!(vec!["snippet 'Notes' of @jane"], instance_report.created);
+        assert_eq!(vec!["snippet 'Old notes' of @bob: user wasn't found on target instance"],
+                   instance_report.skipped);
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+
+        let snippets = target.project_resources(target_api_id, "snippets");
+        assert_eq!(1, snippets.len());
+        assert_eq!("private", snippets[0]["visibility"]);
+        assert_eq!("How to restart api", snippets[0]["description"]);
+        assert_eq!(json!([
+            {"path": "restart.md", "content": "# Restart"},
+            {"path": "scripts/restart.sh", "content": "systemctl restart api"}
+        ]), snippets[0]["files"]);
+
+        let personal_snippets = target.resources("snippets");
+        assert_eq!(1, personal_snippets.len());
+        assert_eq!("jane.doe", personal_snippets[0]["author"]["username"]);
+        assert_eq!(json!([{"path": "notes.txt", "content": "my notes"}]), personal_snippets[0]["files"]);
+
+        let client = ClientBuilder::new().build().unwrap();
+        let reports = run_phase_on_migrated_projects(&client, &config, Phase::Snippets, false).unwrap();
+
+        assert_eq!(2, reports.len());
+        assert!(reports.iter().all(|r| r.created.is_empty()));
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::Context;
+use log::info;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config::{AppConfig, MaskedVariablesPolicy};
+use crate::migration::api::{get_all_pages, post_json, put_json};
+use crate::migration::domain::{GitLabGroup, GitLabProject};
+use crate::phases::PhaseReport;
+
+const DEFAULT_ENVIRONMENT_SCOPE: &str = "*";
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct GitLabVariable {
+    pub key: String,
+
+    /// Absent for hidden variables
+    pub value: Option<String>,
+
+    /// `env_var` or `file`
+    pub variable_type: String,
+
+    #[serde(default)]
+    pub protected: bool,
+
+    #[serde(default)]
+    pub masked: bool,
+
+    #[serde(default)]
+    pub raw: bool,
+
+    /// Absent for group variables on Free tier
+    #[serde(default = "get_default_environment_scope")]
+    pub environment_scope: String,
+
+    pub description: Option<String>,
+}
+
+/// API: https://docs.gitlab.com/ee/api/project_level_variables.html
+pub fn migrate_project_variables(client: &Client, config: &AppConfig, source_project: &GitLabProject,
+                                 target_project: &GitLabProject, dry_run: bool,
+                                 report: &mut PhaseReport) -> anyhow::Result<()> {
+    migrate_variables(client, config, &format!("projects/{}", source_project.id),
+                      &format!("projects/{}", target_project.id), dry_run, report)
+}
+
+/// API: https://docs.gitlab.com/ee/api/group_level_variables.html
+pub fn migrate_group_variables(client: &Client, config: &AppConfig, source_group: &GitLabGroup,
+                               target_group: &GitLabGroup, dry_run: bool,
+                               report: &mut PhaseReport) -> anyhow::Result<()> {
+    migrate_variables(client, config, &format!("groups/{}", source_group.id),
+                      &format!("groups/{}", target_group.id), dry_run, report)
+}
+
+/// Create missing variables on target and update variables which differ from source.
+/// Variables are matched by key and environment scope, variables existing only on target are kept.
+fn migrate_variables(client: &Client, config: &AppConfig, source_owner_path: &str, target_owner_path: &str,
+                     dry_run: bool, report: &mut PhaseReport) -> anyhow::Result<()> {
+    let source_variables: Vec<GitLabVariable> = get_all_pages(
+        client, &config.source, &format!("{source_owner_path}/variables"))
+        .context("unable to get variables from source instance")?;
+
+    if source_variables.is_empty() {
+        return Ok(())
+    }
+
+    let target_variables: Vec<GitLabVariable> = get_all_pages(
+        client, &config.target, &format!("{target_owner_path}/variables"))
+        .context("unable to get variables from target instance")?;
+
+    for variable in &source_variables {
+        let variable_name = format!("{} ({})", variable.key, variable.environment_scope);
+
+        if variable.masked && config.phases.ci_variables.masked == MaskedVariablesPolicy::Report {
+            report.manual.push(format!("{variable_name}: masked, value has to be set manually"));
+            continue;
+        }
+
+        if variable.value.is_none() {
+            report.manual.push(format!("{variable_name}: value isn't readable, has to be set manually"));
+            continue;
+        }
+
+        let target_variable = target_variables.iter()
+            .find(|tv| tv.key == variable.key && tv.environment_scope == variable.environment_scope);
+
+        match target_variable {
+            None => {
+                if !dry_run {
+                    let _: Value = post_json(client, &config.target, &format!("{target_owner_path}/variables"),
+                                             &get_variable_json(variable))
+                        .context(format!("unable to create variable {variable_name}"))?;
+
+                    info!("variable {variable_name} has been created");
+                }
+
+                report.created.push(variable_name);
+            }
+            Some(target_variable) => {
+                let differences = get_variable_differences(variable, target_variable);
+
+                if differences.is_empty() {
+                    continue;
+                }
+
+                if !dry_run {
+                    let _: Value = put_json(
+                        client, &config.target,
+                        &format!("{target_owner_path}/variables/{}?filter[environment_scope]={}",
+                                 variable.key, variable.environment_scope),
+                        &get_variable_json(variable))
+                        .context(format!("unable to update variable {variable_name}"))?;
+
+                    info!("variable {variable_name} has been updated");
+                }
+
+                report.updated.push(format!("{variable_name}: {}", differences.join(", ")));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Changed fields, values are never shown.
+fn get_variable_differences(source: &GitLabVariable, target: &GitLabVariable) -> Vec<String> {
+    let mut differences: Vec<String> = vec![];
+
+    if source.value != target.value {
+        differences.push("value".to_string());
+    }
+
+    if source.variable_type != target.variable_type {
+        differences.push(format!("variable_type '{}' -> '{}'", target.variable_type, source.variable_type));
+    }
+
+    let flags = [
+        ("protected", source.protected, target.protected),
+        ("masked", source.masked, target.masked),
+        ("raw", source.raw, target.raw),
+    ];
+
+    for (name, source_flag, target_flag) in flags {
+        if source_flag != target_flag {
+            differences.push(format!("{name} {target_flag} -> {source_flag}"));
+        }
+    }
+
+    if source.description.as_deref().unwrap_or_default() != target.description.as_deref().unwrap_or_default() {
+        differences.push("description".to_string());
+    }
+
+    differences
+}
+
+fn get_variable_json(variable: &GitLabVariable) -> Value {
+    json!({
+        "key": variable.key,
+        "value": variable.value,
+        "variable_type": variable.variable_type,
+        "protected": variable.protected,
+        "masked": variable.masked,
+        "raw": variable.raw,
+        "environment_scope": variable.environment_scope,
+        "description": variable.description,
+    })
+}
+
+fn get_default_environment_scope() -> String {
+    DEFAULT_ENVIRONMENT_SCOPE.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::{json, Value};
+    use tempfile::TempDir;
+
+    use crate::config::MaskedVariablesPolicy;
+    use crate::migration::migrate_gitlab_instance;
+    use crate::phases::{Phase, run_phase_on_migrated_projects};
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    fn get_variable(key: &str, value: &str, masked: bool, scope: &str) -> Value {
+        json!({
+            "key": key, "value": value, "variable_type": "env_var", "protected": true,
+            "masked": masked, "raw": false, "environment_scope": scope, "description": null
+        })
+    }
+
+    #[test]
+    fn project_and_group_variables_should_be_migrated() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        source.add_project_resource(api_id, "variables", get_variable("DB_HOST", "db.local", false, "production"));
+        source.add_project_resource(api_id, "variables", get_variable("DB_PASSWORD", "secret123", true, "*"));
+        source.add_project_resource(api_id, "variables", json!({
+            "key": "KUBECONFIG", "value": "apiVersion: v1", "variable_type": "file", "protected": false,
+            "masked": false, "raw": true, "environment_scope": "*"
+        }));
+        source.add_resource(&format!("groups/{group_id}/variables"), json!({
+            "key": "REGISTRY", "value": "registry.local", "variable_type": "env_var",
+            "protected": false, "masked": false
+        }));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let mut config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        config.phases.ci_variables.enabled = true;
+        config.phases.ci_variables.masked = MaskedVariablesPolicy::Report;
+
+        let report = migrate_gitlab_instance(&config).unwrap();
+
+        let group_report = report.phases.iter().find(|r| r.path == "backend").unwrap();
+        assert_eq!(vec!["REGISTRY (*)"], group_report.created);
+
+        let project_report = report.phases.iter().find(|r| r.path == "backend/api").unwrap();
+        assert_eq!(Phase::CiVariables, project_report.phase);
+        assert_eq!(vec!["DB_HOST (production)", "KUBECONFIG (*)"], project_report.created);
+        assert_eq!(vec!["DB_PASSWORD (*): masked, value has to be set manually"], project_report.manual);
+
+        let target_group_id = target.group_id("backend").unwrap();
+        assert_eq!("registry.local", target.resources(&format!("groups/{target_group_id}/variables"))[0]["value"]);
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+        let variables = target.project_resources(target_api_id, "variables");
+        assert_eq!(2, variables.len());
+        assert_eq!("production", variables[0]["environment_scope"]);
+        assert_eq!(true, variables[0]["protected"]);
+        assert_eq!("file", variables[1]["variable_type"]);
+        assert_eq!(true, variables[1]["raw"]);
+    }
+
+    #[test]
+    fn changed_variables_should_be_shown_in_dry_run_without_changes() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        source.add_project_resource(api_id, "variables", get_variable("DB_HOST", "db.local", false, "*"));
+        source.add_project_resource(api_id, "variables", get_variable("DB_PASSWORD", "secret123", true, "*"));
+        source.add_project_resource(api_id, "variables", get_variable("API_URL", "https://api", false, "*"));
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        migrate_gitlab_instance(&config).unwrap();
+
+        let target_api_id = target.project_id("backend/api").unwrap();
+        target.add_project_resource(target_api_id, "variables", get_variable("DB_HOST", "db.old", false, "*"));
+        target.add_project_resource(target_api_id, "variables", get_variable("API_URL", "https://api", false, "*"));
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let reports = run_phase_on_migrated_projects(&client, &config, Phase::CiVariables, true).unwrap();
+
+        let project_report = reports.iter().find(|r| r.path == "backend/api").unwrap();
+        assert!(project_report.dry_run);
+        assert_eq!(vec!["DB_PASSWORD (*)"], project_report.created);
+        assert_eq!(vec!["DB_HOST (*): value"], project_report.updated);
+
+        let variables = target.project_resources(target_api_id, "variables");
+        assert_eq!(2, variables.len());
+        assert_eq!("db.old", variables[0]["value"]);
+
+        run_phase_on_migrated_projects(&client, &config, Phase::CiVariables, false).unwrap();
+
+        let variables = target.project_resources(target_api_id, "variables");
+        assert_eq!(3, variables.len());
+        assert_eq!("db.local", variables[0]["value"]);
+        assert_eq!("secret123", variables[2]["value"]);
+    }
+}
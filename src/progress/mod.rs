@@ -0,0 +1,243 @@
+use std::io::{IsTerminal, stdout, Write};
+use std::time::{Duration, Instant};
+
+use crate::git::clone::GitTransfer;
+
+const BAR_WIDTH: usize = 30;
+
+/// Redraws caused by git output are limited to keep terminal responsive
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+const KIBIBYTE: f64 = 1024.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectOutcome {
+    Migrated,
+    Skipped,
+    Failed,
+}
+
+impl ProjectOutcome {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ProjectOutcome::Migrated => "migrated",
+            ProjectOutcome::Skipped => "skipped",
+            ProjectOutcome::Failed => "failed",
+        }
+    }
+}
+
+/// Receives migration events, e.g. to show progress to user. Events are ignored by default.
+pub trait MigrationProgress {
+    fn start(&mut self, _total: usize) {}
+
+    fn project_started(&mut self, _project_path: &str) {}
+
+    /// Size transferred by git since the last call
+    fn bytes_transferred(&mut self, _transfer: GitTransfer, _bytes: u64) {}
+
+    fn project_finished(&mut self, _project_path: &str, _outcome: ProjectOutcome) {}
+
+    fn finish(&mut self) {}
+}
+
+/// Progress isn't reported
+pub struct NoProgress;
+
+impl MigrationProgress for NoProgress {}
+
+/// Progress bar redrawn in place when stdout is a terminal, one line per project otherwise.
+pub struct TerminalProgress {
+    interactive: bool,
+    started_at: Instant,
+    redrawn_at: Option<Instant>,
+    total: usize,
+    migrated: usize,
+    skipped: usize,
+    failed: usize,
+    bytes: u64,
+    current_project: Option<String>,
+}
+
+impl TerminalProgress {
+    pub fn new() -> TerminalProgress {
+        TerminalProgress::with_interactive(stdout().is_terminal())
+    }
+
+    fn with_interactive(interactive: bool) -> TerminalProgress {
+        TerminalProgress {
+            interactive,
+            started_at: Instant::now(),
+            redrawn_at: None,
+            total: 0,
+            migrated: 0,
+            skipped: 0,
+            failed: 0,
+            bytes: 0,
+            current_project: None,
+        }
+    }
+
+    fn get_done(&self) -> usize {
+        self.migrated + self.skipped + self.failed
+    }
+
+    /// E.g. `[#########---------] 12/40 30% ETA 5m 10s | migrated 10, skipped 1, failed 1 | 35.2 MiB | backend/api`
+    fn get_progress_line(&self, elapsed: Duration) -> String {
+        let done = self.get_done();
+
+        let filled = (done * BAR_WIDTH).checked_div(self.total).unwrap_or(BAR_WIDTH).min(BAR_WIDTH);
+        let percent = (done * 100).checked_div(self.total).unwrap_or(100);
+
+        let eta = if done > 0 && done < self.total {
+            format_duration(elapsed.mul_f64((self.total - done) as f64 / done as f64))
+        } else {
+            "-".to_string()
+        };
+
+        let mut line = format!("[{}{}] {done}/{} {percent}% ETA {eta} | {} | {}",
+                               "#".repeat(filled), "-".repeat(BAR_WIDTH - filled), self.total,
+                               self.get_counts(), format_bytes(self.bytes));
+
+        if let Some(project_path) = &self.current_project {
+            line.push_str(&format!(" | {project_path}"));
+        }
+
+        line
+    }
+
+    fn get_counts(&self) -> String {
+        format!("migrated {}, skipped {}, failed {}", self.migrated, self.skipped, self.failed)
+    }
+
+    fn redraw(&mut self, force: bool) {
+        let now = Instant::now();
+
+        if !force && self.redrawn_at.map(|at| now - at < REDRAW_INTERVAL).unwrap_or(false) {
+            return;
+        }
+
+        self.redrawn_at = Some(now);
+
+        let mut out = stdout();
+        let _ = write!(out, "\r\x1b[2K{}", self.get_progress_line(now - self.started_at));
+        let _ = out.flush();
+    }
+}
+
+impl Default for TerminalProgress {
+    fn default() -> Self {
+        TerminalProgress::new()
+    }
+}
+
+impl MigrationProgress for TerminalProgress {
+    fn start(&mut self, total: usize) {
+        self.total = total;
+        self.started_at = Instant::now();
+
+        if self.interactive {
+            self.redraw(true);
+        } else {
+            println!("projects to check: {total}");
+        }
+    }
+
+    fn project_started(&mut self, project_path: &str) {
+        self.current_project = Some(project_path.to_string());
+
+        if self.interactive {
+            self.redraw(true);
+        }
+    }
+
+    fn bytes_transferred(&mut self, _transfer: GitTransfer, bytes: u64) {
+        self.bytes += bytes;
+
+        if self.interactive {
+            self.redraw(false);
+        }
+    }
+
+    fn project_finished(&mut self, project_path: &str, outcome: ProjectOutcome) {
+        match outcome {
+            ProjectOutcome::Migrated => self.migrated += 1,
+            ProjectOutcome::Skipped => self.skipped += 1,
+            ProjectOutcome::Failed => self.failed += 1,
+        }
+
+        self.current_project = None;
+
+        if self.interactive {
+            self.redraw(true);
+
+        } else {
+            println!("[{}/{}] {project_path}: {}", self.get_done(), self.total, outcome.get_name());
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.interactive {
+            self.redraw(true);
+            println!();
+
+        } else {
+            println!("{}, transferred {} in {}", self.get_counts(), format_bytes(self.bytes),
+                     format_duration(self.started_at.elapsed()));
+        }
+    }
+}
+
+/// E.g. `35.2 MiB`
+pub fn format_bytes(bytes: u64) -> String {
+    let mut value = bytes as f64;
+
+    for unit in ["B", "KiB", "MiB", "GiB"] {
+        if value < KIBIBYTE {
+            return match unit {
+                "B" => format!("{bytes} B"),
+                _ => format!("{value:.1} {unit}")
+            }
+        }
+
+        value /= KIBIBYTE;
+    }
+
+    format!("{value:.1} TiB")
+}
+
+/// E.g. `1h 5m`, `5m 10s`, `42s`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    match seconds {
+        0..=59 => format!("{seconds}s"),
+        60..=3599 => format!("{}m {}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::git::clone::GitTransfer;
+    use crate::progress::{format_bytes, MigrationProgress, ProjectOutcome, TerminalProgress};
+
+    #[test]
+    fn progress_line_should_contain_eta_counts_and_bytes() {
+        let mut progress = TerminalProgress::with_interactive(false);
+        progress.start(4);
+        progress.project_finished("backend/api", ProjectOutcome::Migrated);
+        progress.project_finished("backend/web", ProjectOutcome::Skipped);
+        progress.project_started("backend/docs");
+        progress.bytes_transferred(GitTransfer::Received, 1024 * 1024);
+        progress.bytes_transferred(GitTransfer::Pushed, 512 * 1024);
+
+        assert_eq!("[###############---------------] 2/4 50% ETA 1m 30s | migrated 1, skipped 1, failed 0 \
+                    | 1.5 MiB | backend/docs", progress.get_progress_line(Duration::from_secs(90)));
+
+        assert_eq!("232 B", format_bytes(232));
+        assert_eq!("1.0 GiB", format_bytes(1024 * 1024 * 1024));
+    }
+}
@@ -0,0 +1,177 @@
+use std::path::Path;
+
+use log::{error, info};
+use reqwest::blocking::Client;
+
+use crate::config::AppConfig;
+use crate::git::sync::{RepoSyncResult, sync_git_repo};
+use crate::migration::scope::get_scoped_projects;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectSyncStatus {
+    /// Project wasn't found on target instance, `migrate` has to be used
+    NotMigrated,
+
+    Synced(RepoSyncResult),
+
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectSync {
+    pub project_path: String,
+    pub status: ProjectSyncStatus,
+}
+
+/// Push commits and tags added on source instance after migration to already migrated projects.
+pub fn sync_migrated_projects(client: &Client, config: &AppConfig,
+                              force: bool) -> anyhow::Result<Vec<ProjectSync>> {
+    let source = &config.source;
+    let target = &config.target;
+
+    info!("sync projects from '{}' to '{}' (force: {force})..", source.public_url, target.public_url);
+
+    let scoped_projects = get_scoped_projects(client, config)?;
+
+    let mut results: Vec<ProjectSync> = vec![];
+
+    for (index, scoped_project) in scoped_projects.iter().enumerate() {
+        let project_path = scoped_project.get_full_path();
+
+        let status = match &scoped_project.target_project {
+            Some(target_project) => {
+                let source_repo_url = format!("{}/{}", source.git_url, project_path);
+                let target_repo_url = format!("{}/{}/{}.git", target.git_url,
+                                              target_project.namespace.full_path, target_project.path);
+
+                match sync_git_repo(&config.git_bin_path, Path::new(&config.tmp_dir), &project_path,
+                                    &source_repo_url, &target_repo_url, force) {
+                    Ok(sync_result) => ProjectSyncStatus::Synced(sync_result),
+                    Err(e) => {
+                        error!("repo sync error: {}", e);
+                        error!("{}", e.root_cause());
+                        ProjectSyncStatus::Failed(format!("{}: {}", e, e.root_cause()))
+                    }
+                }
+            }
+            None => {
+                info!("project '{project_path}' wasn't migrated yet, skip");
+                ProjectSyncStatus::NotMigrated
+            }
+        };
+
+        info!("project '{project_path}' sync status: {:?}", status);
+        info!("sync progress: {}/{}", index + 1, scoped_projects.len());
+
+        results.push(ProjectSync { project_path, status });
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use tempfile::TempDir;
+
+    use crate::migration::migrate_gitlab_instance;
+    use crate::sync::{ProjectSyncStatus, sync_migrated_projects};
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+
+    #[test]
+    fn new_commits_and_tags_should_be_pushed_to_target() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+        source.add_project(group_id, "web", &["main"]);
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        migrate_gitlab_instance(&config).unwrap();
+
+        let new_commit = source.add_commit(api_id, "main", "after migration");
+        source.add_tag(api_id, "v1.1", "main");
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let results = sync_migrated_projects(&client, &config, false).unwrap();
+
+        let api = results.iter().find(|r| r.project_path == "backend/api").unwrap();
+
+        match &api.status {
+            ProjectSyncStatus::Synced(result) => {
+                assert_eq!(vec!["refs/heads/main", "refs/tags/v1.1"], result.updated_refs);
+                assert!(result.diverged_refs.is_empty());
+            }
+            status => panic!("unexpected status {:?}", status)
+        }
+
+        assert_eq!(new_commit, target.branch_commit("backend/api", "main"));
+
+        let web = results.iter().find(|r| r.project_path == "backend/web").unwrap();
+        assert_eq!(ProjectSyncStatus::Synced(Default::default()), web.status);
+    }
+
+    #[test]
+    fn diverged_branches_should_be_reported_and_forced_only_on_demand() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main"]);
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        migrate_gitlab_instance(&config).unwrap();
+
+        let source_commit = source.add_commit(api_id, "main", "source change");
+        let target_api_id = target.project_id("backend/api").unwrap();
+        let target_commit = target.add_commit(target_api_id, "main", "target change");
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let results = sync_migrated_projects(&client, &config, false).unwrap();
+
+        match &results.first().unwrap().status {
+            ProjectSyncStatus::Synced(result) => {
+                assert_eq!(vec!["refs/heads/main"], result.diverged_refs);
+                assert!(result.updated_refs.is_empty());
+            }
+            status => panic!("unexpected status {:?}", status)
+        }
+
+        assert_eq!(target_commit, target.branch_commit("backend/api", "main"));
+
+        sync_migrated_projects(&client, &config, true).unwrap();
+
+        assert_eq!(source_commit, target.branch_commit("backend/api", "main"));
+    }
+
+    #[test]
+    fn not_migrated_projects_should_be_skipped() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        source.add_project(group_id, "api", &["main"]);
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let results = sync_migrated_projects(&client, &config, false).unwrap();
+
+        assert_eq!(ProjectSyncStatus::NotMigrated, results.first().unwrap().status);
+        assert!(target.project_paths().is_empty());
+    }
+}
@@ -0,0 +1,1220 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use reqwest::Url;
+use serde_json::{json, Map, Value};
+use tempfile::TempDir;
+
+use crate::config::InstanceConfig;
+use crate::tests::mock::{MOCK_TOKEN, MockResponse, MockServer, RecordedRequest, serve_requests};
+
+/// Sub-resources which are single objects instead of lists
+const SINGLE_RESOURCES: [&str; 1] = ["/push_rule"];
+
+#[derive(Clone, Debug)]
+struct FakeGroup {
+    id: u32,
+    parent_id: Option<u32>,
+    name: String,
+    path: String,
+    full_path: String,
+    /// `group` or `user` for personal namespaces, which aren't listed as groups
+    kind: String,
+}
+
+#[derive(Clone, Debug)]
+struct FakeProject {
+    id: u32,
+    name: String,
+    path: String,
+    namespace_id: u32,
+    visibility: String,
+    lfs_objects_size: u64,
+    /// Extra project attributes, e.g. `description` or `archived`,
+    /// `statistics` object is returned only if requested
+    attributes: Map<String, Value>,
+}
+
+struct FakeGitLabState {
+    repos_path: PathBuf,
+    next_id: u32,
+    groups: Vec<FakeGroup>,
+    projects: Vec<FakeProject>,
+    users: Vec<Value>,
+    /// Sub-resources of groups and projects by api path, e.g. `projects/1/merge_requests`
+    resources: BTreeMap<String, Vec<Value>>,
+    /// Uploaded files by `{project_id}/uploads/{secret}/{filename}`
+    uploads: BTreeMap<String, String>,
+    /// Archive requests are rejected, like for tokens without owner role
+    archiving_disabled: bool,
+}
+
+/// In-process GitLab stand-in for end-to-end tests.
+///
+/// Serves groups, projects and branches over the REST API, every project is backed by a
+/// local bare repository reachable through `file://` git url.
+pub struct FakeGitLab {
+    server: MockServer,
+    state: Arc<Mutex<FakeGitLabState>>,
+    repos_dir: TempDir,
+}
+
+impl FakeGitLab {
+    pub fn start() -> FakeGitLab {
+        let repos_dir = TempDir::new().unwrap();
+
+        let state = Arc::new(Mutex::new(FakeGitLabState {
+            repos_path: repos_dir.path().to_path_buf(),
+            next_id: 1,
+            groups: vec![],
+            projects: vec![],
+            users: vec![],
+            resources: BTreeMap::new(),
+            uploads: BTreeMap::new(),
+            archiving_disabled: false,
+        }));
+
+        let handler_state = state.clone();
+
+        let server = serve_requests(move |request| {
+            if let Some(response) = get_package_authorization_error(request) {
+                return response
+            }
+
+            let mut state = handler_state.lock().unwrap();
+            handle_request(&mut state, &request.method, &request.url, &request.body)
+        });
+
+        FakeGitLab { server, state, repos_dir }
+    }
+
+    pub fn instance(&self) -> InstanceConfig {
+        InstanceConfig {
+            public_url: self.server.url.to_string(),
+            git_url: format!("file://{}", self.repos_dir.path().display()),
+            token: MOCK_TOKEN.to_string(),
+        }
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.server.requests()
+    }
+
+    pub fn add_group(&self, name: &str, parent_id: Option<u32>) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        let path = name.to_lowercase();
+        create_group(&mut state, name, &path, parent_id).unwrap().id
+    }
+
+    /// Add project with repository, every branch gets its own commit.
+    pub fn add_project(&self, group_id: u32, name: &str, branches: &[&str]) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        let path = name.to_lowercase();
+        let project = create_project(&mut state, name, &path, group_id).unwrap();
+
+        let repo_path = get_repo_path(&state, &project);
+
+        if let Some((first_branch, other_branches)) = branches.split_first() {
+            let tree = git(&repo_path, &["mktree"]);
+            let root_commit = git(&repo_path, &["commit-tree", &tree, "-m", first_branch]);
+            git(&repo_path, &["update-ref", &format!("refs/heads/{first_branch}"), &root_commit]);
+            git(&repo_path, &["symbolic-ref", "HEAD", &format!("refs/heads/{first_branch}")]);
+
+            for branch in other_branches {
+                let commit = git(&repo_path, &["commit-tree", &tree, "-p", &root_commit, "-m", branch]);
+                git(&repo_path, &["update-ref", &format!("refs/heads/{branch}"), &commit]);
+            }
+        }
+
+        project.id
+    }
+
+    pub fn add_tag(&self, project_id: u32, tag: &str, branch: &str) {
+        let state = self.state.lock().unwrap();
+        let project = state.projects.iter().find(|p| p.id == project_id).unwrap();
+        git(&get_repo_path(&state, project), &["tag", tag, branch]);
+    }
+
+    pub fn set_visibility(&self, project_id: u32, visibility: &str) {
+        self.update_project(project_id, |p| p.visibility = visibility.to_string());
+    }
+
+    /// Reject archive and unarchive requests with `403 Forbidden`.
+    pub fn disable_archiving(&self) {
+        self.state.lock().unwrap().archiving_disabled = true;
+    }
+
+    pub fn set_project_attribute(&self, project_id: u32, name: &str, value: Value) {
+        self.update_project(project_id, |p| { p.attributes.insert(name.to_string(), value); });
+    }
+
+    pub fn project_attribute(&self, project_id: u32, name: &str) -> Value {
+        let state = self.state.lock().unwrap();
+        let project = state.projects.iter().find(|p| p.id == project_id).unwrap();
+        project.attributes.get(name).cloned().unwrap_or(Value::Null)
+    }
+
+    pub fn set_lfs_objects_size(&self, project_id: u32, size: u64) {
+        self.update_project(project_id, |p| p.lfs_objects_size = size);
+    }
+
+    /// Add item to project sub-resource list, e.g. `issues`.
+    pub fn add_project_resource(&self, project_id: u32, resource: &str, item: Value) {
+        self.add_resource(&format!("projects/{project_id}/{resource}"), item);
+    }
+
+    /// Add item to sub-resource list by api path, e.g. `groups/1/variables`.
+    pub fn add_resource(&self, path: &str, item: Value) {
+        let mut state = self.state.lock().unwrap();
+        state.resources.entry(path.to_string()).or_default().push(item);
+    }
+
+    /// Items of sub-resource list by api path, e.g. `projects/1/merge_requests/1/discussions`.
+    pub fn resources(&self, path: &str) -> Vec<Value> {
+        let state = self.state.lock().unwrap();
+        state.resources.get(path).cloned().unwrap_or_default()
+    }
+
+    /// Add project upload, returns its markdown url `/uploads/{secret}/{filename}`.
+    pub fn add_upload(&self, project_id: u32, filename: &str, content: &str) -> String {
+        let mut state = self.state.lock().unwrap();
+        add_upload(&mut state, project_id, filename, content)
+    }
+
+    /// Project uploads by markdown url
+    pub fn uploads(&self, project_id: u32) -> BTreeMap<String, String> {
+        let state = self.state.lock().unwrap();
+        let prefix = format!("{project_id}/");
+
+        state.uploads.iter()
+            .filter_map(|(key, content)| key.strip_prefix(&prefix).map(|url| (format!("/{url}"), content.to_string())))
+            .collect()
+    }
+
+    /// Add project or personal snippet, file contents are kept in `content` fields of the snippet or its `files`,
+    /// binary contents in `content_bytes` arrays.
+    pub fn add_snippet(&self, project_id: Option<u32>, mut snippet: Value) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        let id = get_next_id(&mut state);
+
+        snippet["id"] = json!(id);
+        snippet["project_id"] = json!(project_id);
+
+        state.resources.entry(get_snippets_path(project_id)).or_default().push(snippet);
+        id
+    }
+
+    /// Add package version with files, returns package id.
+    pub fn add_package(&self, project_id: u32, package_type: &str, name: &str, version: &str,
+                       files: &[(&str, &str)]) -> u32 {
+        let mut state = self.state.lock().unwrap();
+
+        let mut package_id = 0;
+
+        for (file_name, content) in files {
+            package_id = add_package_file(&mut state, project_id, package_type, name, version, file_name, content);
+        }
+
+        package_id
+    }
+
+    /// File names and contents of package version
+    pub fn package_files(&self, project_id: u32, package_type: &str, name: &str,
+                         version: &str) -> Vec<(String, String)> {
+        let state = self.state.lock().unwrap();
+
+        find_package_files(&state, project_id, package_type, name, version).iter()
+            .map(|f| (f["file_name"].as_str().unwrap().to_string(), f["content"].as_str().unwrap().to_string()))
+            .collect()
+    }
+
+    /// Add user with personal namespace, its id is returned by `group_id(username)`.
+    pub fn add_user(&self, username: &str) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        let id = get_next_id(&mut state);
+        state.users.push(json!({"id": id, "username": username, "name": username}));
+
+        let namespace_id = get_next_id(&mut state);
+        state.groups.push(FakeGroup {
+            id: namespace_id,
+            parent_id: None,
+            name: username.to_string(),
+            path: username.to_string(),
+            full_path: username.to_string(),
+            kind: "user".to_string(),
+        });
+
+        id
+    }
+
+    /// Items of project sub-resource list, e.g. `remote_mirrors`.
+    pub fn project_resources(&self, project_id: u32, resource: &str) -> Vec<Value> {
+        self.resources(&format!("projects/{project_id}/{resource}"))
+    }
+
+    pub fn project_id(&self, project_full_path: &str) -> Option<u32> {
+        let state = self.state.lock().unwrap();
+        state.projects.iter().find(|p| get_project_full_path(&state, p) == project_full_path)
+            .map(|p| p.id)
+    }
+
+    /// Commit id of branch head in project repository.
+    pub fn branch_commit(&self, project_full_path: &str, branch: &str) -> String {
+        let state = self.state.lock().unwrap();
+        let project = state.projects.iter()
+            .find(|p| get_project_full_path(&state, p) == project_full_path).unwrap();
+        git(&get_repo_path(&state, project), &["rev-parse", &format!("refs/heads/{branch}")])
+    }
+
+    /// Add commit on top of branch in project repository.
+    pub fn add_commit(&self, project_id: u32, branch: &str, message: &str) -> String {
+        let state = self.state.lock().unwrap();
+        let project = state.projects.iter().find(|p| p.id == project_id).unwrap();
+        let repo_path = get_repo_path(&state, project);
+
+        let tree = git(&repo_path, &["mktree"]);
+        let branch_ref = format!("refs/heads/{branch}");
+        let commit = git(&repo_path, &["commit-tree", &tree, "-p", &branch_ref, "-m", message]);
+        git(&repo_path, &["update-ref", &branch_ref, &commit]);
+
+        commit
+    }
+
+    fn update_project<F: FnOnce(&mut FakeProject)>(&self, project_id: u32, update: F) {
+        let mut state = self.state.lock().unwrap();
+        let project = state.projects.iter_mut().find(|p| p.id == project_id).unwrap();
+        update(project);
+    }
+
+    pub fn group_id(&self, group_full_path: &str) -> Option<u32> {
+        let state = self.state.lock().unwrap();
+        state.groups.iter().find(|g| g.full_path == group_full_path).map(|g| g.id)
+    }
+
+    pub fn group_paths(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.groups.iter().filter(|g| g.kind == "group").map(|g| g.full_path.to_string()).collect()
+    }
+
+    pub fn project_paths(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.projects.iter().map(|p| get_project_full_path(&state, p)).collect()
+    }
+
+    pub fn branches(&self, project_full_path: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+
+        match state.projects.iter().find(|p| get_project_full_path(&state, p) == project_full_path) {
+            Some(project) => get_repo_branches(&get_repo_path(&state, project)),
+            None => vec![]
+        }
+    }
+
+    pub fn tags(&self, project_full_path: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+
+        match state.projects.iter().find(|p| get_project_full_path(&state, p) == project_full_path) {
+            Some(project) => get_repo_refs(&get_repo_path(&state, project), "refs/tags").into_iter()
+                .map(|(name, _)| name).collect(),
+            None => vec![]
+        }
+    }
+}
+
+fn handle_request(state: &mut FakeGitLabState, method: &str, url: &str, body: &str) -> MockResponse {
+    let url = Url::parse(&format!("http://localhost{url}")).unwrap();
+
+    let params: Vec<(String, String)> = url.query_pairs()
+        .map(|(k, v)| (k.to_string(), v.to_string())).collect();
+
+    let param = |name: &str| params.iter()
+        .find(|(k, _)| k == name).map(|(_, v)| v.to_string());
+
+    let segments: Vec<&str> = url.path().trim_start_matches("/api/v4/").split('/').collect();
+
+    let method = if method == "HEAD" { "GET" } else { method };
+
+    match (method, segments.as_slice()) {
+        ("GET", ["groups"]) => {
+            let groups: Vec<Value> = state.groups.iter().filter(|g| g.kind == "group").map(get_group_json).collect();
+            get_page(&groups, param("page"), param("per_page"))
+        }
+        ("POST", ["groups"]) => {
+            let parent_id = param("parent_id").map(|v| v.parse().unwrap());
+
+            match create_group(state, &param("name").unwrap_or_default(),
+                               &param("path").unwrap_or_default(), parent_id) {
+                Some(group) => get_json_response(201, &get_group_json(&group)),
+                None => get_json_response(400, &json!({"message": "Failed to save group"}))
+            }
+        }
+        ("GET", ["projects"]) => {
+            let projects: Vec<Value> = state.projects.iter()
+                .map(|p| get_project_json(state, p, false)).collect();
+            get_page(&projects, param("page"), param("per_page"))
+        }
+        ("POST", ["projects"]) => {
+            // projects without namespace are created in namespace of sudo user
+            let namespace_id = param("namespace_id").and_then(|id| id.parse().ok())
+                .or_else(|| param("sudo").and_then(|username| state.groups.iter()
+                    .find(|g| g.kind == "user" && g.path == username).map(|g| g.id)))
+                .unwrap_or(0);
+
+            match create_project(state, &param("name").unwrap_or_default(),
+                                 &param("path").unwrap_or_default(), namespace_id) {
+                Some(project) => get_json_response(201, &get_project_json(state, &project, false)),
+                None => get_json_response(400, &json!({"message": {"path": ["has already been taken"]}}))
+            }
+        }
+        ("GET", ["projects", id]) => {
+            match find_project(state, id) {
+                Some(project) => get_json_response(200, &get_project_json(
+                    state, &project, param("statistics").is_some())),
+                None => get_not_found()
+            }
+        }
+        ("PUT", ["projects", id]) => {
+            let body: Value = serde_json::from_str(body).unwrap_or(json!({}));
+
+            match state.projects.iter_mut().find(|p| p.id.to_string() == *id) {
+                Some(project) => {
+                    for (name, value) in body.as_object().cloned().unwrap_or_default() {
+                        project.attributes.insert(name, value);
+                    }
+
+                    let project = project.clone();
+                    get_json_response(200, &get_project_json(state, &project, false))
+                }
+                None => get_not_found()
+            }
+        }
+        ("POST", ["projects", id, "fork"]) => {
+            let body: Value = serde_json::from_str(body).unwrap_or(json!({}));
+
+            let upstream = match find_project(state, id) {
+                Some(upstream) => upstream,
+                None => return get_not_found()
+            };
+
+            let namespace_id = body["namespace_id"].as_u64().map(|id| id as u32)
+                .or_else(|| param("sudo").and_then(|username| state.groups.iter()
+                    .find(|g| g.kind == "user" && g.path == username).map(|g| g.id)))
+                .unwrap_or(0);
+
+            match create_project(state, body["name"].as_str().unwrap_or(&upstream.name),
+                                 body["path"].as_str().unwrap_or(&upstream.path), namespace_id) {
+                Some(mut fork) => {
+                    let upstream_repo_path = get_repo_path(state, &upstream);
+                    git(&get_repo_path(state, &fork), &["fetch", "--quiet",
+                        &format!("{}", upstream_repo_path.display()), "+refs/*:refs/*"]);
+
+                    fork.attributes.insert("forked_from_project".to_string(), json!({
+                        "id": upstream.id, "path_with_namespace": get_project_full_path(state, &upstream)
+                    }));
+                    fork.attributes.insert("import_status".to_string(), json!("finished"));
+
+                    let fork_json = get_project_json(state, &fork, false);
+                    state.projects.retain(|p| p.id != fork.id);
+                    state.projects.push(fork);
+
+                    get_json_response(201, &fork_json)
+                }
+                None => get_json_response(400, &json!({"message": {"path": ["has already been taken"]}}))
+            }
+        }
+        ("POST", ["projects", _, "archive" | "unarchive"]) if state.archiving_disabled =>
+            get_json_response(403, &json!({"message": "403 Forbidden"})),
+        ("POST", ["projects", id, action @ ("archive" | "unarchive")]) => {
+            match state.projects.iter_mut().find(|p| p.id.to_string() == *id) {
+                Some(project) => {
+                    project.attributes.insert("archived".to_string(), json!(*action == "archive"));
+
+                    let project = project.clone();
+                    get_json_response(201, &get_project_json(state, &project, false))
+                }
+                None => get_not_found()
+            }
+        }
+        ("DELETE", ["projects", id]) => {
+            match find_project(state, id) {
+                Some(project) => {
+                    let _ = fs::remove_dir_all(get_repo_path(state, &project));
+                    state.projects.retain(|p| p.id != project.id);
+                    get_json_response(202, &json!({"message": "202 Accepted"}))
+                }
+                None => get_not_found()
+            }
+        }
+        ("GET", ["projects", id, "repository", "branches"]) => {
+            match find_project(state, id) {
+                Some(project) => {
+                    let branches: Vec<Value> = get_repo_refs(&get_repo_path(state, &project), "refs/heads")
+                        .iter().map(|(name, commit)| json!({"name": name, "commit": {"id": commit}}))
+                        .collect();
+                    get_page(&branches, param("page"), param("per_page"))
+                }
+                None => get_not_found()
+            }
+        }
+        ("DELETE", ["projects", id, "repository", kind @ ("branches" | "tags"), name]) => {
+            let project = match find_project(state, id) {
+                Some(project) => project,
+                None => return get_not_found()
+            };
+
+            let name = get_decoded_segment(name);
+
+            if *kind == "branches" && get_project_json(state, &project, false)["default_branch"] == name.as_str() {
+                return get_json_response(400, &json!({"message": "The default branch of a project cannot be deleted."}))
+            }
+
+            let ref_name = match *kind {
+                "branches" => format!("refs/heads/{name}"),
+                _ => format!("refs/tags/{name}")
+            };
+
+            git(&get_repo_path(state, &project), &["update-ref", "-d", &ref_name]);
+            MockResponse::new(204, "")
+        }
+        ("GET", ["projects", id, "repository", "tags"]) => {
+            match find_project(state, id) {
+                Some(project) => {
+                    let tags: Vec<Value> = get_repo_refs(&get_repo_path(state, &project), "refs/tags")
+                        .iter().map(|(name, commit)| json!({"name": name, "target": commit,
+                            "commit": {"id": commit}}))
+                        .collect();
+                    get_page(&tags, param("page"), param("per_page"))
+                }
+                None => get_not_found()
+            }
+        }
+        ("GET", ["projects", id, "repository", "commits"]) => {
+            match find_project(state, id) {
+                Some(project) => {
+                    let ref_name = param("ref_name").unwrap_or("HEAD".to_string());
+                    let max_count = format!("--max-count={}", param("per_page").unwrap_or("20".to_string()));
+                    let commits: Vec<Value> = git(&get_repo_path(state, &project), &["rev-list", &max_count, &ref_name])
+                        .lines().map(|sha| json!({"id": sha})).collect();
+                    get_json_response(200, &json!(commits))
+                }
+                None => get_not_found()
+            }
+        }
+        ("GET", ["projects", id, "repository", "commits", sha]) => {
+            match find_project(state, id) {
+                Some(project) if git_commit_exists(&get_repo_path(state, &project), sha) =>
+                    get_json_response(200, &json!({"id": sha})),
+                _ => get_not_found()
+            }
+        }
+        ("GET", ["users"]) => {
+            let users: Vec<Value> = state.users.iter()
+                .filter(|u| param("username").map(|n| u["username"] == n.as_str()).unwrap_or(true))
+                .cloned().collect();
+            get_page(&users, param("page"), param("per_page"))
+        }
+        ("GET", ["user"]) => get_json_response(200, &json!({"id": 1, "username": "root", "name": "Administrator"})),
+        ("GET", ["users", user_id]) => {
+            match state.users.iter().find(|u| item_matches(u, user_id)) {
+                Some(user) => get_json_response(200, user),
+                None => get_not_found()
+            }
+        }
+        ("GET", ["projects", project_id, "uploads", secret, filename]) => {
+            match state.uploads.get(&format!("{project_id}/uploads/{secret}/{filename}")) {
+                Some(content) => MockResponse::new(200, content),
+                None => get_not_found()
+            }
+        }
+        ("POST", ["projects", project_id, "uploads"]) => {
+            let project_id: u32 = project_id.parse().unwrap();
+            let (filename, content) = get_multipart_file(body);
+            let url = add_upload(state, project_id, &filename, &content);
+
+            get_json_response(201, &json!({
+                "alt": filename, "url": url, "markdown": format!("[{filename}]({url})")
+            }))
+        }
+        ("GET", ["snippets", "all"]) => {
+            let snippets: Vec<Value> = state.resources.iter()
+                .filter(|(path, _)| *path == "snippets" || path.ends_with("/snippets"))
+                .flat_map(|(_, snippets)| snippets.iter().cloned()).collect();
+            get_page(&snippets, param("page"), param("per_page"))
+        }
+        ("POST", ["snippets"]) => {
+            let mut snippet: Value = serde_json::from_str(body).unwrap_or(json!({}));
+            snippet["id"] = json!(get_next_id(state));
+            snippet["project_id"] = Value::Null;
+            snippet["author"] = json!({"id": 1, "username": param("sudo").unwrap_or_default(), "name": ""});
+            set_snippet_file_paths(&mut snippet);
+
+            state.resources.entry("snippets".to_string()).or_default().push(snippet.clone());
+            get_json_response(201, &snippet)
+        }
+        ("GET", ["snippets", snippet_id, "raw"]) =>
+            get_snippet_content(state, &get_snippets_path(None), snippet_id, None),
+        ("GET", ["snippets", snippet_id, "files", _, file_path, "raw"]) =>
+            get_snippet_content(state, &get_snippets_path(None), snippet_id, Some(get_decoded_segment(file_path))),
+        ("GET", ["projects", project_id, "snippets", snippet_id, "raw"]) =>
+            get_snippet_content(state, &format!("projects/{project_id}/snippets"), snippet_id, None),
+        ("GET", ["projects", project_id, "snippets", snippet_id, "files", _, file_path, "raw"]) =>
+            get_snippet_content(state, &format!("projects/{project_id}/snippets"), snippet_id,
+                                Some(get_decoded_segment(file_path))),
+        ("GET", ["projects", project_id, feature, ..]) if is_feature_disabled(state, project_id, feature) =>
+            get_json_response(403, &json!({"message": "403 Forbidden"})),
+        ("GET" | "PUT", ["projects", project_id, "packages", package_type @ ("generic" | "maven"), package_path @ ..])
+            if package_path.len() >= 3 => {
+            let project_id: u32 = project_id.parse().unwrap();
+            let (file_name, package_path) = package_path.split_last().unwrap();
+            let (version, name) = package_path.split_last().unwrap();
+            let name = name.join("/");
+
+            if method == "PUT" {
+                add_package_file(state, project_id, package_type, &name, version, file_name, body);
+                return get_json_response(201, &json!({"message": "201 Created"}))
+            }
+
+            get_package_file_content(state, project_id, package_type, Some((&name, version)), file_name)
+        }
+        ("GET", ["projects", project_id, "packages", "npm", name]) => {
+            let project_id: u32 = project_id.parse().unwrap();
+            let name = get_decoded_segment(name);
+
+            let packages: Vec<Value> = find_packages(state, project_id, "npm").into_iter()
+                .filter(|p| p["name"] == name.as_str()).collect();
+
+            if packages.is_empty() {
+                return get_not_found()
+            }
+
+            let mut versions = Map::new();
+
+            for package in &packages {
+                let version = package["version"].as_str().unwrap();
+                let mut metadata = package["metadata"].clone();
+                metadata["name"] = json!(name);
+                metadata["version"] = json!(version);
+                metadata["dist"] = json!({"shasum": "0a1b", "tarball": format!("{name}-{version}.tgz")});
+                versions.insert(version.to_string(), metadata);
+            }
+
+            let latest = packages.last().unwrap()["version"].clone();
+
+            get_json_response(200, &json!({"name": name, "versions": versions, "dist-tags": {"latest": latest}}))
+        }
+        ("GET", ["projects", project_id, "packages", "npm", name, "-", file_name]) => {
+            let project_id: u32 = project_id.parse().unwrap();
+            let name = get_decoded_segment(name);
+
+            let files: Vec<Value> = find_packages(state, project_id, "npm").iter()
+                .filter(|p| p["name"] == name.as_str())
+                .flat_map(|p| find_package_files(state, project_id, "npm", &name, p["version"].as_str().unwrap()))
+                .collect();
+
+            match files.iter().find(|f| f["file_name"] == *file_name) {
+                Some(file) => MockResponse::new(200, file["content"].as_str().unwrap()),
+                None => get_not_found()
+            }
+        }
+        ("PUT", ["projects", project_id, "packages", "npm", name]) => {
+            let project_id: u32 = project_id.parse().unwrap();
+            let name = get_decoded_segment(name);
+            let publish: Value = serde_json::from_str(body).unwrap_or(json!({}));
+
+            let (file_name, attachment) = match publish["_attachments"].as_object().and_then(|a| a.iter().next()) {
+                Some(attachment) => attachment,
+                None => return get_json_response(400, &json!({"error": "attachment is missing"}))
+            };
+
+            let content = STANDARD.decode(attachment["data"].as_str().unwrap_or_default()).unwrap();
+            let (version, metadata) = publish["versions"].as_object().unwrap().iter().next().unwrap();
+
+            let package_id = add_package_file(state, project_id, "npm", &name, version, file_name,
+                                              &String::from_utf8(content).unwrap());
+            set_package_metadata(state, project_id, package_id, metadata.clone());
+
+            get_json_response(200, &json!({"message": "Package was published"}))
+        }
+        ("GET", ["projects", project_id, "packages", "pypi", "files", _, file_name]) =>
+            get_package_file_content(state, project_id.parse().unwrap(), "pypi", None, file_name),
+        ("POST", ["projects", project_id, "packages", "pypi"]) => {
+            let project_id: u32 = project_id.parse().unwrap();
+            let fields = get_multipart_fields(body);
+
+            let field = |name: &str| fields.iter().find(|(n, _, _)| n == name)
+                .map(|(_, file_name, content)| (file_name.clone(), content.to_string()));
+
+            match (field("name"), field("version"), field("content")) {
+                (Some((_, name)), Some((_, version)), Some((Some(file_name), content))) => {
+                    let package_id = add_package_file(state, project_id, "pypi", &name, &version, &file_name, &content);
+                    if let Some((_, requires_python)) = field("requires_python") {
+                        set_package_metadata(state, project_id, package_id, json!({"requires_python": requires_python}));
+                    }
+                    get_json_response(201, &json!({"message": "201 Created"}))
+                }
+                _ => get_json_response(400, &json!({"error": "name, version or content is missing"}))
+            }
+        }
+        (_, [owner @ ("projects" | "groups"), owner_id, resource_path @ ..]) => {
+            if !owner_exists(state, owner, owner_id) {
+                return get_not_found()
+            }
+
+            let path = format!("{owner}/{owner_id}/{}", resource_path.join("/"));
+            handle_resource_request(state, method, &path, body, &param)
+        }
+        _ => get_not_found()
+    }
+}
+
+fn owner_exists(state: &FakeGitLabState, owner: &str, owner_id: &str) -> bool {
+    if owner == "projects" {
+        find_project(state, owner_id).is_some()
+    } else {
+        state.groups.iter().any(|g| g.id.to_string() == owner_id)
+    }
+}
+
+/// Generic sub-resources: lists are stored by api path, items are addressed by id, iid or name.
+fn handle_resource_request<F>(state: &mut FakeGitLabState, method: &str, path: &str, body: &str,
+                              param: &F) -> MockResponse
+    where F: Fn(&str) -> Option<String> {
+    if SINGLE_RESOURCES.iter().any(|r| path.ends_with(r)) {
+        return handle_single_resource_request(state, method, path, body)
+    }
+
+    let (parent_path, item_key) = path.rsplit_once('/').unwrap();
+    let item_key = get_decoded_segment(item_key);
+
+    match method {
+        "GET" => {
+            if let Some(items) = state.resources.get(path) {
+                let items: Vec<Value> = items.iter()
+                    .filter(|item| matches_filter(item, param("state"))).cloned().collect();
+                return get_page(&items, param("page"), param("per_page"))
+            }
+
+            let item_found = state.resources.get(parent_path)
+                .and_then(|items| items.iter().find(|item| item_matches(item, &item_key)));
+
+            match item_found {
+                Some(item) => get_json_response(200, item),
+                None => get_page(&[], param("page"), param("per_page"))
+            }
+        }
+        "POST" => {
+            let mut item: Value = serde_json::from_str(body).unwrap_or(json!({}));
+            let id = get_next_id(state);
+
+            let items = state.resources.entry(path.to_string()).or_default();
+
+            item["id"] = json!(id);
+            item["iid"] = json!(items.len() + 1);
+
+            if path.ends_with("/remote_mirrors") {
+                item["url"] = json!(get_masked_url(item["url"].as_str().unwrap_or_default()));
+            }
+
+            if path.ends_with("/merge_requests") {
+                item["state"] = json!("opened");
+            }
+
+            if path.ends_with("/snippets") {
+                set_snippet_file_paths(&mut item);
+            }
+
+            for (name, value) in item.as_object().cloned().unwrap_or_default() {
+                if name.ends_with("_access_level") {
+                    item[format!("{name}s")] = json!([{"access_level": value}]);
+                }
+            }
+
+            if let Some(labels) = item["labels"].as_str() {
+                let labels: Vec<&str> = labels.split(',').filter(|l| !l.is_empty()).collect();
+                item["labels"] = json!(labels);
+            }
+
+            if path.ends_with("/discussions") {
+                item["id"] = json!(format!("{id:040x}"));
+                item["notes"] = json!([{"id": id, "body": item["body"]}]);
+            }
+
+            items.push(item.clone());
+
+            // replies are listed in discussion notes too
+            let discussion_path = path.strip_suffix("/notes").and_then(|p| p.rsplit_once('/'))
+                .filter(|(discussions_path, _)| discussions_path.ends_with("/discussions"));
+
+            if let Some((discussions_path, discussion_id)) = discussion_path {
+                let discussion = state.resources.get_mut(discussions_path)
+                    .and_then(|discussions| discussions.iter_mut().find(|d| d["id"] == discussion_id));
+
+                if let Some(discussion) = discussion {
+                    discussion["notes"].as_array_mut().unwrap().push(json!({"id": id, "body": item["body"]}));
+                }
+            }
+
+            get_json_response(201, &item)
+        }
+        "PUT" => {
+            let body: Value = serde_json::from_str(body).unwrap_or(json!({}));
+
+            let item_found = state.resources.get_mut(parent_path)
+                .and_then(|items| items.iter_mut().find(|item| item_matches(item, &item_key)));
+
+            match item_found {
+                Some(item) => {
+                    for (name, value) in body.as_object().cloned().unwrap_or_default() {
+                        item[name] = value;
+                    }
+
+                    match item["state_event"].as_str() {
+                        Some("close") => item["state"] = json!("closed"),
+                        Some("reopen") => item["state"] = json!("opened"),
+                        _ => {}
+                    }
+                    get_json_response(200, item)
+                }
+                None => get_not_found()
+            }
+        }
+        "DELETE" => {
+            let items = state.resources.entry(parent_path.to_string()).or_default();
+            let items_before = items.len();
+
+            items.retain(|item| !item_matches(item, &item_key));
+
+            if items.len() < items_before {
+                MockResponse::new(204, "")
+            } else {
+                get_not_found()
+            }
+        }
+        _ => get_not_found()
+    }
+}
+
+/// Resources without list, e.g. `projects/1/push_rule`, stored as list with one item.
+fn handle_single_resource_request(state: &mut FakeGitLabState, method: &str, path: &str,
+                                  body: &str) -> MockResponse {
+    let body: Value = serde_json::from_str(body).unwrap_or(json!({}));
+    let items = state.resources.entry(path.to_string()).or_default();
+
+    match (method, items.first_mut()) {
+        ("GET", Some(item)) => get_json_response(200, item),
+        ("GET", None) => get_json_response(200, &Value::Null),
+        ("POST", None) => {
+            items.push(body.clone());
+            get_json_response(201, &body)
+        }
+        ("PUT", Some(item)) => {
+            for (name, value) in body.as_object().cloned().unwrap_or_default() {
+                item[name] = value;
+            }
+            get_json_response(200, item)
+        }
+        ("DELETE", Some(_)) => {
+            items.clear();
+            MockResponse::new(204, "")
+        }
+        _ => get_json_response(422, &json!({"message": "unexpected request"}))
+    }
+}
+
+fn item_matches(item: &Value, key: &str) -> bool {
+    let as_key = |value: &Value| match value {
+        Value::Number(number) => Some(number.to_string()),
+        Value::String(text) => Some(text.to_string()),
+        _ => None
+    };
+
+    ["id", "iid", "name", "key"].iter().any(|field| as_key(&item[*field]).as_deref() == Some(key))
+}
+
+fn get_decoded_segment(segment: &str) -> String {
+    let url = Url::parse(&format!("http://localhost/?value={segment}")).unwrap();
+    url.query_pairs().next().map(|(_, v)| v.to_string()).unwrap_or_default()
+}
+
+/// GitLab hides credentials in urls returned by api.
+fn get_masked_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) if !url.username().is_empty() => {
+            let _ = url.set_username("*****");
+            let _ = url.set_password(Some("*****"));
+            url.to_string()
+        }
+        _ => url.to_string()
+    }
+}
+
+fn matches_filter(item: &Value, state: Option<String>) -> bool {
+    match state {
+        Some(state) if state != "all" => item["state"] == state.as_str(),
+        _ => true
+    }
+}
+
+fn get_json_response(status: u16, value: &Value) -> MockResponse {
+    MockResponse::new(status, &value.to_string())
+}
+
+fn get_not_found() -> MockResponse {
+    get_json_response(404, &json!({"message": "404 Not Found"}))
+}
+
+fn get_page(items: &[Value], page: Option<String>, per_page: Option<String>) -> MockResponse {
+    let page: usize = page.and_then(|v| v.parse().ok()).unwrap_or(1);
+    let per_page: usize = per_page.and_then(|v| v.parse().ok()).unwrap_or(20);
+
+    let page_items: Vec<&Value> = items.iter().skip((page - 1) * per_page).take(per_page).collect();
+
+    get_json_response(200, &json!(page_items))
+        .with_header("X-Total", &items.len().to_string())
+}
+
+fn create_group(state: &mut FakeGitLabState, name: &str, path: &str,
+                parent_id: Option<u32>) -> Option<FakeGroup> {
+    let full_path = match parent_id {
+        Some(id) => format!("{}/{path}", state.groups.iter().find(|g| g.id == id)?.full_path),
+        None => path.to_string()
+    };
+
+    if state.groups.iter().any(|g| g.full_path == full_path) {
+        return None
+    }
+
+    let group = FakeGroup {
+        id: get_next_id(state),
+        parent_id,
+        name: name.to_string(),
+        path: path.to_string(),
+        full_path,
+        kind: "group".to_string(),
+    };
+
+    state.groups.push(group.clone());
+
+    Some(group)
+}
+
+fn create_project(state: &mut FakeGitLabState, name: &str, path: &str,
+                  namespace_id: u32) -> Option<FakeProject> {
+    state.groups.iter().find(|g| g.id == namespace_id)?;
+
+    if state.projects.iter().any(|p| p.namespace_id == namespace_id && p.path == path) {
+        return None
+    }
+
+    let project = FakeProject {
+        id: get_next_id(state),
+        name: name.to_string(),
+        path: path.to_string(),
+        namespace_id,
+        visibility: "private".to_string(),
+        lfs_objects_size: 0,
+        attributes: json!({"description": "", "archived": false}).as_object().unwrap().clone(),
+    };
+
+    let repo_path = get_repo_path(state, &project);
+    fs::create_dir_all(&repo_path).unwrap();
+    git(&repo_path, &["init", "--bare", "--quiet"]);
+
+    state.projects.push(project.clone());
+
+    Some(project)
+}
+
+/// Project feature is disabled by `{feature}_enabled: false` attribute, `feature` is api path segment,
+/// e.g. `packages` or `registry` for `container_registry_enabled`.
+fn is_feature_disabled(state: &FakeGitLabState, project_id: &str, feature: &str) -> bool {
+    let attribute = match feature {
+        "registry" => "container_registry_enabled".to_string(),
+        _ => format!("{feature}_enabled")
+    };
+
+    find_project(state, project_id)
+        .is_some_and(|p| p.attributes.get(&attribute) == Some(&json!(false)))
+}
+
+fn find_project(state: &FakeGitLabState, id: &str) -> Option<FakeProject> {
+    let id: u32 = id.parse().ok()?;
+    state.projects.iter().find(|p| p.id == id).cloned()
+}
+
+fn add_upload(state: &mut FakeGitLabState, project_id: u32, filename: &str, content: &str) -> String {
+    let secret = format!("{:032x}", get_next_id(state));
+    let url = format!("/uploads/{secret}/{filename}");
+    state.uploads.insert(format!("{project_id}{url}"), content.to_string());
+    url
+}
+
+/// File name and content of the first part of multipart form.
+fn get_multipart_file(body: &str) -> (String, String) {
+    let filename = body.split("filename=\"").nth(1)
+        .and_then(|v| v.split('"').next()).unwrap_or_default();
+
+    let content = body.split_once("\r\n\r\n").map(|(_, v)| v).unwrap_or_default();
+    let content = content.rsplit_once("\r\n--").map(|(v, _)| v).unwrap_or(content);
+
+    (filename.to_string(), content.to_string())
+}
+
+/// Name, file name and content of every part of multipart form.
+fn get_multipart_fields(body: &str) -> Vec<(String, Option<String>, String)> {
+    let boundary = body.lines().next().unwrap_or_default().trim();
+
+    body.split(boundary).filter_map(|part| {
+        let (headers, content) = part.split_once("\r\n\r\n")?;
+
+        let get_header_param = |param: &str| headers.split(&format!("{param}=\"")).nth(1)
+            .and_then(|v| v.split('"').next()).map(|v| v.to_string());
+
+        Some((get_header_param("name")?, get_header_param("filename"),
+              content.strip_suffix("\r\n").unwrap_or(content).to_string()))
+    }).collect()
+}
+
+/// npm clients authorize with bearer token, PyPI clients with basic auth
+fn get_package_authorization_error(request: &RecordedRequest) -> Option<MockResponse> {
+    let authorization = request.header("Authorization").unwrap_or_default();
+
+    let authorized = if request.url.contains("/packages/npm/") {
+        authorization == format!("Bearer {MOCK_TOKEN}")
+    } else if request.url.contains("/packages/pypi") {
+        authorization.starts_with("Basic ")
+    } else {
+        true
+    };
+
+    if authorized { None } else { Some(get_json_response(401, &json!({"message": "401 Unauthorized"}))) }
+}
+
+fn find_packages(state: &FakeGitLabState, project_id: u32, package_type: &str) -> Vec<Value> {
+    state.resources.get(&format!("projects/{project_id}/packages")).cloned().unwrap_or_default().into_iter()
+        .filter(|p| p["package_type"] == package_type).collect()
+}
+
+fn find_package_files(state: &FakeGitLabState, project_id: u32, package_type: &str, name: &str,
+                      version: &str) -> Vec<Value> {
+    find_packages(state, project_id, package_type).iter()
+        .find(|p| p["name"] == name && p["version"] == version)
+        .and_then(|p| state.resources.get(&format!("projects/{project_id}/packages/{}/package_files", p["id"])))
+        .cloned().unwrap_or_default()
+}
+
+/// Adds package version if it's missing, returns package id.
+fn add_package_file(state: &mut FakeGitLabState, project_id: u32, package_type: &str, name: &str, version: &str,
+                    file_name: &str, content: &str) -> u32 {
+    let package_found = find_packages(state, project_id, package_type).into_iter()
+        .find(|p| p["name"] == name && p["version"] == version);
+
+    let package_id = match package_found {
+        Some(package) => package["id"].as_u64().unwrap() as u32,
+        None => {
+            let id = get_next_id(state);
+            state.resources.entry(format!("projects/{project_id}/packages")).or_default().push(json!({
+                "id": id, "name": name, "version": version, "package_type": package_type, "status": "default"
+            }));
+            id
+        }
+    };
+
+    let file_id = get_next_id(state);
+
+    state.resources.entry(format!("projects/{project_id}/packages/{package_id}/package_files")).or_default()
+        .push(json!({
+            "id": file_id, "package_id": package_id, "file_name": file_name, "size": content.len(),
+            "file_sha256": format!("{file_id:064x}"), "content": content
+        }));
+
+    package_id
+}
+
+fn set_package_metadata(state: &mut FakeGitLabState, project_id: u32, package_id: u32, metadata: Value) {
+    if let Some(packages) = state.resources.get_mut(&format!("projects/{project_id}/packages")) {
+        for package in packages.iter_mut().filter(|p| p["id"] == package_id) {
+            package["metadata"] = metadata.clone();
+        }
+    }
+}
+
+/// `name_and_version` is omitted for formats which address files by file name only
+fn get_package_file_content(state: &FakeGitLabState, project_id: u32, package_type: &str,
+                            name_and_version: Option<(&str, &str)>, file_name: &str) -> MockResponse {
+    let files: Vec<Value> = find_packages(state, project_id, package_type).iter()
+        .filter(|p| name_and_version.map(|(n, v)| p["name"] == n && p["version"] == v).unwrap_or(true))
+        .flat_map(|p| find_package_files(state, project_id, package_type, p["name"].as_str().unwrap(),
+                                         p["version"].as_str().unwrap()))
+        .collect();
+
+    match files.iter().find(|f| f["file_name"] == file_name) {
+        Some(file) => MockResponse::new(200, file["content"].as_str().unwrap()),
+        None => get_not_found()
+    }
+}
+
+fn get_snippets_path(project_id: Option<u32>) -> String {
+    match project_id {
+        Some(project_id) => format!("projects/{project_id}/snippets"),
+        None => "snippets".to_string()
+    }
+}
+
+/// Snippet files are created with `file_path` and listed with `path`
+fn set_snippet_file_paths(snippet: &mut Value) {
+    if let Some(files) = snippet["files"].as_array_mut() {
+        for file in files {
+            file["path"] = file["file_path"].take();
+            file.as_object_mut().unwrap().remove("file_path");
+        }
+    }
+}
+
+/// Raw content of snippet or of its file
+fn get_snippet_content(state: &FakeGitLabState, snippets_path: &str, snippet_id: &str,
+                       file_path: Option<String>) -> MockResponse {
+    let snippet_found = state.resources.get(snippets_path)
+        .and_then(|snippets| snippets.iter().find(|s| item_matches(s, snippet_id)));
+
+    let content_holder = snippet_found.and_then(|snippet| match &file_path {
+        Some(file_path) => snippet["files"].as_array()
+            .and_then(|files| files.iter().find(|f| f["path"] == file_path.as_str())),
+        None => Some(snippet)
+    });
+
+    let content = content_holder.and_then(|holder| match holder["content_bytes"].as_array() {
+        Some(bytes) => Some(bytes.iter().filter_map(|b| b.as_u64()).map(|b| b as u8).collect()),
+        None => holder["content"].as_str().map(|content| content.as_bytes().to_vec())
+    });
+
+    match content {
+        Some(content) => MockResponse::from_bytes(200, content),
+        None => get_not_found()
+    }
+}
+
+fn get_next_id(state: &mut FakeGitLabState) -> u32 {
+    let id = state.next_id;
+    state.next_id += 1;
+    id
+}
+
+fn get_group_json(group: &FakeGroup) -> Value {
+    json!({
+        "id": group.id,
+        "parent_id": group.parent_id,
+        "name": group.name,
+        "path": group.path,
+        "full_path": group.full_path,
+        "visibility": "private",
+    })
+}
+
+fn get_project_json(state: &FakeGitLabState, project: &FakeProject, statistics: bool) -> Value {
+    let group = state.groups.iter().find(|g| g.id == project.namespace_id).unwrap();
+
+    let repo_path = get_repo_path(state, project);
+    let branches = get_repo_branches(&repo_path);
+
+    let head_branch = if repo_path.exists() {
+        git(&repo_path, &["symbolic-ref", "--short", "HEAD"])
+    } else {
+        "".to_string()
+    };
+
+    // same fallback as gitaly uses when HEAD points to missing branch
+    let default_branch = [head_branch.as_str(), "main", "master"].iter()
+        .find(|b| branches.iter().any(|branch| branch == *b))
+        .map(|b| b.to_string())
+        .or_else(|| branches.first().cloned());
+
+    let mut project_json = json!({
+        "id": project.id,
+        "name": project.name,
+        "path": project.path,
+        "path_with_namespace": get_project_full_path(state, project),
+        "visibility": project.visibility,
+        "namespace": {
+            "id": group.id,
+            "name": group.name,
+            "path": group.path,
+            "full_path": group.full_path,
+            "kind": group.kind,
+        },
+        "default_branch": default_branch,
+        "empty_repo": branches.is_empty(),
+    });
+
+    for (name, value) in project.attributes.iter().filter(|(name, _)| *name != "statistics") {
+        project_json[name] = value.clone();
+    }
+
+    if statistics {
+        let mut statistics_json = project.attributes.get("statistics").cloned().unwrap_or(json!({}));
+        statistics_json["lfs_objects_size"] = json!(project.lfs_objects_size);
+        project_json["statistics"] = statistics_json;
+    }
+
+    project_json
+}
+
+fn get_project_full_path(state: &FakeGitLabState, project: &FakeProject) -> String {
+    let group = state.groups.iter().find(|g| g.id == project.namespace_id).unwrap();
+    format!("{}/{}", group.full_path, project.path)
+}
+
+fn get_repo_path(state: &FakeGitLabState, project: &FakeProject) -> PathBuf {
+    state.repos_path.join(format!("{}.git", get_project_full_path(state, project)))
+}
+
+fn get_repo_branches(repo_path: &Path) -> Vec<String> {
+    get_repo_refs(repo_path, "refs/heads").into_iter().map(|(name, _)| name).collect()
+}
+
+/// Ref names with commit ids (peeled for annotated tags) under `prefix`.
+fn get_repo_refs(repo_path: &Path, prefix: &str) -> Vec<(String, String)> {
+    if !repo_path.exists() {
+        return vec![]
+    }
+
+    git(repo_path, &["for-each-ref", "--format=%(refname:short) %(objectname) %(*objectname)", prefix])
+        .lines().filter(|l| !l.is_empty())
+        .map(|l| {
+            let parts: Vec<&str> = l.split(' ').collect();
+            let commit = parts.get(2).filter(|c| !c.is_empty()).unwrap_or(&parts[1]);
+            (parts[0].to_string(), commit.to_string())
+        })
+        .collect()
+}
+
+/// Run git inside repository with fixed identity, returns trimmed stdout.
+fn git_commit_exists(repo_path: &Path, sha: &str) -> bool {
+    Command::new("git")
+        .args(["cat-file", "-e", &format!("{sha}^{{commit}}")])
+        .current_dir(repo_path)
+        .stdin(Stdio::null())
+        .output().map(|output| output.status.success()).unwrap_or(false)
+}
+
+pub fn git(repo_path: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo_path)
+        .env("GIT_AUTHOR_NAME", "gmt")
+        .env("GIT_AUTHOR_EMAIL", "gmt@localhost")
+        .env("GIT_COMMITTER_NAME", "gmt")
+        .env("GIT_COMMITTER_EMAIL", "gmt@localhost")
+        .stdin(Stdio::null())
+        .output().unwrap();
+
+    assert!(output.status.success(), "git {:?} failed: {}", args,
+            String::from_utf8_lossy(&output.stderr));
+
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
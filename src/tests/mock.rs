@@ -0,0 +1,181 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+
+use tiny_http::{Header, Response, Server};
+
+use crate::config::InstanceConfig;
+
+pub const MOCK_TOKEN: &str = "mock-token";
+
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    pub body: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: &str) -> MockResponse {
+        MockResponse::from_bytes(status, body.as_bytes().to_vec())
+    }
+
+    pub fn from_bytes(status: u16, body: Vec<u8>) -> MockResponse {
+        MockResponse { status, body, headers: vec![] }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> MockResponse {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+struct MockRoute {
+    method: String,
+    url: String,
+    status: u16,
+    body: String,
+}
+
+/// Minimal HTTP server for API tests.
+///
+/// Routes registered later take precedence. A route url with query string is
+/// compared with the full request url, otherwise only the path is compared.
+pub struct MockServer {
+    pub url: String,
+    server: Arc<Server>,
+    routes: Arc<Mutex<Vec<MockRoute>>>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    pub fn start() -> MockServer {
+        let routes: Arc<Mutex<Vec<MockRoute>>> = Arc::new(Mutex::new(vec![]));
+
+        let handler_routes = routes.clone();
+
+        let mut server = serve(move |method, url, _| {
+            let path = url.split('?').next().unwrap_or(url);
+
+            let routes = handler_routes.lock().unwrap();
+
+            let route_found = routes.iter().rev()
+                .find(|r| r.method == method &&
+                    (r.url == url || (!r.url.contains('?') && r.url == path)));
+
+            match route_found {
+                Some(route) => MockResponse::new(route.status, &route.body),
+                None => MockResponse::new(404, r#"{"message":"404 Not Found"}"#)
+            }
+        });
+
+        server.routes = routes;
+        server
+    }
+
+    pub fn mock(&self, method: &str, url: &str, status: u16, body: &str) {
+        self.routes.lock().unwrap().push(MockRoute {
+            method: method.to_string(),
+            url: url.to_string(),
+            status,
+            body: body.to_string(),
+        });
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn instance(&self) -> InstanceConfig {
+        InstanceConfig {
+            public_url: self.url.to_string(),
+            git_url: "".to_string(),
+            token: MOCK_TOKEN.to_string(),
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Start server on random local port, every request is answered by `handler(method, url, body)`.
+pub fn serve<F>(handler: F) -> MockServer
+    where F: Fn(&str, &str, &str) -> MockResponse + Send + 'static {
+    serve_requests(move |request| handler(&request.method, &request.url, &request.body))
+}
+
+/// Start server on random local port, every request is answered by `handler(request)`.
+pub fn serve_requests<F>(handler: F) -> MockServer
+    where F: Fn(&RecordedRequest) -> MockResponse + Send + 'static {
+    let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+
+    let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+
+    let requests: Arc<Mutex<Vec<RecordedRequest>>> = Arc::new(Mutex::new(vec![]));
+
+    let thread_server = server.clone();
+    let thread_requests = requests.clone();
+
+    let handle = thread::spawn(move || {
+        for mut request in thread_server.incoming_requests() {
+            let mut body = String::new();
+            let _ = request.as_reader().read_to_string(&mut body);
+
+            let recorded_request = RecordedRequest {
+                method: request.method().to_string(),
+                url: request.url().to_string(),
+                body,
+                headers: request.headers().iter()
+                    .map(|h| (h.field.to_string(), h.value.to_string())).collect(),
+            };
+
+            thread_requests.lock().unwrap().push(recorded_request.clone());
+
+            let mock_response = handler(&recorded_request);
+
+            let mut response = Response::from_data(mock_response.body)
+                .with_status_code(mock_response.status);
+
+            if !mock_response.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("Content-Type")) {
+                response.add_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+            }
+
+            for (name, value) in mock_response.headers {
+                response.add_header(Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap());
+            }
+
+            let _ = request.respond(response);
+        }
+    });
+
+    MockServer {
+        url,
+        server,
+        routes: Arc::new(Mutex::new(vec![])),
+        requests,
+        handle: Some(handle),
+    }
+}
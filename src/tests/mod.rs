@@ -1,6 +1,38 @@
+use std::path::Path;
+
 use log::LevelFilter;
 
+use crate::config::{AppConfig, ArchivedProjectsPolicy, CollisionsConfig, ErrorHandlersConfig, InstanceConfig, LogFormat, MetricsConfig, MigrationConfig, MirrorConfig, PersonalProjectsConfig, PhasesConfig, UsersConfig};
+
+pub mod gitlab;
+pub mod mock;
+pub mod registry;
+
 pub fn init_logging() {
     let _ = env_logger::builder().filter_level(LevelFilter::Debug)
         .is_test(true).try_init();
-}
\ No newline at end of file
+}
+
+pub fn get_test_config(source: InstanceConfig, target: InstanceConfig, tmp_dir: &Path) -> AppConfig {
+    AppConfig {
+        log_level: "debug".to_string(),
+        log_format: LogFormat::Text,
+        git_bin_path: "git".to_string(),
+        tmp_dir: format!("{}", tmp_dir.display()),
+        source,
+        target,
+        migration: MigrationConfig {
+            ignore_empty_repos: false,
+            collisions: CollisionsConfig::default(),
+            personal_projects: PersonalProjectsConfig::default(),
+            archived: ArchivedProjectsPolicy::Migrate,
+        },
+        error_handlers: ErrorHandlersConfig {
+            remove_target_repo_after_clone_error: false,
+        },
+        mirror: MirrorConfig::default(),
+        users: UsersConfig::default(),
+        phases: PhasesConfig::default(),
+        metrics: MetricsConfig::default(),
+    }
+}
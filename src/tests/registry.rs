@@ -0,0 +1,227 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use reqwest::Url;
+use serde_json::json;
+
+use crate::tests::mock::{MockResponse, MockServer, RecordedRequest, serve_requests};
+
+pub const MANIFEST_MEDIA_TYPE: &str = "application/vnd.docker.distribution.manifest.v2+json";
+
+pub const MANIFEST_LIST_MEDIA_TYPE: &str = "application/vnd.docker.distribution.manifest.list.v2+json";
+
+#[derive(Default)]
+struct FakeRegistryState {
+    next_upload_id: u32,
+    issued_tokens: u32,
+    /// Tokens which aren't expired yet
+    valid_tokens: Vec<String>,
+    /// `expires_in` of issued tokens in seconds, GitLab default is used if not set
+    token_lifetime: Option<u64>,
+    /// Content by `{repository}@{digest}`
+    blobs: BTreeMap<String, String>,
+    /// Media type and content by `{repository}:{tag or digest}`
+    manifests: BTreeMap<String, (String, String)>,
+}
+
+/// In-process container registry stand-in, implements parts of registry HTTP API v2 used for image copy.
+///
+/// Requests are authorized with bearer token issued by `/jwt/auth` like GitLab does,
+/// every token is unique and stays valid until `expire_tokens`. Digests aren't verified.
+pub struct FakeRegistry {
+    server: MockServer,
+    state: Arc<Mutex<FakeRegistryState>>,
+}
+
+impl FakeRegistry {
+    pub fn start() -> FakeRegistry {
+        let state = Arc::new(Mutex::new(FakeRegistryState::default()));
+
+        let handler_state = state.clone();
+        let url: Arc<Mutex<String>> = Arc::new(Mutex::new(String::new()));
+        let handler_url = url.clone();
+
+        let server = serve_requests(move |request| {
+            let mut state = handler_state.lock().unwrap();
+            handle_request(&mut state, &handler_url.lock().unwrap(), request)
+        });
+
+        *url.lock().unwrap() = server.url.to_string();
+
+        FakeRegistry { server, state }
+    }
+
+    pub fn url(&self) -> String {
+        self.server.url.to_string()
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.server.requests()
+    }
+
+    /// Issued tokens are rejected from now on
+    pub fn expire_tokens(&self) {
+        self.state.lock().unwrap().valid_tokens.clear();
+    }
+
+    pub fn set_token_lifetime(&self, seconds: u64) {
+        self.state.lock().unwrap().token_lifetime = Some(seconds);
+    }
+
+    /// Add image with config and layers of declared `layer_size`, returns manifest digest.
+    pub fn add_image(&self, repository: &str, tag: &str, layers: &[&str], layer_size: u64) -> String {
+        let mut state = self.state.lock().unwrap();
+
+        let config = format!("{{\"architecture\":\"amd64\",\"tag\":\"{tag}\"}}");
+        let config_digest = get_digest(&config);
+        state.blobs.insert(format!("{repository}@{config_digest}"), config.to_string());
+
+        let mut layer_descriptors = vec![];
+
+        for layer in layers {
+            let layer_digest = get_digest(layer);
+            state.blobs.insert(format!("{repository}@{layer_digest}"), layer.to_string());
+
+            layer_descriptors.push(json!({
+                "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip",
+                "digest": layer_digest, "size": layer_size
+            }));
+        }
+
+        let manifest = json!({
+            "schemaVersion": 2, "mediaType": MANIFEST_MEDIA_TYPE,
+            "config": {"mediaType": "application/vnd.docker.container.image.v1+json",
+                       "digest": config_digest, "size": config.len()},
+            "layers": layer_descriptors
+        }).to_string();
+
+        let digest = get_digest(&manifest);
+
+        state.manifests.insert(format!("{repository}:{tag}"), (MANIFEST_MEDIA_TYPE.to_string(), manifest.to_string()));
+        state.manifests.insert(format!("{repository}:{digest}"), (MANIFEST_MEDIA_TYPE.to_string(), manifest));
+
+        digest
+    }
+
+    /// Add multi-platform image referencing manifests by digest.
+    pub fn add_image_list(&self, repository: &str, tag: &str, manifest_digests: &[&str]) {
+        let mut state = self.state.lock().unwrap();
+
+        let manifests: Vec<_> = manifest_digests.iter()
+            .map(|digest| json!({"mediaType": MANIFEST_MEDIA_TYPE, "digest": digest, "size": 100}))
+            .collect();
+
+        let manifest = json!({
+            "schemaVersion": 2, "mediaType": MANIFEST_LIST_MEDIA_TYPE, "manifests": manifests
+        }).to_string();
+
+        state.manifests.insert(format!("{repository}:{tag}"), (MANIFEST_LIST_MEDIA_TYPE.to_string(), manifest));
+    }
+
+    /// Media type and content of manifest by tag or digest
+    pub fn manifest(&self, repository: &str, reference: &str) -> Option<(String, String)> {
+        let state = self.state.lock().unwrap();
+        state.manifests.get(&format!("{repository}:{reference}")).cloned()
+    }
+
+    pub fn blobs(&self, repository: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        let prefix = format!("{repository}@");
+
+        state.blobs.iter().filter(|(key, _)| key.starts_with(&prefix))
+            .map(|(_, content)| content.to_string()).collect()
+    }
+}
+
+fn handle_request(state: &mut FakeRegistryState, registry_url: &str, request: &RecordedRequest) -> MockResponse {
+    let url = Url::parse(&format!("http://localhost{}", request.url)).unwrap();
+
+    let param = |name: &str| url.query_pairs()
+        .find(|(k, _)| k == name).map(|(_, v)| v.to_string());
+
+    if url.path() == "/jwt/auth" {
+        return match request.header("Authorization") {
+            Some(authorization) if authorization.starts_with("Basic ") => {
+                state.issued_tokens += 1;
+                let token = format!("registry-token-{}", state.issued_tokens);
+                state.valid_tokens.push(token.to_string());
+
+                MockResponse::new(200, &json!({
+                    "token": token, "expires_in": state.token_lifetime.unwrap_or(300)
+                }).to_string())
+            }
+            _ => MockResponse::new(401, "{}")
+        }
+    }
+
+    let authorized = request.header("Authorization").and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| state.valid_tokens.iter().any(|t| t == token)).unwrap_or(false);
+
+    let path = match url.path().strip_prefix("/v2/") {
+        Some(path) if authorized => path.to_string(),
+        Some(_) => return MockResponse::new(401, r#"{"errors":[{"code":"UNAUTHORIZED"}]}"#)
+            .with_header("WWW-Authenticate",
+                         &format!("Bearer realm=\"{registry_url}/jwt/auth\",service=\"container_registry\"")),
+        None => return MockResponse::new(404, "{}")
+    };
+
+    if path.is_empty() {
+        return MockResponse::new(200, "{}")
+    }
+
+    let method = request.method.as_str();
+
+    if let Some((repository, reference)) = path.split_once("/manifests/") {
+        let key = format!("{repository}:{reference}");
+
+        return match method {
+            "GET" | "HEAD" => match state.manifests.get(&key) {
+                Some((media_type, content)) => MockResponse::new(200, content)
+                    .with_header("Content-Type", media_type),
+                None => MockResponse::new(404, r#"{"errors":[{"code":"MANIFEST_UNKNOWN"}]}"#)
+            },
+            "PUT" => {
+                let media_type = request.header("Content-Type").unwrap_or_default().to_string();
+                state.manifests.insert(key, (media_type, request.body.to_string()));
+                MockResponse::new(201, "")
+            }
+            _ => MockResponse::new(405, "{}")
+        }
+    }
+
+    if let Some((repository, upload_id)) = path.split_once("/blobs/uploads/") {
+        return match method {
+            "POST" => {
+                state.next_upload_id += 1;
+                MockResponse::new(202, "")
+                    .with_header("Location", &format!("/v2/{repository}/blobs/uploads/{}?state=x", state.next_upload_id))
+            }
+            "PUT" if !upload_id.is_empty() => match param("digest") {
+                Some(digest) => {
+                    state.blobs.insert(format!("{repository}@{digest}"), request.body.to_string());
+                    MockResponse::new(201, "")
+                }
+                None => MockResponse::new(400, r#"{"errors":[{"code":"DIGEST_INVALID"}]}"#)
+            },
+            _ => MockResponse::new(405, "{}")
+        }
+    }
+
+    if let Some((repository, digest)) = path.split_once("/blobs/") {
+        return match (method, state.blobs.get(&format!("{repository}@{digest}"))) {
+            ("GET" | "HEAD", Some(content)) => MockResponse::new(200, content)
+                .with_header("Content-Type", "application/octet-stream"),
+            _ => MockResponse::new(404, r#"{"errors":[{"code":"BLOB_UNKNOWN"}]}"#)
+        }
+    }
+
+    MockResponse::new(404, "{}")
+}
+
+fn get_digest(content: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!("sha256:{:064x}", hasher.finish())
+}
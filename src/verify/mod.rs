@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use log::{info, warn};
+use reqwest::blocking::Client;
+
+use crate::config::{AppConfig, InstanceConfig};
+use crate::migration::api::get_total_count;
+use crate::migration::domain::GitLabProject;
+use crate::migration::project::{get_project_branch_list, get_project_statistics, get_project_tags};
+use crate::migration::scope::get_scoped_projects;
+
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    /// Compare visibility, off by default as target projects are created as private by `migrate`
+    pub compare_visibility: bool,
+
+    /// Compare issue and merge request counts
+    pub compare_counts: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectVerification {
+    /// Full path of source project
+    pub project_path: String,
+
+    pub source_project_id: u32,
+
+    pub target_project_id: Option<u32>,
+
+    /// Full path of target project, differs from source path for renamed, quarantined and personal projects
+    pub target_project_path: Option<String>,
+
+    pub mismatches: Vec<String>,
+}
+
+impl ProjectVerification {
+    pub fn is_verified(&self) -> bool {
+        self.target_project_id.is_some() && self.mismatches.is_empty()
+    }
+}
+
+/// Compare every source project in migration scope with its copy on target instance.
+pub fn verify_migration(client: &Client, config: &AppConfig,
+                        options: &VerifyOptions) -> anyhow::Result<Vec<ProjectVerification>> {
+    let source = &config.source;
+    let target = &config.target;
+
+    info!("verifying migration from '{}' to '{}'..", source.public_url, target.public_url);
+
+    let scoped_projects = get_scoped_projects(client, config)?;
+
+    let mut results: Vec<ProjectVerification> = vec![];
+
+    for scoped_project in &scoped_projects {
+        let project_path = scoped_project.get_full_path();
+        let source_project = &scoped_project.source_project;
+
+        let verification = match &scoped_project.target_project {
+            Some(target_project) => {
+                let mismatches = compare_projects(client, source, target, source_project,
+                                                  target_project, options)
+                    .context(format!("unable to verify project '{project_path}'"))?;
+
+                ProjectVerification {
+                    project_path, source_project_id: source_project.id,
+                    target_project_id: Some(target_project.id),
+                    target_project_path: Some(format!("{}/{}", target_project.namespace.full_path,
+                                                      target_project.path)),
+                    mismatches,
+                }
+            }
+            None => ProjectVerification {
+                project_path, source_project_id: source_project.id, target_project_id: None,
+                target_project_path: None,
+                mismatches: vec!["project wasn't found on target instance".to_string()],
+            }
+        };
+
+        info!("project '{}' verified: {}", verification.project_path, verification.is_verified());
+
+        results.push(verification);
+    }
+
+    Ok(results)
+}
+
+fn compare_projects(client: &Client, source: &InstanceConfig, target: &InstanceConfig,
+                    source_project: &GitLabProject, target_project: &GitLabProject,
+                    options: &VerifyOptions) -> anyhow::Result<Vec<String>> {
+    let mut mismatches: Vec<String> = vec![];
+
+    let source_branches = get_project_branch_list(client, source, source_project.id)?.into_iter()
+        .map(|b| (b.name, b.commit.id)).collect();
+    let target_branches = get_project_branch_list(client, target, target_project.id)?.into_iter()
+        .map(|b| (b.name, b.commit.id)).collect();
+
+    mismatches.append(&mut compare_refs("branch", &source_branches, &target_branches));
+
+    let source_tags = get_project_tags(client, source, source_project.id)?.into_iter()
+        .map(|t| (t.name, t.commit.id)).collect();
+    let target_tags = get_project_tags(client, target, target_project.id)?.into_iter()
+        .map(|t| (t.name, t.commit.id)).collect();
+
+    mismatches.append(&mut compare_refs("tag", &source_tags, &target_tags));
+
+    if source_project.default_branch != target_project.default_branch {
+        mismatches.push(format!("default branch: source '{}', target '{}'",
+                                source_project.default_branch.as_deref().unwrap_or("-"),
+                                target_project.default_branch.as_deref().unwrap_or("-")));
+    }
+
+    if options.compare_visibility && source_project.visibility != target_project.visibility {
+        mismatches.push(format!("visibility: source '{}', target '{}'",
+                                source_project.visibility, target_project.visibility));
+    }
+
+    // LFS object count isn't exposed by API, total size of LFS objects is compared instead
+    let source_statistics = get_project_statistics(client, source, source_project.id)?;
+    let target_statistics = get_project_statistics(client, target, target_project.id)?;
+
+    match (source_statistics, target_statistics) {
+        (Some(source_statistics), Some(target_statistics)) => {
+            if source_statistics.lfs_objects_size != target_statistics.lfs_objects_size {
+                mismatches.push(format!("lfs objects size: source {}, target {}",
+                                        source_statistics.lfs_objects_size, target_statistics.lfs_objects_size));
+            }
+        }
+        _ => warn!("project statistics are not available for '{}', lfs objects are not compared",
+                   source_project.path)
+    }
+
+    if options.compare_counts {
+        for resource in ["issues", "merge_requests"] {
+            let source_count = get_total_count(client, source,
+                &format!("projects/{}/{resource}?state=all", source_project.id))?;
+            let target_count = get_total_count(client, target,
+                &format!("projects/{}/{resource}?state=all", target_project.id))?;
+
+            if source_count != target_count {
+                mismatches.push(format!("{} count: source {source_count}, target {target_count}",
+                                        resource.replace('_', " ")));
+            }
+        }
+    }
+
+    Ok(mismatches)
+}
+
+/// Compare refs by name and commit id.
+fn compare_refs(kind: &str, source_refs: &BTreeMap<String, String>,
+                target_refs: &BTreeMap<String, String>) -> Vec<String> {
+    let mut mismatches: Vec<String> = vec![];
+
+    for (name, source_commit) in source_refs {
+        match target_refs.get(name) {
+            Some(target_commit) => {
+                if source_commit != target_commit {
+                    mismatches.push(format!("{kind} '{name}': source {}, target {}",
+                                            get_short_sha(source_commit), get_short_sha(target_commit)));
+                }
+            }
+            None => mismatches.push(format!("{kind} '{name}': source {}, target missing",
+                                            get_short_sha(source_commit)))
+        }
+    }
+
+    for (name, target_commit) in target_refs {
+        if !source_refs.contains_key(name) {
+            mismatches.push(format!("{kind} '{name}': source missing, target {}",
+                                    get_short_sha(target_commit)));
+        }
+    }
+
+    mismatches
+}
+
+fn get_short_sha(sha: &str) -> &str {
+    &sha[..sha.len().min(8)]
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::blocking::ClientBuilder;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::migration::migrate_gitlab_instance;
+    use crate::tests::gitlab::FakeGitLab;
+    use crate::tests::{get_test_config, init_logging};
+    use crate::verify::{verify_migration, VerifyOptions};
+
+    const OPTIONS: VerifyOptions = VerifyOptions { compare_visibility: false, compare_counts: false };
+
+    #[test]
+    fn migrated_projects_should_be_verified() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        // default branch isn't the one target instance falls back to
+        let api_id = source.add_project(group_id, "api", &["develop", "main"]);
+        source.add_tag(api_id, "v1.0", "main");
+
+        let target = FakeGitLab::start();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+        migrate_gitlab_instance(&config).unwrap();
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let results = verify_migration(&client, &config, &OPTIONS).unwrap();
+
+        assert_eq!(1, results.len());
+        assert!(results.first().unwrap().is_verified(), "{:?}", results);
+    }
+
+    #[test]
+    fn differences_should_be_reported() {
+        init_logging();
+
+        let source = FakeGitLab::start();
+        let group_id = source.add_group("Backend", None);
+        let api_id = source.add_project(group_id, "api", &["main", "develop"]);
+        source.add_tag(api_id, "v1.0", "main");
+        source.set_visibility(api_id, "internal");
+        source.add_project_resource(api_id, "issues", json!({"id": 1, "state": "opened"}));
+        source.add_project(group_id, "web", &["main"]);
+
+        let target = FakeGitLab::start();
+        let target_group_id = target.add_group("Backend", None);
+        let target_api_id = target.add_project(target_group_id, "api", &["main"]);
+        target.add_commit(target_api_id, "main", "diverged");
+
+        let tmp_dir = TempDir::new().unwrap();
+        let config = get_test_config(source.instance(), target.instance(), tmp_dir.path());
+
+        let client = ClientBuilder::new().build().unwrap();
+
+        let options = VerifyOptions { compare_visibility: true, compare_counts: true };
+
+        let results = verify_migration(&client, &config, &options).unwrap();
+
+        let api = results.iter().find(|r| r.project_path == "backend/api").unwrap();
+        let mismatches = api.mismatches.join("\n");
+
+        assert!(!api.is_verified());
+        assert!(mismatches.contains("branch 'main': source"), "{mismatches}");
+        assert!(mismatches.contains("branch 'develop': source"), "{mismatches}");
+        assert!(mismatches.contains("tag 'v1.0'"), "{mismatches}");
+        assert!(mismatches.contains("visibility: source 'internal', target 'private'"), "{mismatches}");
+        assert!(mismatches.contains("issues count: source 1, target 0"), "{mismatches}");
+
+        let web = results.iter().find(|r| r.project_path == "backend/web").unwrap();
+        assert!(web.target_project_id.is_none());
+        assert!(!web.is_verified());
+    }
+}